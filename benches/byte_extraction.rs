@@ -0,0 +1,65 @@
+// cargo bench --bench byte_extraction
+//
+// times the ways CompressorBuffer::read_byte could take the low byte of a shifted accumulator:
+// truncating with a cast, indexing to_be_bytes, or masking off the low bits before casting
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const LEN: usize = 1 << 20;
+const ROUNDS: u32 = 50;
+
+fn time_extracting(accumulators: &[u64], extract: fn(u64) -> u8) -> Duration {
+    let mut fastest = Duration::MAX;
+
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+
+        let mut sum = 0u8;
+        for &accumulator in accumulators {
+            sum = sum.wrapping_add(extract(black_box(accumulator)));
+        }
+
+        black_box(sum);
+        fastest = fastest.min(start.elapsed());
+    }
+
+    fastest
+}
+
+fn cast(accumulator: u64) -> u8 {
+    accumulator as u8
+}
+
+fn to_be_bytes(accumulator: u64) -> u8 {
+    accumulator.to_be_bytes()[7]
+}
+
+fn mask(accumulator: u64) -> u8 {
+    (accumulator & ((1 << 8) - 1)) as u8
+}
+
+fn main() {
+    // a cheap xorshift so the accumulators aren't predictable
+    let mut state = 0x2545F4914F6CDD1Du64;
+    let accumulators: Vec<u64> = (0..LEN)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+        .collect();
+
+    for (name, extract) in [
+        ("cast", cast as fn(u64) -> u8),
+        ("to_be_bytes", to_be_bytes),
+        ("mask", mask),
+    ] {
+        println!(
+            "{:<12} {:?} per MiB",
+            name,
+            time_extracting(&accumulators, extract)
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use crate::compressor::Compressor;
+use crate::decode_tree::DecodeTree;
+use crate::decompressor::{DecodeError, Decompressor};
+use crate::format::FormatError;
+use crate::frequencies::count_frequencies;
+use crate::huffman_table::HuffmanTable;
+
+// the byte starting each block, saying which table its payload is compressed with
+const END_MARKER: u8 = 0x00;
+const NEW_TABLE_MARKER: u8 = 0x01;
+const SAME_TABLE_MARKER: u8 = 0x02;
+
+// compresses every block_size bytes with a table built from just that block, for input whose
+// byte distribution drifts. each block is written as:
+// - a marker: NEW_TABLE_MARKER followed by the serialized table, or SAME_TABLE_MARKER to reuse
+//   the previous block's table
+// - the block's uncompressed length and then its payload length, as big-endian u32s
+// - the payload, padded to a byte boundary. a block of a single distinct byte has no payload
+//   since its length is all that's needed to rebuild it
+// the output ends with END_MARKER
+pub struct AdaptiveCompressor {
+    block_size: usize,
+    switch_threshold: Option<f64>,
+}
+
+impl AdaptiveCompressor {
+    // block_size must be more than 0 and fit in a u32
+    pub fn new(block_size: usize) -> AdaptiveCompressor {
+        assert!(
+            block_size > 0 && u32::try_from(block_size).is_ok(),
+            "block_size must be between 1 and u32::MAX"
+        );
+
+        AdaptiveCompressor {
+            block_size,
+            switch_threshold: None,
+        }
+    }
+
+    // keeps compressing with the current table until a block's byte distribution drifts more
+    // than threshold bits per byte away from the distribution of the block the table was built
+    // from, measured as the KL divergence between them. a block with a byte the table has no code
+    // for always gets a new table
+    pub fn with_switch_threshold(mut self, threshold: f64) -> AdaptiveCompressor {
+        self.switch_threshold = Some(threshold);
+        self
+    }
+
+    pub fn compress(&self, src: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        // the table in use and the frequencies it was built from
+        let mut current: Option<(HuffmanTable, [u64; 256])> = None;
+
+        for block in src.chunks(self.block_size) {
+            let freqs = count_frequencies(block);
+            let block_table = HuffmanTable::from_frequencies(&freqs);
+
+            // tables built from frequencies are canonical, so equal lengths mean equal codes
+            let keep_current_table =
+                current
+                    .as_ref()
+                    .is_some_and(|(current_table, current_freqs)| {
+                        current_table.same_lengths(&block_table)
+                            || self.switch_threshold.is_some_and(|threshold| {
+                                kl_divergence(&freqs, current_freqs) <= threshold
+                            })
+                    });
+
+            if keep_current_table {
+                output.push(SAME_TABLE_MARKER);
+            } else {
+                output.push(NEW_TABLE_MARKER);
+                output.extend(block_table.serialize_checked());
+                current = Some((block_table, freqs));
+            }
+
+            let (table, _) = current.as_ref().unwrap();
+
+            let mut payload = Vec::new();
+            if !has_single_code(table) {
+                let mut compressor = Compressor::new(table);
+                for &byte in block {
+                    // the table was either built from block or kept for having a code for every
+                    // byte in it
+                    compressor
+                        .compress_byte(byte)
+                        .expect("byte missing from its table");
+                    compressor.drain_into(&mut payload);
+                }
+                compressor.end();
+                compressor.drain_into(&mut payload);
+            }
+
+            output.extend((block.len() as u32).to_be_bytes());
+            output.extend((payload.len() as u32).to_be_bytes());
+            output.extend(payload);
+        }
+
+        output.push(END_MARKER);
+        output
+    }
+
+    pub fn decompress(bytes: &[u8]) -> Result<Vec<u8>, FormatError> {
+        let mut output = Vec::new();
+        let mut rest = bytes;
+        let mut table: Option<HuffmanTable> = None;
+
+        loop {
+            let (&marker, after_marker) = rest.split_first().ok_or(FormatError::Truncated)?;
+            rest = after_marker;
+
+            match marker {
+                END_MARKER => return Ok(output),
+                NEW_TABLE_MARKER => {
+                    let (new_table, table_len) = HuffmanTable::deserialize_checked(rest)?;
+                    rest = &rest[table_len..];
+                    table = Some(new_table);
+                }
+                SAME_TABLE_MARKER if table.is_some() => {}
+                marker => return Err(FormatError::InvalidMarker(marker)),
+            }
+
+            let table = table.as_ref().unwrap();
+
+            let (block_len, after_block_len) = read_u32(rest)?;
+            let (payload_len, after_payload_len) = read_u32(after_block_len)?;
+            let payload = after_payload_len
+                .get(..payload_len as usize)
+                .ok_or(FormatError::Truncated)?;
+            rest = &after_payload_len[payload_len as usize..];
+
+            if has_single_code(table) {
+                let byte = (0..=255)
+                    .find(|&byte| table.get_compressed_value_bit_count(byte) > 0)
+                    .unwrap();
+                output.extend(std::iter::repeat_n(byte, block_len as usize));
+                continue;
+            }
+
+            let tree = DecodeTree::new(table, None).map_err(DecodeError::from)?;
+            let mut decompressor =
+                Decompressor::new(&tree, payload).with_expected_len(block_len as usize);
+
+            let output_len = output.len();
+            output.extend(&mut decompressor);
+
+            if let Some(err) = decompressor.error() {
+                return Err(err.clone().into());
+            }
+
+            if output.len() - output_len != block_len as usize {
+                return Err(FormatError::Truncated);
+            }
+        }
+    }
+}
+
+fn has_single_code(table: &HuffmanTable) -> bool {
+    table
+        .bit_counts
+        .iter()
+        .filter(|&&bit_count| bit_count > 0)
+        .count()
+        == 1
+}
+
+// in bits, from the distribution of table_freqs to that of freqs. infinite if a byte in freqs
+// never occurs in table_freqs, since a table built from them has no code for it
+fn kl_divergence(freqs: &[u64; 256], table_freqs: &[u64; 256]) -> f64 {
+    let total: u64 = freqs.iter().sum();
+    let table_total: u64 = table_freqs.iter().sum();
+    let mut divergence = 0.0;
+
+    for (&freq, &table_freq) in freqs.iter().zip(table_freqs) {
+        if freq == 0 {
+            continue;
+        }
+
+        if table_freq == 0 {
+            return f64::INFINITY;
+        }
+
+        let probability = freq as f64 / total as f64;
+        let table_probability = table_freq as f64 / table_total as f64;
+        divergence += probability * (probability / table_probability).log2();
+    }
+
+    divergence
+}
+
+fn read_u32(bytes: &[u8]) -> Result<(u32, &[u8]), FormatError> {
+    let (u32_bytes, rest) = bytes.split_first_chunk().ok_or(FormatError::Truncated)?;
+    Ok((u32::from_be_bytes(*u32_bytes), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialize::TableError;
+
+    fn drifting_input() -> Vec<u8> {
+        let mut src = vec![0x00; 4096];
+        src.extend(vec![0xFF; 4096]);
+        src
+    }
+
+    #[test]
+    fn it_round_trips_input_whose_distribution_drifts() {
+        let src = drifting_input();
+
+        let compressed = AdaptiveCompressor::new(1024).compress(&src);
+
+        assert_eq!(AdaptiveCompressor::decompress(&compressed).unwrap(), src);
+    }
+
+    #[test]
+    fn it_compresses_drifting_input_smaller_than_one_global_table() {
+        let src = drifting_input();
+
+        let adaptive = AdaptiveCompressor::new(4096).compress(&src);
+        let global = AdaptiveCompressor::new(src.len()).compress(&src);
+
+        assert!(adaptive.len() < global.len());
+        assert_eq!(AdaptiveCompressor::decompress(&global).unwrap(), src);
+    }
+
+    #[test]
+    fn it_round_trips_blocks_with_mixed_bytes_and_a_partial_last_block() {
+        let src: Vec<u8> = (0..10_000u32)
+            .map(|i| {
+                if i < 6000 {
+                    (i % 7) as u8
+                } else {
+                    0x80 + (i % 3) as u8
+                }
+            })
+            .collect();
+
+        let compressed = AdaptiveCompressor::new(1500).compress(&src);
+
+        assert_eq!(AdaptiveCompressor::decompress(&compressed).unwrap(), src);
+    }
+
+    #[test]
+    fn it_reuses_the_previous_table_when_it_is_unchanged() {
+        let src = vec![0x00; 4096];
+
+        let compressed = AdaptiveCompressor::new(1024).compress(&src);
+
+        // the first block carries the table and later blocks only a marker and their lengths
+        let table_len = HuffmanTable::from_bytes(&src[..1024])
+            .serialize_checked()
+            .len();
+        let first_block_len = 1 + table_len + 8;
+        assert_eq!(compressed[0], NEW_TABLE_MARKER);
+        assert_eq!(compressed.len(), first_block_len + 3 * 9 + 1);
+        for block in 0..3 {
+            assert_eq!(compressed[first_block_len + block * 9], SAME_TABLE_MARKER);
+        }
+        assert_eq!(AdaptiveCompressor::decompress(&compressed).unwrap(), src);
+    }
+
+    // a stable skewed distribution, so every block has roughly but not exactly the same counts
+    fn stable_input() -> Vec<u8> {
+        let mut state = 0x9E3779B9u32;
+
+        (0..16_384)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+
+                match state % 16 {
+                    0..=6 => b'e',
+                    7..=10 => b't',
+                    11..=12 => b'a',
+                    13 => b'o',
+                    14 => b'i',
+                    _ => b'n',
+                }
+            })
+            .collect()
+    }
+
+    fn count_tables(compressed: &[u8]) -> usize {
+        let mut rest = compressed;
+        let mut tables = 0;
+
+        while let Some((&marker, after_marker)) = rest.split_first() {
+            rest = after_marker;
+
+            match marker {
+                END_MARKER => break,
+                NEW_TABLE_MARKER => {
+                    let (_, table_len) = HuffmanTable::deserialize_checked(rest).unwrap();
+                    rest = &rest[table_len..];
+                    tables += 1;
+                }
+                _ => {}
+            }
+
+            let (_, after_block_len) = read_u32(rest).unwrap();
+            let (payload_len, after_payload_len) = read_u32(after_block_len).unwrap();
+            rest = &after_payload_len[payload_len as usize..];
+        }
+
+        tables
+    }
+
+    #[test]
+    fn it_keeps_one_table_for_a_stable_distribution_under_a_switch_threshold() {
+        let src = stable_input();
+
+        let every_block = AdaptiveCompressor::new(1024).compress(&src);
+        let thresholded = AdaptiveCompressor::new(1024)
+            .with_switch_threshold(0.05)
+            .compress(&src);
+
+        assert!(count_tables(&every_block) > 1);
+        assert_eq!(count_tables(&thresholded), 1);
+        assert!(thresholded.len() < every_block.len());
+        assert_eq!(AdaptiveCompressor::decompress(&thresholded).unwrap(), src);
+    }
+
+    #[test]
+    fn it_switches_tables_past_the_switch_threshold() {
+        let mut src = stable_input();
+        src.extend(std::iter::repeat_n(b"xyz", 1024).flatten());
+
+        let compressed = AdaptiveCompressor::new(1024)
+            .with_switch_threshold(0.05)
+            .compress(&src);
+
+        assert_eq!(count_tables(&compressed), 2);
+        assert_eq!(AdaptiveCompressor::decompress(&compressed).unwrap(), src);
+    }
+
+    #[test]
+    fn it_round_trips_empty_input() {
+        let compressed = AdaptiveCompressor::new(16).compress(&[]);
+
+        assert_eq!(compressed, vec![END_MARKER]);
+        assert_eq!(
+            AdaptiveCompressor::decompress(&compressed).unwrap(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_block_table_that_fails_its_checksum() {
+        let mut compressed = AdaptiveCompressor::new(1024).compress(&drifting_input());
+        // the bit count of 0x00 in the first block's table
+        compressed[1] += 1;
+
+        assert_eq!(
+            AdaptiveCompressor::decompress(&compressed),
+            Err(FormatError::Table(TableError::ChecksumMismatch))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_an_invalid_marker() {
+        assert_eq!(
+            AdaptiveCompressor::decompress(&[SAME_TABLE_MARKER]),
+            Err(FormatError::InvalidMarker(SAME_TABLE_MARKER))
+        );
+        assert_eq!(
+            AdaptiveCompressor::decompress(&[0x7F]),
+            Err(FormatError::InvalidMarker(0x7F))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_truncated_input() {
+        let compressed = AdaptiveCompressor::new(1024).compress(&drifting_input());
+
+        assert_eq!(
+            AdaptiveCompressor::decompress(&compressed[..compressed.len() - 1]),
+            Err(FormatError::Truncated)
+        );
+    }
+}
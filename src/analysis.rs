@@ -0,0 +1,300 @@
+use crate::huffman_table::{code_lengths, HuffmanTable};
+
+// a test helper for pointing at the first bit two compressed outputs disagree on. bit positions
+// are counted from the most significant bit of each byte, which is the order the compressor
+// writes them in
+#[cfg(test)]
+pub(crate) fn bit_diff(a: &[u8], b: &[u8]) -> Option<(usize, u8)> {
+    for (index, (byte_a, byte_b)) in a.iter().zip(b.iter()).enumerate() {
+        let difference = byte_a ^ byte_b;
+
+        if difference != 0 {
+            return Some((index, difference.leading_zeros() as u8));
+        }
+    }
+
+    if a.len() != b.len() {
+        return Some((a.len().min(b.len()), 0));
+    }
+
+    None
+}
+
+// the total bits each byte value contributes to the compressed output of src
+pub fn symbol_output_bits(src: &[u8], table: &HuffmanTable) -> [u64; 256] {
+    let mut output_bits = [0; 256];
+
+    for &byte in src {
+        output_bits[byte as usize] += table.get_compressed_value_bit_count(byte) as u64;
+    }
+
+    output_bits
+}
+
+// well compressed output should use every byte value roughly equally often
+pub fn output_byte_histogram(compressed: &[u8]) -> [u64; 256] {
+    let mut histogram = [0; 256];
+
+    for &byte in compressed {
+        histogram[byte as usize] += 1;
+    }
+
+    histogram
+}
+
+// how many more bits per symbol an optimal huffman code needs than the entropy of freqs, which
+// is roughly what arithmetic coding could save
+pub fn huffman_vs_entropy_gap(freqs: &[u64; 256]) -> f64 {
+    let total: u64 = freqs.iter().sum();
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    let entropy: f64 = freqs
+        .iter()
+        .filter(|&&freq| freq > 0)
+        .map(|&freq| {
+            let probability = freq as f64 / total as f64;
+            -probability * probability.log2()
+        })
+        .sum();
+
+    let average_code_length = code_bits(freqs) as f64 / total as f64;
+
+    (average_code_length - entropy).max(0.0)
+}
+
+// the total bits an optimal table built from freqs would write, using the same code lengths
+// HuffmanTable::from_frequencies gives each symbol
+fn code_bits(freqs: &[u64]) -> u64 {
+    code_lengths(freqs)
+        .iter()
+        .zip(freqs)
+        .map(|(&length, &freq)| length as u64 * freq)
+        .sum()
+}
+
+// the offset to split src at so that compressing each side with its own optimal table gives the
+// smallest total output. the cost of storing the tables themselves isn't taken into account.
+// the prefix and suffix counts are moved along by one byte per split and only cover the bytes
+// that occur in src, so each split is costed over that alphabet rather than all 256 bytes
+pub fn optimal_split(src: &[u8]) -> Option<usize> {
+    if src.len() < 2 {
+        return None;
+    }
+
+    let mut symbols = [usize::MAX; 256];
+    let mut suffix_freqs = Vec::new();
+
+    for &byte in src {
+        if symbols[byte as usize] == usize::MAX {
+            symbols[byte as usize] = suffix_freqs.len();
+            suffix_freqs.push(0);
+        }
+
+        suffix_freqs[symbols[byte as usize]] += 1;
+    }
+
+    let mut prefix_freqs = vec![0; suffix_freqs.len()];
+
+    let mut best_split = None;
+    let mut best_code_bits = u64::MAX;
+
+    for (split, &byte) in src.iter().enumerate().take(src.len() - 1) {
+        let symbol = symbols[byte as usize];
+        prefix_freqs[symbol] += 1;
+        suffix_freqs[symbol] -= 1;
+
+        let code_bits = code_bits(&prefix_freqs) + code_bits(&suffix_freqs);
+
+        if code_bits < best_code_bits {
+            best_code_bits = code_bits;
+            best_split = Some(split + 1);
+        }
+    }
+
+    best_split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Huffman;
+
+    #[test]
+    fn it_returns_none_for_identical_outputs() {
+        assert_eq!(bit_diff(&[0xB3, 0x0C], &[0xB3, 0x0C]), None);
+    }
+
+    #[test]
+    fn it_returns_the_first_differing_bit() {
+        assert_eq!(bit_diff(&[0b10000000], &[0b11000000]), Some((0, 1)));
+    }
+
+    #[test]
+    fn it_returns_the_byte_index_of_the_first_difference() {
+        assert_eq!(
+            bit_diff(&[0xFF, 0xFF, 0x00], &[0xFF, 0xFE, 0x01]),
+            Some((1, 7))
+        );
+    }
+
+    #[test]
+    fn it_returns_the_end_of_the_shorter_output_when_lengths_differ() {
+        assert_eq!(bit_diff(&[0xAA], &[0xAA, 0x00]), Some((1, 0)));
+    }
+
+    #[test]
+    fn it_attributes_output_bits_to_each_byte_value() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0xFF] = 0b11000;
+        bit_counts[0xFF] = 5;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let src = vec![0x00, 0x00, 0x00, 0x01, 0xFF, 0x01, 0x00];
+
+        let output_bits = symbol_output_bits(&src, &table);
+
+        assert_eq!(output_bits[0x00], 4);
+        assert_eq!(output_bits[0x01], 4);
+        assert_eq!(output_bits[0xFF], 5);
+
+        let mut huffman = Huffman::new(table, None);
+        let mut output = Vec::new();
+        let bit_len = huffman.compress_exact(&src, &mut output).unwrap();
+
+        assert_eq!(output_bits.iter().sum::<u64>(), bit_len);
+    }
+
+    #[test]
+    fn it_returns_a_tiny_gap_for_a_dyadic_distribution() {
+        let mut freqs = [0; 256];
+        freqs[0x00] = 4;
+        freqs[0x01] = 2;
+        freqs[0x02] = 1;
+        freqs[0x03] = 1;
+
+        assert!(huffman_vs_entropy_gap(&freqs) < 1e-9);
+    }
+
+    #[test]
+    fn it_returns_a_larger_gap_for_a_skewed_distribution() {
+        let mut freqs = [0; 256];
+        freqs[0x00] = 99;
+        freqs[0x01] = 1;
+
+        let gap = huffman_vs_entropy_gap(&freqs);
+
+        assert!(gap > 0.9);
+        assert!(gap < 1.0);
+    }
+
+    #[test]
+    fn it_costs_the_gap_with_the_code_lengths_of_from_frequencies() {
+        let mut freqs = [0; 256];
+        for (byte, freq) in [(0x00, 5), (0x01, 5), (0x02, 3), (0x03, 3), (0x04, 1)] {
+            freqs[byte] = freq;
+        }
+
+        let table = HuffmanTable::from_frequencies(&freqs);
+        let table_bits: u64 = (0..=255)
+            .map(|byte| freqs[byte as usize] * table.get_compressed_value_bit_count(byte) as u64)
+            .sum();
+
+        assert_eq!(code_bits(&freqs), table_bits);
+        assert_eq!(code_bits(&[0, 7, 0]), 7);
+        assert_eq!(code_bits(&[0; 256]), 0);
+    }
+
+    #[test]
+    fn it_returns_no_gap_for_empty_frequencies() {
+        assert_eq!(huffman_vs_entropy_gap(&[0; 256]), 0.0);
+    }
+
+    #[test]
+    fn it_counts_each_byte_value_in_the_compressed_output() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x41] = 0b1111;
+        bit_counts[0x41] = 4;
+
+        values[0x42] = 0b0;
+        bit_counts[0x42] = 4;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+        let mut output = Vec::new();
+        huffman.compress(b"AAABBBBAB", &mut output).unwrap();
+
+        assert_eq!(output, vec![0xFF, 0xF0, 0x00, 0x0F, 0x00]);
+
+        let histogram = output_byte_histogram(&output);
+
+        assert_eq!(histogram[0xFF], 1);
+        assert_eq!(histogram[0xF0], 1);
+        assert_eq!(histogram[0x0F], 1);
+        assert_eq!(histogram[0x00], 2);
+        assert_eq!(histogram.iter().sum::<u64>(), 5);
+    }
+
+    #[test]
+    fn it_splits_at_an_abrupt_change_in_distribution() {
+        let mut src = Vec::new();
+        for i in 0..600 {
+            src.push([0x00, 0x01, 0x02, 0x03][i % 4]);
+        }
+        for i in 0..400 {
+            src.push(0x80 + (i % 16) as u8);
+        }
+
+        let split = optimal_split(&src).unwrap();
+
+        assert!(split.abs_diff(600) <= 4);
+    }
+
+    #[test]
+    fn it_splits_where_costing_every_split_from_scratch_does() {
+        let src = b"aaaabababcccccccdcdcdcdaab";
+
+        let mut best_split = None;
+        let mut best_code_bits = u64::MAX;
+
+        for split in 1..src.len() {
+            let mut left_freqs = [0; 256];
+            let mut right_freqs = [0; 256];
+            src[..split]
+                .iter()
+                .for_each(|&byte| left_freqs[byte as usize] += 1);
+            src[split..]
+                .iter()
+                .for_each(|&byte| right_freqs[byte as usize] += 1);
+
+            let code_bits = code_bits(&left_freqs) + code_bits(&right_freqs);
+
+            if code_bits < best_code_bits {
+                best_code_bits = code_bits;
+                best_split = Some(split);
+            }
+        }
+
+        assert_eq!(optimal_split(src), best_split);
+    }
+
+    #[test]
+    fn it_does_not_split_inputs_shorter_than_two_bytes() {
+        assert_eq!(optimal_split(&[]), None);
+        assert_eq!(optimal_split(&[0x00]), None);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::format::{decode_from_slice, encode_to_vec, FormatError};
+
+// ends an archive, after the manifest and its offset
+const FOOTER_MAGIC: &[u8; 4] = b"HUFM";
+
+// the size of each manifest entry and of the footer, in bytes
+const ENTRY_LEN: usize = 24;
+const FOOTER_LEN: usize = 8 + FOOTER_MAGIC.len();
+
+// where a block is in an archive and how big it is before and after compressing
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BlockEntry {
+    pub offset: u64,
+    pub original_len: u64,
+    pub compressed_len: u64,
+}
+
+// blocks compressed one after another with encode_to_vec, each with its own table, followed by a
+// manifest so any single block can be decompressed without touching the others:
+// - a BlockEntry for each block, as three big-endian u64s
+// - the offset the manifest starts at, as a big-endian u64
+// - FOOTER_MAGIC
+pub struct Archive<'a> {
+    bytes: &'a [u8],
+    manifest: Vec<BlockEntry>,
+}
+
+impl<'a> Archive<'a> {
+    pub fn write(blocks: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut manifest = Vec::with_capacity(blocks.len());
+
+        for block in blocks {
+            let compressed = encode_to_vec(block);
+
+            manifest.push(BlockEntry {
+                offset: bytes.len() as u64,
+                original_len: block.len() as u64,
+                compressed_len: compressed.len() as u64,
+            });
+            bytes.extend(compressed);
+        }
+
+        let manifest_offset = bytes.len() as u64;
+        for entry in manifest {
+            bytes.extend(entry.offset.to_be_bytes());
+            bytes.extend(entry.original_len.to_be_bytes());
+            bytes.extend(entry.compressed_len.to_be_bytes());
+        }
+        bytes.extend(manifest_offset.to_be_bytes());
+        bytes.extend(FOOTER_MAGIC);
+
+        bytes
+    }
+
+    // reads the manifest from the end of bytes, without decompressing any blocks
+    pub fn open(bytes: &'a [u8]) -> Result<Archive<'a>, FormatError> {
+        let rest = bytes
+            .strip_suffix(FOOTER_MAGIC)
+            .ok_or(FormatError::BadMagic)?;
+        let (rest, manifest_offset) = rest.split_last_chunk().ok_or(FormatError::Truncated)?;
+        let manifest_offset = u64::from_be_bytes(*manifest_offset);
+
+        let manifest_bytes = usize::try_from(manifest_offset)
+            .ok()
+            .and_then(|offset| rest.get(offset..))
+            .ok_or(FormatError::Truncated)?;
+
+        if manifest_bytes.len() % ENTRY_LEN != 0 {
+            return Err(FormatError::InvalidHeader);
+        }
+
+        let field = |entry: &[u8], index: usize| {
+            u64::from_be_bytes(entry[index * 8..index * 8 + 8].try_into().unwrap())
+        };
+        let manifest: Vec<BlockEntry> = manifest_bytes
+            .chunks_exact(ENTRY_LEN)
+            .map(|entry| BlockEntry {
+                offset: field(entry, 0),
+                original_len: field(entry, 1),
+                compressed_len: field(entry, 2),
+            })
+            .collect();
+
+        let blocks_len = bytes.len() - manifest_bytes.len() - FOOTER_LEN;
+        let in_bounds = |entry: &BlockEntry| {
+            entry
+                .offset
+                .checked_add(entry.compressed_len)
+                .is_some_and(|end| end <= blocks_len as u64)
+        };
+
+        if !manifest.iter().all(in_bounds) {
+            return Err(FormatError::InvalidHeader);
+        }
+
+        Ok(Archive { bytes, manifest })
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.manifest.len()
+    }
+
+    pub fn manifest(&self) -> &[BlockEntry] {
+        &self.manifest
+    }
+
+    // decompresses only block n
+    pub fn read_block(&self, n: usize) -> Result<Vec<u8>, FormatError> {
+        let entry = self.manifest.get(n).ok_or(FormatError::NoSuchBlock(n))?;
+
+        let start = entry.offset as usize;
+        let end = start + entry.compressed_len as usize;
+        let block = decode_from_slice(&self.bytes[start..end])?;
+
+        if block.len() as u64 != entry.original_len {
+            return Err(FormatError::InvalidHeader);
+        }
+
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialize::TableError;
+
+    const BLOCKS: [&[u8]; 3] = [
+        b"it was the best of times, it was the worst of times",
+        &[0x00; 100],
+        b"it was the age of wisdom, it was the age of foolishness",
+    ];
+
+    #[test]
+    fn it_reads_any_block_of_an_archive() {
+        let bytes = Archive::write(&BLOCKS);
+        let archive = Archive::open(&bytes).unwrap();
+
+        assert_eq!(archive.block_count(), 3);
+
+        for (n, block) in BLOCKS.iter().enumerate() {
+            assert_eq!(archive.read_block(n).unwrap(), *block);
+            assert_eq!(archive.manifest()[n].original_len, block.len() as u64);
+        }
+    }
+
+    #[test]
+    fn it_reads_a_block_without_decompressing_the_others() {
+        let mut bytes = Archive::write(&BLOCKS);
+        let manifest = Archive::open(&bytes).unwrap().manifest().to_vec();
+
+        // wreck the first and last blocks
+        for n in [0, 2] {
+            let start = manifest[n].offset as usize;
+            let end = start + manifest[n].compressed_len as usize;
+            bytes[start..end].fill(0xFF);
+        }
+
+        let archive = Archive::open(&bytes).unwrap();
+
+        assert_eq!(archive.read_block(1).unwrap(), BLOCKS[1]);
+        assert_eq!(archive.read_block(0), Err(FormatError::BadMagic));
+    }
+
+    #[test]
+    fn it_writes_blocks_back_to_back_before_the_manifest() {
+        let bytes = Archive::write(&BLOCKS);
+        let archive = Archive::open(&bytes).unwrap();
+
+        let mut offset = 0;
+        for entry in archive.manifest() {
+            assert_eq!(entry.offset, offset);
+            offset += entry.compressed_len;
+        }
+
+        assert_eq!(bytes.len() as u64, offset + 3 * 24 + 8 + 4);
+    }
+
+    #[test]
+    fn it_errs_on_a_block_whose_table_fails_its_checksum() {
+        let mut bytes = Archive::write(&BLOCKS);
+        // the space's bit count in the first block's table, after the magic and version
+        bytes[5 + b' ' as usize] += 1;
+
+        let archive = Archive::open(&bytes).unwrap();
+        assert_eq!(
+            archive.read_block(0),
+            Err(FormatError::Table(TableError::ChecksumMismatch))
+        );
+        assert_eq!(archive.read_block(2).unwrap(), BLOCKS[2]);
+    }
+
+    #[test]
+    fn it_errs_on_a_block_past_the_end() {
+        let bytes = Archive::write(&BLOCKS);
+
+        assert_eq!(
+            Archive::open(&bytes).unwrap().read_block(3),
+            Err(FormatError::NoSuchBlock(3))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_an_archive_without_its_footer() {
+        let bytes = Archive::write(&BLOCKS);
+
+        assert!(matches!(
+            Archive::open(&bytes[..bytes.len() - 1]),
+            Err(FormatError::BadMagic)
+        ));
+        assert!(matches!(
+            Archive::open(&bytes[bytes.len() - 10..]),
+            Err(FormatError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn it_writes_an_empty_archive() {
+        let bytes = Archive::write(&[]);
+
+        assert_eq!(Archive::open(&bytes).unwrap().block_count(), 0);
+    }
+}
@@ -0,0 +1,351 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use crate::compressor::Compressor;
+use crate::huffman_table::HuffmanTable;
+use crate::terminal_code::TerminalCode;
+
+// the poll methods of tokio's AsyncWrite, so the crate needs no runtime. a tokio writer can
+// implement it by forwarding to its own poll_write, poll_flush and poll_shutdown
+pub trait AsyncWrite {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>>;
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+}
+
+impl AsyncWrite for Vec<u8> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+// HuffmanWriter as an AsyncWrite: bytes written are compressed and the complete ones written to
+// the inner writer, and shutdown appends the terminal code and padding before shutting it down.
+// complete bytes the inner writer hasn't accepted yet are kept in pending, so a write dropped
+// partway never repeats them
+pub struct AsyncHuffmanWriter<'a, W: AsyncWrite + Unpin> {
+    inner: W,
+    compressor: Compressor<'a>,
+    terminal_code: Option<&'a TerminalCode>,
+    pending: Vec<u8>,
+    // set once shutdown has appended the terminal code, so it's only appended once
+    finished: bool,
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncHuffmanWriter<'a, W> {
+    pub fn new(
+        inner: W,
+        table: &'a HuffmanTable,
+        terminal_code: Option<&'a TerminalCode>,
+    ) -> AsyncHuffmanWriter<'a, W> {
+        AsyncHuffmanWriter {
+            inner,
+            compressor: Compressor::new(table),
+            terminal_code,
+            pending: Vec::with_capacity(8),
+            finished: false,
+        }
+    }
+
+    // the inner writer, e.g. once shutdown is done
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.compressor.drain_into(&mut self.pending);
+
+        while !self.pending.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..n);
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncHuffmanWriter<'_, W> {
+    // writes out what's pending before taking more. a byte the table can't compress is reported
+    // as an InvalidInput error once the bytes before it have been taken
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        if this.finished {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        ready!(this.poll_write_pending(cx))?;
+
+        for (i, &byte) in buf.iter().enumerate() {
+            if let Err(err) = this.compressor.compress_byte(byte) {
+                if i > 0 {
+                    return Poll::Ready(Ok(i));
+                }
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidInput, err)));
+            }
+            this.compressor.drain_into(&mut this.pending);
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if !this.finished {
+            if let Some(terminal_code) = this.terminal_code {
+                this.compressor
+                    .append_terminal_code(terminal_code)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            }
+            this.compressor.end();
+            this.finished = true;
+        }
+
+        ready!(this.poll_write_pending(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_tree::DecodeTree;
+    use crate::decompressor::Decompressor;
+    use crate::writer::HuffmanWriter;
+    use std::future::{poll_fn, Future};
+    use std::task::Waker;
+
+    // polls the future until it's done. the writers here never need waking
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    async fn write_all<W: AsyncWrite + Unpin>(writer: &mut W, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await?;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    async fn shutdown<W: AsyncWrite + Unpin>(writer: &mut W) -> io::Result<()> {
+        poll_fn(|cx| Pin::new(&mut *writer).poll_shutdown(cx)).await
+    }
+
+    // returns Pending before every write and takes at most one byte at a time
+    struct SlowWriter {
+        bytes: Vec<u8>,
+        ready: bool,
+    }
+
+    impl AsyncWrite for SlowWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if !self.ready {
+                self.ready = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            self.ready = false;
+            self.bytes.push(buf[0]);
+            Poll::Ready(Ok(1))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    struct FailingWriter;
+
+    impl AsyncWrite for FailingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(io::Error::other("disk full")))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    const TERMINAL_CODE: TerminalCode = TerminalCode {
+        bit_count: 3,
+        value: 0b111,
+    };
+
+    const SRC: [u8; 6] = [0xA0, 0xCB, 0xB3, 0xB3, 0xA0, 0xCB];
+
+    fn table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b101;
+        bit_counts[0xCB] = 3;
+
+        values[0xB3] = 0b1101_0110_0101;
+        bit_counts[0xB3] = 12;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    fn sync_output(table: &HuffmanTable) -> Vec<u8> {
+        let mut writer = HuffmanWriter::new(Vec::new(), table, Some(&TERMINAL_CODE));
+        for &byte in &SRC {
+            writer.write_byte(byte).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn it_writes_output_that_decompresses_the_same_as_huffman_writer() {
+        let table = table();
+
+        let output = block_on(async {
+            let mut writer = AsyncHuffmanWriter::new(Vec::new(), &table, Some(&TERMINAL_CODE));
+            write_all(&mut writer, &SRC[..4]).await?;
+            write_all(&mut writer, &SRC[4..]).await?;
+            shutdown(&mut writer).await?;
+            Ok::<_, io::Error>(writer.into_inner())
+        })
+        .unwrap();
+
+        assert_eq!(output, sync_output(&table));
+
+        let tree = DecodeTree::new(&table, Some(&TERMINAL_CODE)).unwrap();
+        let decompressed: Vec<u8> = Decompressor::new(&tree, &output).collect();
+        assert_eq!(decompressed, SRC);
+    }
+
+    #[test]
+    fn it_never_repeats_bytes_when_polled_again_after_pending() {
+        let table = table();
+        let slow_writer = SlowWriter {
+            bytes: Vec::new(),
+            ready: false,
+        };
+        let mut writer = AsyncHuffmanWriter::new(slow_writer, &table, Some(&TERMINAL_CODE));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        // every poll_write and poll_shutdown future is dropped as soon as it returns Pending, as
+        // a timeout or select! would, and the next one polled in its place
+        let mut src = &SRC[..];
+        while !src.is_empty() {
+            if let Poll::Ready(n) = Pin::new(&mut writer).poll_write(&mut cx, src) {
+                src = &src[n.unwrap()..];
+            }
+        }
+        while Pin::new(&mut writer).poll_shutdown(&mut cx).is_pending() {}
+
+        assert_eq!(writer.into_inner().bytes, sync_output(&table));
+    }
+
+    #[test]
+    fn it_surfaces_errors_from_the_inner_writer() {
+        let table = table();
+
+        let mut writer = AsyncHuffmanWriter::new(FailingWriter, &table, None);
+
+        let err = block_on(async {
+            write_all(&mut writer, &[0xB3]).await?;
+            shutdown(&mut writer).await
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "disk full");
+    }
+
+    #[test]
+    fn it_reports_a_byte_without_a_code_as_invalid_input() {
+        let table = table();
+
+        let mut writer = AsyncHuffmanWriter::new(Vec::new(), &table, None);
+
+        let err = block_on(write_all(&mut writer, &[0x00])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn it_takes_the_bytes_before_one_without_a_code() {
+        let table = table();
+        let mut writer = AsyncHuffmanWriter::new(Vec::new(), &table, None);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let n = Pin::new(&mut writer).poll_write(&mut cx, &[0xA0, 0xCB, 0x00]);
+
+        assert!(matches!(n, Poll::Ready(Ok(2))));
+    }
+
+    #[test]
+    fn it_errs_on_writes_after_shutdown() {
+        let table = table();
+
+        let mut writer = AsyncHuffmanWriter::new(Vec::new(), &table, None);
+
+        let err = block_on(async {
+            shutdown(&mut writer).await?;
+            write_all(&mut writer, &[0xA0]).await
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,125 @@
+// sorts every rotation of src and returns the last byte of each, along with the position of src
+// itself among the sorted rotations. bytes that are followed by the same context end up next to
+// each other, so repetitive data turns into long runs that move to front then makes small
+pub fn burrows_wheeler_encode(src: &[u8]) -> (Vec<u8>, usize) {
+    let rotations = sorted_rotations(src);
+    let len = src.len();
+
+    let last = rotations
+        .iter()
+        .map(|&rotation| src[(rotation + len - 1) % len])
+        .collect();
+    let primary_index = rotations
+        .iter()
+        .position(|&rotation| rotation == 0)
+        .unwrap_or(0);
+
+    (last, primary_index)
+}
+
+// the inverse of burrows_wheeler_encode. the sorted rotations' first bytes are just last sorted,
+// and the nth occurrence of a byte in last is the same byte as its nth occurrence in first, so
+// following that from the primary index walks src backwards
+pub fn burrows_wheeler_decode(last: &[u8], primary_index: usize) -> Vec<u8> {
+    let mut first_position = [0; 256];
+    let mut counts = [0; 256];
+    for &byte in last {
+        counts[byte as usize] += 1;
+    }
+    let mut position = 0;
+    for byte in 0..256 {
+        first_position[byte] = position;
+        position += counts[byte];
+    }
+
+    // where each byte of last is in first
+    let mut seen = [0; 256];
+    let next: Vec<usize> = last
+        .iter()
+        .map(|&byte| {
+            let rank = seen[byte as usize];
+            seen[byte as usize] += 1;
+            first_position[byte as usize] + rank
+        })
+        .collect();
+
+    let mut src = vec![0; last.len()];
+    let mut row = primary_index;
+    for byte in src.iter_mut().rev() {
+        *byte = last[row];
+        row = next[row];
+    }
+
+    src
+}
+
+// the start of each rotation of src in sorted order, by prefix doubling: rotations are ranked by
+// their first k bytes, then by pairs of those ranks for the first 2k. equal rotations, as in
+// periodic data, keep their original order
+fn sorted_rotations(src: &[u8]) -> Vec<usize> {
+    let len = src.len();
+    let mut rotations: Vec<usize> = (0..len).collect();
+    let mut ranks: Vec<usize> = src.iter().map(|&byte| byte as usize).collect();
+    let mut k = 1;
+
+    while k < len {
+        let key = |rotation: usize| (ranks[rotation], ranks[(rotation + k) % len]);
+        rotations.sort_by_key(|&rotation| key(rotation));
+
+        let mut new_ranks = vec![0; len];
+        for pair in rotations.windows(2) {
+            new_ranks[pair[1]] = new_ranks[pair[0]] + (key(pair[0]) != key(pair[1])) as usize;
+        }
+
+        let distinct = new_ranks[rotations[len - 1]] == len - 1;
+        ranks = new_ranks;
+
+        if distinct {
+            break;
+        }
+
+        k *= 2;
+    }
+
+    if len == 1 {
+        return rotations;
+    }
+
+    rotations.sort_by_key(|&rotation| (ranks[rotation], rotation));
+    rotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encodes_banana() {
+        assert_eq!(burrows_wheeler_encode(b"banana"), (b"nnbaaa".to_vec(), 3));
+    }
+
+    #[test]
+    fn it_round_trips_text() {
+        let src = b"it was the best of times, it was the worst of times";
+        let (last, primary_index) = burrows_wheeler_encode(src);
+
+        assert_eq!(burrows_wheeler_decode(&last, primary_index), src);
+    }
+
+    #[test]
+    fn it_round_trips_periodic_and_tiny_inputs() {
+        for src in [&b""[..], b"a", b"aaaa", b"abab", b"abcabcabc"] {
+            let (last, primary_index) = burrows_wheeler_encode(src);
+
+            assert_eq!(burrows_wheeler_decode(&last, primary_index), src);
+        }
+    }
+
+    #[test]
+    fn it_round_trips_every_byte() {
+        let src: Vec<u8> = (0..2000u32).map(|i| (i * i % 251) as u8).collect();
+        let (last, primary_index) = burrows_wheeler_encode(&src);
+
+        assert_eq!(burrows_wheeler_decode(&last, primary_index), src);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::serialize::TableError;
+
+// byte pair merges learned from some data, each replacing an adjacent pair of bytes with a byte
+// that doesn't otherwise occur in it, most frequent pair first. merges can also replace pairs
+// containing earlier merges' bytes, so they're undone in reverse
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MergeTable {
+    pub merges: Vec<(u8, [u8; 2])>,
+}
+
+impl MergeTable {
+    // learns up to max_merges merges from src, stopping early when no pair occurs more than once
+    // or there are no unused bytes left to merge into, which also keeps it under 256 merges
+    pub fn learn(src: &[u8], max_merges: usize) -> MergeTable {
+        let mut tokens = src.to_vec();
+        let mut used = [false; 256];
+        for &byte in src {
+            used[byte as usize] = true;
+        }
+
+        let mut merges = Vec::new();
+
+        while merges.len() < max_merges {
+            let Some(merged) = (0..=255).find(|&byte| !used[byte as usize]) else {
+                break;
+            };
+
+            let mut counts = vec![0u32; 1 << 16];
+            for pair in tokens.windows(2) {
+                counts[(pair[0] as usize) << 8 | pair[1] as usize] += 1;
+            }
+
+            // the lowest pair among the most frequent, so the same data always learns the same
+            let (pair, &count) = counts
+                .iter()
+                .enumerate()
+                .max_by_key(|&(pair, &count)| (count, std::cmp::Reverse(pair)))
+                .unwrap();
+
+            if count < 2 {
+                break;
+            }
+
+            let pair = [(pair >> 8) as u8, pair as u8];
+            tokens = merge(&tokens, pair, merged);
+            used[merged as usize] = true;
+            merges.push((merged, pair));
+        }
+
+        MergeTable { merges }
+    }
+
+    pub fn apply(&self, src: &[u8]) -> Vec<u8> {
+        self.merges
+            .iter()
+            .fold(src.to_vec(), |tokens, &(merged, pair)| {
+                merge(&tokens, pair, merged)
+            })
+    }
+
+    pub fn undo(&self, tokens: &[u8]) -> Vec<u8> {
+        self.merges
+            .iter()
+            .rev()
+            .fold(tokens.to_vec(), |tokens, &(merged, pair)| {
+                tokens
+                    .iter()
+                    .flat_map(|&token| match token == merged {
+                        true => pair.to_vec(),
+                        false => vec![token],
+                    })
+                    .collect()
+            })
+    }
+
+    // the number of merges, then the merged byte and pair of each
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = vec![self.merges.len() as u8];
+
+        for &(merged, pair) in &self.merges {
+            bytes.push(merged);
+            bytes.extend(pair);
+        }
+
+        bytes
+    }
+
+    // reads a merge table written by serialize from the start of bytes, returning it along with
+    // the number of bytes it took up
+    pub fn deserialize(bytes: &[u8]) -> Result<(MergeTable, usize), TableError> {
+        let (&len, rest) = bytes.split_first().ok_or(TableError::Truncated)?;
+        let merge_bytes = rest.get(..len as usize * 3).ok_or(TableError::Truncated)?;
+
+        let merges = merge_bytes
+            .chunks_exact(3)
+            .map(|merge| (merge[0], [merge[1], merge[2]]))
+            .collect();
+
+        Ok((MergeTable { merges }, 1 + merge_bytes.len()))
+    }
+}
+
+// replaces each occurrence of pair, left to right without overlapping, with merged
+fn merge(tokens: &[u8], pair: [u8; 2], merged: u8) -> Vec<u8> {
+    let mut merged_tokens = Vec::with_capacity(tokens.len());
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if tokens[i..].starts_with(&pair) {
+            merged_tokens.push(merged);
+            i += 2;
+        } else {
+            merged_tokens.push(tokens[i]);
+            i += 1;
+        }
+    }
+
+    merged_tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_merges_a_repeated_pair_into_an_unused_byte() {
+        let table = MergeTable::learn(b"abababab", 1);
+
+        assert_eq!(table.merges, vec![(0x00, *b"ab")]);
+        assert_eq!(table.apply(b"abababab"), vec![0x00; 4]);
+    }
+
+    #[test]
+    fn it_merges_pairs_of_earlier_merges() {
+        let src = b"abababababababab";
+        let table = MergeTable::learn(src, 10);
+
+        // ab, then abab, then abababab, which only occurs twice without overlapping
+        assert_eq!(table.merges.len(), 3);
+        assert_eq!(table.apply(src), vec![0x02, 0x02]);
+        assert_eq!(table.undo(&table.apply(src)), src);
+    }
+
+    #[test]
+    fn it_stops_when_no_pair_repeats() {
+        let table = MergeTable::learn(b"abcdef", 10);
+
+        assert!(table.merges.is_empty());
+    }
+
+    #[test]
+    fn it_stops_when_every_byte_is_used() {
+        let mut src: Vec<u8> = (0..=255).collect();
+        src.extend(b"xyxy");
+
+        assert!(MergeTable::learn(&src, 10).merges.is_empty());
+    }
+
+    #[test]
+    fn it_round_trips_a_merge_table() {
+        let table = MergeTable::learn(b"the cat sat on the mat with the hat", 5);
+        let mut bytes = table.serialize();
+        bytes.push(0xFF);
+
+        let (deserialized, len) = MergeTable::deserialize(&bytes).unwrap();
+
+        assert_eq!(deserialized, table);
+        assert_eq!(len, bytes.len() - 1);
+        assert_eq!(
+            MergeTable::deserialize(&bytes[..len - 1]),
+            Err(TableError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,183 @@
+// canonical codes are assigned in order of bit count and then of symbol, with each code being
+// the previous one plus one, shifted left whenever the bit count increases
+pub fn canonical_code(lengths: &[u8; 256], symbol: u8) -> Option<(u32, u8)> {
+    let mut ranks = [0; 256];
+    for (rank, entry) in ranks.iter_mut().enumerate() {
+        *entry = rank;
+    }
+
+    code_for_rank(lengths, &ranks, symbol)
+}
+
+// as canonical_code, but codes of the same bit count are assigned in the order the symbols
+// appear in order rather than by symbol value. symbols missing from order have no code
+pub fn canonical_code_ordered(lengths: &[u8; 256], order: &[u8], symbol: u8) -> Option<(u32, u8)> {
+    let mut ranks = [usize::MAX; 256];
+    let mut ordered_lengths = [0; 256];
+
+    for (rank, &ordered_symbol) in order.iter().enumerate() {
+        ranks[ordered_symbol as usize] = rank;
+        ordered_lengths[ordered_symbol as usize] = lengths[ordered_symbol as usize];
+    }
+
+    code_for_rank(&ordered_lengths, &ranks, symbol)
+}
+
+// the canonical code of every symbol at once, for alphabets of any size. symbols with a length of
+// 0 or over 32 get a value of 0
+pub(crate) fn canonical_values(lengths: &[u8]) -> Vec<u32> {
+    let mut length_counts = [0u64; 33];
+    for &length in lengths {
+        if (1..=32).contains(&length) {
+            length_counts[length as usize] += 1;
+        }
+    }
+
+    // the first code of each length, which the codes of shorter lengths count up to
+    let mut next_codes = [0u64; 33];
+    let mut code = 0;
+    for length in 1..=32 {
+        code = (code + length_counts[length - 1]) << 1;
+        next_codes[length] = code;
+    }
+
+    lengths
+        .iter()
+        .map(|&length| {
+            if !(1..=32).contains(&length) {
+                return 0;
+            }
+
+            let code = next_codes[length as usize];
+            next_codes[length as usize] += 1;
+            code as u32
+        })
+        .collect()
+}
+
+fn code_for_rank(lengths: &[u8; 256], ranks: &[usize; 256], symbol: u8) -> Option<(u32, u8)> {
+    let bit_count = lengths[symbol as usize];
+    let rank = ranks[symbol as usize];
+
+    if bit_count == 0 || bit_count > 32 {
+        return None;
+    }
+
+    let mut code: u64 = 0;
+
+    for length in 1..=bit_count {
+        code <<= 1;
+
+        let preceding_symbols = lengths
+            .iter()
+            .zip(ranks.iter())
+            .filter(|&(&other_length, &other_rank)| {
+                other_length == length && (length < bit_count || other_rank < rank)
+            })
+            .count();
+
+        code += preceding_symbols as u64;
+    }
+
+    Some((code as u32, bit_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_lengths() -> [u8; 256] {
+        let mut lengths = [0; 256];
+        lengths[0x41] = 1;
+        lengths[0x00] = 2;
+        lengths[0x10] = 3;
+        lengths[0xFF] = 3;
+        lengths
+    }
+
+    #[test]
+    fn it_returns_none_for_a_symbol_without_a_code() {
+        assert_eq!(canonical_code(&example_lengths(), 0x42), None);
+    }
+
+    #[test]
+    fn it_returns_zeroes_for_the_first_symbol_in_canonical_order() {
+        assert_eq!(canonical_code(&example_lengths(), 0x41), Some((0b0, 1)));
+    }
+
+    #[test]
+    fn it_returns_the_canonical_code_for_symbols_in_between() {
+        assert_eq!(canonical_code(&example_lengths(), 0x00), Some((0b10, 2)));
+        assert_eq!(canonical_code(&example_lengths(), 0x10), Some((0b110, 3)));
+    }
+
+    #[test]
+    fn it_returns_ones_for_the_last_symbol_in_canonical_order() {
+        assert_eq!(canonical_code(&example_lengths(), 0xFF), Some((0b111, 3)));
+    }
+
+    #[test]
+    fn it_orders_symbols_of_equal_length_by_value() {
+        let lengths = [8; 256];
+
+        for symbol in 0..=255 {
+            assert_eq!(canonical_code(&lengths, symbol), Some((symbol as u32, 8)));
+        }
+    }
+
+    #[test]
+    fn it_assigns_codes_of_equal_length_in_the_order_given() {
+        let lengths = example_lengths();
+        let order: Vec<u8> = (0..=255).rev().collect();
+
+        assert_eq!(
+            canonical_code_ordered(&lengths, &order, 0x41),
+            Some((0b0, 1))
+        );
+        assert_eq!(
+            canonical_code_ordered(&lengths, &order, 0x00),
+            Some((0b10, 2))
+        );
+        assert_eq!(
+            canonical_code_ordered(&lengths, &order, 0xFF),
+            Some((0b110, 3))
+        );
+        assert_eq!(
+            canonical_code_ordered(&lengths, &order, 0x10),
+            Some((0b111, 3))
+        );
+    }
+
+    #[test]
+    fn it_reverses_the_assignment_when_given_a_reversed_order() {
+        let lengths = [8; 256];
+        let order: Vec<u8> = (0..=255).rev().collect();
+
+        for symbol in 0..=255 {
+            assert_eq!(
+                canonical_code_ordered(&lengths, &order, symbol),
+                Some((255 - symbol as u32, 8))
+            );
+        }
+    }
+
+    #[test]
+    fn it_returns_none_for_a_symbol_missing_from_the_order() {
+        assert_eq!(
+            canonical_code_ordered(&example_lengths(), &[0x41, 0x00, 0x10], 0xFF),
+            None
+        );
+    }
+
+    #[test]
+    fn it_assigns_the_same_values_to_every_symbol_at_once() {
+        let lengths = example_lengths();
+
+        let values = canonical_values(&lengths);
+
+        for symbol in 0..=255 {
+            let expected = canonical_code(&lengths, symbol).map_or(0, |(value, _)| value);
+            assert_eq!(values[symbol as usize], expected);
+        }
+    }
+}
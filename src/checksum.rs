@@ -0,0 +1,95 @@
+const FNV_OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+
+const FNV_PRIME: u64 = 0x100000001B3;
+
+const CRC32_POLYNOMIAL: u32 = 0xEDB88320;
+
+pub struct Fnv1a {
+    hash: u64,
+}
+
+impl Fnv1a {
+    pub fn new() -> Self {
+        Self {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        self.hash ^= byte as u64;
+        self.hash = self.hash.wrapping_mul(FNV_PRIME);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { crc: u32::MAX }
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        self.crc ^= byte as u32;
+
+        for _ in 0..8 {
+            let mask = (self.crc & 1).wrapping_neg();
+            self.crc = (self.crc >> 1) ^ (CRC32_POLYNOMIAL & mask);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.crc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fnv1a(data: &[u8]) -> u64 {
+        let mut hasher = Fnv1a::new();
+        for &byte in data {
+            hasher.write_byte(byte);
+        }
+        hasher.finish()
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = Crc32::new();
+        for &byte in data {
+            crc.write_byte(byte);
+        }
+        crc.finish()
+    }
+
+    #[test]
+    fn it_returns_the_offset_basis_for_empty_input() {
+        assert_eq!(fnv1a(b""), 0xCBF29CE484222325);
+    }
+
+    #[test]
+    fn it_matches_known_fnv1a_hashes() {
+        assert_eq!(fnv1a(b"a"), 0xAF63DC4C8601EC8C);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171F73967E8);
+    }
+
+    #[test]
+    fn it_returns_a_zero_crc32_for_empty_input() {
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn it_matches_known_crc32_checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414FA339
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::format::{compress_self_contained, decompress_self_contained, FormatError};
+
+// src split into chunks that are each compressed with compress_self_contained, so every chunk
+// has its own optimal table stored as run-length encoded code lengths. the directory holds the
+// offset each chunk starts at in data, so any chunk can be decompressed without the others
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChunkedArchive {
+    directory: Vec<u64>,
+    data: Vec<u8>,
+}
+
+// chunk_size must be more than 0
+pub fn compress_chunked_adaptive(src: &[u8], chunk_size: usize) -> ChunkedArchive {
+    assert!(chunk_size > 0, "chunk_size must be more than 0");
+
+    let mut directory = Vec::new();
+    let mut data = Vec::new();
+
+    for chunk in src.chunks(chunk_size) {
+        directory.push(data.len() as u64);
+        data.extend(compress_self_contained(chunk));
+    }
+
+    ChunkedArchive { directory, data }
+}
+
+impl ChunkedArchive {
+    pub fn chunk_count(&self) -> usize {
+        self.directory.len()
+    }
+
+    pub fn decompress_chunk(&self, n: usize) -> Result<Vec<u8>, FormatError> {
+        let &start = self.directory.get(n).ok_or(FormatError::NoSuchBlock(n))?;
+        let end = self
+            .directory
+            .get(n + 1)
+            .map_or(self.data.len() as u64, |&end| end);
+
+        decompress_self_contained(&self.data[start as usize..end as usize])
+    }
+
+    pub fn decompress(&self) -> Result<Vec<u8>, FormatError> {
+        let mut output = Vec::new();
+
+        for n in 0..self.chunk_count() {
+            output.extend(self.decompress_chunk(n)?);
+        }
+
+        Ok(output)
+    }
+
+    // the number of chunks as a big-endian u32, then the directory as big-endian u64s, then
+    // the chunks
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend((self.directory.len() as u32).to_be_bytes());
+        for &offset in &self.directory {
+            bytes.extend(offset.to_be_bytes());
+        }
+        bytes.extend(&self.data);
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ChunkedArchive, FormatError> {
+        let (chunk_count, rest) = bytes.split_first_chunk().ok_or(FormatError::Truncated)?;
+        let chunk_count = u32::from_be_bytes(*chunk_count) as usize;
+
+        let directory_len = chunk_count.checked_mul(8).ok_or(FormatError::Truncated)?;
+        let directory_bytes = rest.get(..directory_len).ok_or(FormatError::Truncated)?;
+        let data = rest[directory_len..].to_vec();
+
+        let directory: Vec<u64> = directory_bytes
+            .chunks_exact(8)
+            .map(|offset| u64::from_be_bytes(offset.try_into().unwrap()))
+            .collect();
+
+        // offsets have to start at 0 and never go backwards or past the end of the data
+        let in_order = directory.first().is_none_or(|&first| first == 0)
+            && directory.windows(2).all(|pair| pair[0] <= pair[1])
+            && directory
+                .last()
+                .is_none_or(|&last| last <= data.len() as u64);
+
+        if !in_order {
+            return Err(FormatError::InvalidHeader);
+        }
+
+        Ok(ChunkedArchive { directory, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialize::TableError;
+
+    // text, then binary-ish bytes, then a run, so each chunk wants a different table
+    fn heterogeneous_src() -> Vec<u8> {
+        let mut src = Vec::new();
+        for _ in 0..20 {
+            src.extend(b"it was the best of times, ");
+        }
+        src.extend((0..520u32).map(|i| (i * 37 % 256) as u8));
+        src.extend([0xAA; 520]);
+        src
+    }
+
+    #[test]
+    fn it_decompresses_each_chunk_independently() {
+        let src = heterogeneous_src();
+        let archive = compress_chunked_adaptive(&src, 520);
+
+        assert_eq!(archive.chunk_count(), 3);
+
+        for (n, chunk) in src.chunks(520).enumerate() {
+            assert_eq!(archive.decompress_chunk(n).unwrap(), chunk);
+        }
+
+        assert_eq!(archive.decompress().unwrap(), src);
+        assert_eq!(
+            archive.decompress_chunk(3),
+            Err(FormatError::NoSuchBlock(3))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_chunk_whose_table_fails_its_checksum() {
+        let mut archive = compress_chunked_adaptive(&heterogeneous_src(), 520);
+        // the first run's bit count, giving the bytes from 0x00 a code
+        archive.data[3] += 1;
+
+        assert_eq!(
+            archive.decompress_chunk(0),
+            Err(FormatError::Table(TableError::ChecksumMismatch))
+        );
+        assert!(archive.decompress_chunk(1).is_ok());
+    }
+
+    #[test]
+    fn it_compresses_heterogeneous_data_smaller_than_one_table() {
+        let src = heterogeneous_src();
+
+        let chunked = compress_chunked_adaptive(&src, 520).to_bytes();
+        let single = compress_self_contained(&src);
+
+        assert!(chunked.len() < single.len());
+    }
+
+    #[test]
+    fn it_round_trips_an_archive_through_bytes() {
+        let archive = compress_chunked_adaptive(&heterogeneous_src(), 300);
+
+        let bytes = archive.to_bytes();
+
+        assert_eq!(ChunkedArchive::from_bytes(&bytes).unwrap(), archive);
+        assert_eq!(
+            ChunkedArchive::from_bytes(&bytes[..10]),
+            Err(FormatError::Truncated)
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_directory_out_of_order() {
+        let mut bytes = compress_chunked_adaptive(&heterogeneous_src(), 520).to_bytes();
+
+        // the second chunk's offset, past the end of the data
+        bytes[4 + 8..4 + 16].fill(0xFF);
+
+        assert_eq!(
+            ChunkedArchive::from_bytes(&bytes),
+            Err(FormatError::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn it_compresses_empty_input_to_no_chunks() {
+        let archive = compress_chunked_adaptive(&[], 16);
+
+        assert_eq!(archive.chunk_count(), 0);
+        assert_eq!(archive.decompress().unwrap(), Vec::<u8>::new());
+    }
+}
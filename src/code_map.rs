@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::huffman_table::HuffmanTable;
+
+// the inverse of a table, from each (value, bit_count) code to the byte it was written for, for
+// decoders that walk the input a bit at a time and check each completed code. two bytes sharing a
+// code is a debug assertion failure, and otherwise the lowest byte keeps it
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CodeMap {
+    bytes: HashMap<(u32, u8), u8>,
+}
+
+impl CodeMap {
+    pub fn get(&self, value: u32, bit_count: u8) -> Option<u8> {
+        self.bytes.get(&(value, bit_count)).copied()
+    }
+
+    // the number of bytes with a code
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    // roughly the heap bytes taken up by the map: an entry plus a byte of the hash map's own
+    // bookkeeping for each slot it has room for
+    pub fn memory_bytes(&self) -> usize {
+        self.bytes.capacity() * (std::mem::size_of::<((u32, u8), u8)>() + 1)
+    }
+}
+
+impl From<&HuffmanTable> for CodeMap {
+    fn from(table: &HuffmanTable) -> CodeMap {
+        let mut bytes = HashMap::new();
+
+        for byte in 0..=255 {
+            let bit_count = table.get_compressed_value_bit_count(byte);
+            if bit_count == 0 {
+                continue;
+            }
+
+            let value = table.get_compressed_value(byte);
+            let shared_with = *bytes.entry((value, bit_count)).or_insert(byte);
+            debug_assert_eq!(
+                shared_with, byte,
+                "bytes 0x{:02X} and 0x{:02X} share a code",
+                shared_with, byte
+            );
+        }
+
+        CodeMap { bytes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_maps_every_code_back_to_its_byte() {
+        let table = HuffmanTable::from_bytes(b"the quick brown fox jumps over the lazy dog");
+
+        let code_map = CodeMap::from(&table);
+
+        for byte in 0..=255 {
+            let bit_count = table.get_compressed_value_bit_count(byte);
+            if bit_count > 0 {
+                let value = table.get_compressed_value(byte);
+                assert_eq!(code_map.get(value, bit_count), Some(byte));
+            }
+        }
+        assert_eq!(code_map.len(), 27);
+    }
+
+    #[test]
+    fn it_distinguishes_codes_by_bit_count() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x01] = 0b0;
+        bit_counts[0x01] = 1;
+
+        values[0x02] = 0b10;
+        bit_counts[0x02] = 2;
+
+        values[0x03] = 0b11;
+        bit_counts[0x03] = 2;
+
+        let code_map = CodeMap::from(&HuffmanTable { values, bit_counts });
+
+        assert_eq!(code_map.get(0b0, 1), Some(0x01));
+        assert_eq!(code_map.get(0b00, 2), None);
+        assert_eq!(code_map.get(0b1, 1), None);
+        assert_eq!(code_map.get(0b11, 2), Some(0x03));
+    }
+
+    #[test]
+    fn it_is_empty_for_a_table_with_no_codes() {
+        let code_map = CodeMap::from(&HuffmanTable::from_bytes(&[]));
+
+        assert!(code_map.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "bytes 0x05 and 0x09 share a code")]
+    fn it_panics_in_debug_builds_when_two_bytes_share_a_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x05] = 0b101;
+        bit_counts[0x05] = 3;
+
+        values[0x09] = 0b101;
+        bit_counts[0x09] = 3;
+
+        let _ = CodeMap::from(&HuffmanTable { values, bit_counts });
+    }
+
+    #[test]
+    fn it_reports_memory_bytes_that_grow_with_the_number_of_codes() {
+        let small = CodeMap::from(&HuffmanTable::from_bytes(b"abcd"));
+        let large = CodeMap::from(&HuffmanTable::from_bytes(
+            b"the quick brown fox jumps over the lazy dog",
+        ));
+
+        assert!(small.memory_bytes() >= small.len() * std::mem::size_of::<((u32, u8), u8)>());
+        assert!(large.memory_bytes() > small.memory_bytes());
+    }
+}
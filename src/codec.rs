@@ -0,0 +1,136 @@
+use crate::compressor::{CompressError, Compressor};
+use crate::decode_tree::{DecodeTree, DecodeTreeError};
+use crate::decompressor::{DecodeError, Decompressor};
+use crate::huffman_table::HuffmanTable;
+use crate::terminal_code::TerminalCode;
+
+// a table and terminal code along with the decode tree built from them, so the same value can
+// both encode and decode without rebuilding the tree every time
+pub struct Codec {
+    table: HuffmanTable,
+    terminal_code: Option<TerminalCode>,
+    tree: DecodeTree,
+}
+
+impl Codec {
+    // errs if the codes in the table, or the terminal code, aren't prefix free
+    pub fn new(
+        table: HuffmanTable,
+        terminal_code: Option<TerminalCode>,
+    ) -> Result<Codec, DecodeTreeError> {
+        let tree = DecodeTree::new(&table, terminal_code.as_ref())?;
+
+        Ok(Codec {
+            table,
+            terminal_code,
+            tree,
+        })
+    }
+
+    // the optimal table for the frequencies, with a terminal code so decode knows where the
+    // data ends
+    pub fn from_frequencies(freqs: &[u64; 256]) -> Codec {
+        let (table, terminal_code) = HuffmanTable::from_frequencies_with_eof(freqs);
+
+        Codec::new(table, Some(terminal_code)).expect("built codes are prefix free")
+    }
+
+    pub fn table(&self) -> &HuffmanTable {
+        &self.table
+    }
+
+    pub fn terminal_code(&self) -> Option<&TerminalCode> {
+        self.terminal_code.as_ref()
+    }
+
+    pub fn encode(&self, src: &[u8]) -> Result<Vec<u8>, CompressError> {
+        let mut compressor = Compressor::new(&self.table);
+        let mut output = Vec::new();
+
+        for &byte in src {
+            compressor.compress_byte(byte)?;
+            compressor.drain_into(&mut output);
+        }
+
+        if let Some(terminal_code) = &self.terminal_code {
+            compressor.append_terminal_code(terminal_code)?;
+        }
+
+        compressor.end();
+        compressor.drain_into(&mut output);
+
+        Ok(output)
+    }
+
+    // decodes everything encode wrote. without a terminal code the padding at the end can
+    // decode as extra bytes, so use a Decompressor with an expected length instead
+    pub fn decode(&self, compressed: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut decompressor = Decompressor::new(&self.tree, compressed);
+        let output = decompressor.by_ref().collect();
+
+        match decompressor.error() {
+            Some(err) => Err(err.clone()),
+            None => Ok(output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frequencies::count_frequencies;
+
+    #[test]
+    fn it_round_trips_input_with_a_codec_built_from_frequencies() {
+        let mut src = Vec::new();
+        let mut state: u32 = 0x2545_F491;
+        for _ in 0..5000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            src.push((state % 64) as u8 + b' ');
+        }
+
+        let codec = Codec::from_frequencies(&count_frequencies(&src));
+
+        for len in [0, 1, 7, 100, src.len()] {
+            let encoded = codec.encode(&src[..len]).unwrap();
+
+            assert_eq!(codec.decode(&encoded).unwrap(), &src[..len]);
+        }
+    }
+
+    #[test]
+    fn it_errs_on_a_byte_without_a_code() {
+        let codec = Codec::from_frequencies(&count_frequencies(b"abc"));
+
+        assert_eq!(
+            codec.encode(b"abd"),
+            Err(CompressError::NoCodeForByte(b'd'))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_truncated_input() {
+        let src = b"it was the best of times, it was the worst of times";
+        let codec = Codec::from_frequencies(&count_frequencies(src));
+        let encoded = codec.encode(src).unwrap();
+
+        assert!(matches!(
+            codec.decode(&encoded[..encoded.len() - 3]),
+            Err(DecodeError::UnexpectedEnd { .. })
+        ));
+    }
+
+    #[test]
+    fn it_errs_on_codes_that_are_not_prefix_free() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+        values[0x01] = 0b01;
+        bit_counts[0x01] = 2;
+
+        assert!(Codec::new(HuffmanTable { values, bit_counts }, None).is_err());
+    }
+}
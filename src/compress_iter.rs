@@ -0,0 +1,142 @@
+use crate::compressor::{CompressError, Compressor};
+use crate::huffman_table::HuffmanTable;
+use crate::terminal_code::TerminalCode;
+
+// lazily compresses src, yielding each compressed byte as soon as the buffer holds it. once src
+// runs out the terminal code (if any) and padding are appended before the iterator ends. a byte
+// that can't be compressed stops the iteration early, with the reason in error()
+pub struct CompressIter<'a> {
+    compressor: Compressor<'a>,
+    src: std::slice::Iter<'a, u8>,
+    terminal_code: Option<&'a TerminalCode>,
+    ended: bool,
+    error: Option<CompressError>,
+}
+
+impl<'a> CompressIter<'a> {
+    pub(crate) fn new(
+        table: &'a HuffmanTable,
+        terminal_code: Option<&'a TerminalCode>,
+        src: &'a [u8],
+    ) -> CompressIter<'a> {
+        CompressIter {
+            compressor: Compressor::new(table),
+            src: src.iter(),
+            terminal_code,
+            ended: false,
+            error: None,
+        }
+    }
+
+    // set once compressing has stopped on a byte (or terminal code) that couldn't be written
+    pub fn error(&self) -> Option<&CompressError> {
+        self.error.as_ref()
+    }
+
+    fn fail(&mut self, err: CompressError) {
+        self.error = Some(err);
+        self.ended = true;
+    }
+}
+
+impl Iterator for CompressIter<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            if let Some(compressed_byte) = self.compressor.next() {
+                return Some(compressed_byte);
+            }
+
+            if self.ended {
+                return None;
+            }
+
+            match self.src.next() {
+                Some(&byte) => {
+                    if let Err(err) = self.compressor.compress_byte(byte) {
+                        self.fail(err);
+                    }
+                }
+                None => {
+                    if let Some(terminal_code) = self.terminal_code {
+                        if let Err(err) = self.compressor.append_terminal_code(terminal_code) {
+                            self.fail(err);
+                            continue;
+                        }
+                    }
+
+                    self.compressor.end();
+                    self.ended = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Huffman;
+
+    fn huffman(terminal_code: Option<TerminalCode>) -> Huffman {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x02] = 0b110;
+        bit_counts[0x02] = 3;
+
+        Huffman::new(HuffmanTable { values, bit_counts }, terminal_code)
+    }
+
+    #[test]
+    fn it_yields_the_same_bytes_as_compress() {
+        let src = [
+            0x02, 0x00, 0x01, 0x01, 0x02, 0x00, 0x00, 0x02, 0x01, 0x02, 0x02,
+        ];
+
+        for terminal_code in [
+            None,
+            Some(TerminalCode {
+                bit_count: 3,
+                value: 0b111,
+            }),
+        ] {
+            let mut huffman = huffman(terminal_code);
+
+            let mut output = Vec::new();
+            huffman.compress(&src, &mut output).unwrap();
+
+            let collected: Vec<u8> = huffman.compress_iter(&src).collect();
+
+            assert_eq!(collected, output);
+        }
+    }
+
+    #[test]
+    fn it_yields_nothing_for_empty_input_without_a_terminal_code() {
+        let huffman = huffman(None);
+
+        assert_eq!(huffman.compress_iter(&[]).count(), 0);
+    }
+
+    #[test]
+    fn it_stops_and_reports_a_byte_without_a_code() {
+        let huffman = huffman(None);
+
+        let mut compress_iter = huffman.compress_iter(&[0x01, 0x01, 0x01, 0x01, 0x03, 0x00]);
+        let collected: Vec<u8> = compress_iter.by_ref().collect();
+
+        assert_eq!(collected, vec![0b10_10_10_10]);
+        assert_eq!(
+            compress_iter.error(),
+            Some(&CompressError::NoCodeForByte(0x03))
+        );
+    }
+}
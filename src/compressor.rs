@@ -1,35 +1,122 @@
+mod accumulator;
 mod buffer;
+mod wide;
+
+pub use crate::compressor::accumulator::BitAccumulator;
+pub use crate::compressor::buffer::{BitOrder, BufferError};
+pub use crate::compressor::wide::WideCompressor;
 
 use crate::compressor::buffer::CompressorBuffer;
 use crate::huffman_table::HuffmanTable;
 use crate::terminal_code::TerminalCode;
 
-pub struct Compressor<'a> {
+use std::error::Error;
+use std::fmt;
+
+// buffers pending bits in an A, a u64 unless with_accumulator picks another. a code that
+// doesn't fit alongside the bits not yet read out errs with BufferError::Overflow, so a u32 can
+// need draining after every byte for tables with long codes
+pub struct Compressor<'a, A: BitAccumulator = u64> {
     table: &'a HuffmanTable,
-    buffer: CompressorBuffer,
+    buffer: CompressorBuffer<A>,
 }
 
 impl<'a> Compressor<'a> {
     pub fn new(table: &'a HuffmanTable) -> Self {
+        Compressor::with_bit_order(table, BitOrder::default())
+    }
+
+    pub fn with_bit_order(table: &'a HuffmanTable, bit_order: BitOrder) -> Self {
+        Compressor::with_accumulator(table, bit_order)
+    }
+}
+
+impl<'a, A: BitAccumulator> Compressor<'a, A> {
+    // e.g. Compressor::<u32>::with_accumulator(&table, BitOrder::MsbFirst)
+    pub fn with_accumulator(table: &'a HuffmanTable, bit_order: BitOrder) -> Self {
         Compressor {
             table,
-            buffer: CompressorBuffer::new(),
+            buffer: CompressorBuffer::with_accumulator(bit_order),
         }
     }
 
-    pub fn compress_byte(&mut self, byte: u8) {
+    pub fn compress_byte(&mut self, byte: u8) -> Result<(), CompressError> {
         let value = self.table.get_compressed_value(byte);
         let bit_count = self.table.get_compressed_value_bit_count(byte);
-        self.buffer.write_bits(value, bit_count);
+
+        if bit_count == 0 {
+            return Err(CompressError::NoCodeForByte(byte));
+        }
+
+        self.buffer.write_bits(value, bit_count)?;
+
+        Ok(())
+    }
+
+    // compresses application symbols by mapping each one to the byte whose code it should be
+    // written with, draining complete bytes onto out as it goes. the compressor isn't ended, so
+    // more can follow
+    pub fn compress_mapped<T, I, F>(
+        &mut self,
+        src: I,
+        mut map: F,
+        out: &mut Vec<u8>,
+    ) -> Result<(), CompressError>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(T) -> u8,
+    {
+        for symbol in src {
+            self.compress_byte(map(symbol))?;
+            self.drain_into(out);
+        }
+
+        Ok(())
+    }
+
+    // discards anything not yet read out so the compressor can start on a new message with the
+    // same table
+    pub fn reset(&mut self) {
+        self.buffer.reset();
+    }
+
+    pub fn available_bytes(&self) -> u8 {
+        self.buffer.available_bytes()
+    }
+
+    // the bits compressed but not yet read out as bytes, see CompressorBuffer::peek_pending
+    pub fn pending_bit_count(&self) -> u8 {
+        self.buffer.pending_bit_count()
+    }
+
+    pub fn peek_pending(&self) -> u32 {
+        self.buffer.peek_pending()
+    }
+
+    // pushes every complete byte compressed so far onto out, returning how many were pushed
+    pub fn drain_into(&mut self, out: &mut Vec<u8>) -> usize {
+        let available_bytes = self.available_bytes() as usize;
+        out.reserve(available_bytes);
+
+        while let Some(compressed_byte) = self.buffer.read_byte() {
+            out.push(compressed_byte);
+        }
+
+        available_bytes
     }
 
     fn get_compressed_byte(&mut self) -> Option<u8> {
         self.buffer.read_byte()
     }
 
-    pub fn append_terminal_code(&mut self, terminal_code: &TerminalCode) {
+    pub fn append_terminal_code(
+        &mut self,
+        terminal_code: &TerminalCode,
+    ) -> Result<(), CompressError> {
         self.buffer
-            .write_bits(terminal_code.value, terminal_code.bit_count);
+            .write_bits(terminal_code.value, terminal_code.bit_count)?;
+
+        Ok(())
     }
 
     pub fn end(&mut self) {
@@ -38,12 +125,15 @@ impl<'a> Compressor<'a> {
         if byte_boundary_offset != 0 {
             let padding_value = 0b0;
             let padding_bit_count = 8 - byte_boundary_offset;
-            self.buffer.write_bits(padding_value, padding_bit_count);
+            // the capacity is a whole number of bytes, so padding to the next boundary always fits
+            self.buffer
+                .write_bits(padding_value, padding_bit_count)
+                .expect("padding overflowed the buffer");
         }
     }
 }
 
-impl<'a> Iterator for Compressor<'a> {
+impl<A: BitAccumulator> Iterator for Compressor<'_, A> {
     type Item = u8;
 
     fn next(&mut self) -> Option<u8> {
@@ -51,7 +141,44 @@ impl<'a> Iterator for Compressor<'a> {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CompressError {
+    Expanding,
+    MissingPrefix,
+    NoCodeForByte(u8),
+    NoCodeForSymbol(u16),
+    Buffer(BufferError),
+    // the output slice filled up before everything was written
+    OutputTooSmall,
+}
+
+impl fmt::Display for CompressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompressError::Expanding => write!(f, "compressed output would be larger than input"),
+            CompressError::MissingPrefix => write!(f, "input does not start with the prefix"),
+            CompressError::NoCodeForByte(byte) => {
+                write!(f, "byte 0x{:02X} has no code in the table", byte)
+            }
+            CompressError::NoCodeForSymbol(symbol) => {
+                write!(f, "symbol {} has no code in the table", symbol)
+            }
+            CompressError::Buffer(err) => write!(f, "{}", err),
+            CompressError::OutputTooSmall => write!(f, "output is too small"),
+        }
+    }
+}
+
+impl Error for CompressError {}
+
+impl From<BufferError> for CompressError {
+    fn from(err: BufferError) -> CompressError {
+        CompressError::Buffer(err)
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
 mod tests {
     use super::*;
 
@@ -81,7 +208,7 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0x1F);
+        compressor.compress_byte(0x1F).unwrap();
 
         let result = compressor.next();
 
@@ -100,7 +227,7 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0x08);
+        compressor.compress_byte(0x08).unwrap();
 
         compressor.end();
 
@@ -121,8 +248,8 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0xFF);
-        compressor.compress_byte(0xFF);
+        compressor.compress_byte(0xFF).unwrap();
+        compressor.compress_byte(0xFF).unwrap();
 
         let result = compressor.next();
 
@@ -141,8 +268,8 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0xAD);
-        compressor.compress_byte(0xAD);
+        compressor.compress_byte(0xAD).unwrap();
+        compressor.compress_byte(0xAD).unwrap();
 
         let result = compressor.next();
 
@@ -171,9 +298,9 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0x0B);
-        compressor.compress_byte(0x11);
-        compressor.compress_byte(0x9D);
+        compressor.compress_byte(0x0B).unwrap();
+        compressor.compress_byte(0x11).unwrap();
+        compressor.compress_byte(0x9D).unwrap();
 
         let result = compressor.next();
 
@@ -181,8 +308,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "attempt to shift left with overflow")]
-    fn it_panics_when_attempting_to_compress_to_a_single_32_bit_value() {
+    fn it_compresses_to_a_single_32_bit_value() {
         let mut values = [0; 256];
         let mut bit_counts = [0; 256];
 
@@ -193,12 +319,13 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0x3C);
+        compressor.compress_byte(0x3C).unwrap();
+
+        assert_eq!(compressor.collect::<Vec<u8>>(), vec![0xFF; 4]);
     }
 
     #[test]
-    #[should_panic(expected = "attempt to shift right with overflow")]
-    fn it_panics_when_next_is_called_and_compressed_values_exceed_32_bits() {
+    fn it_errs_when_compressed_values_exceed_64_bits() {
         let mut values = [0; 256];
         let mut bit_counts = [0; 256];
 
@@ -209,11 +336,39 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0x77); // compressed values = 16 bits
-        compressor.compress_byte(0x77); // compressed values = 32 bits
-        compressor.compress_byte(0x77); // compressed values = 48 bits
+        compressor.compress_byte(0x77).unwrap(); // compressed values = 16 bits
+        compressor.compress_byte(0x77).unwrap(); // compressed values = 32 bits
+        compressor.compress_byte(0x77).unwrap(); // compressed values = 48 bits
+        compressor.compress_byte(0x77).unwrap(); // compressed values = 64 bits
 
-        compressor.next();
+        assert_eq!(
+            compressor.compress_byte(0x77),
+            Err(CompressError::Buffer(BufferError::Overflow))
+        );
+    }
+
+    #[test]
+    fn it_errs_instead_of_panicking_on_a_32_bit_code_when_the_buffer_is_full() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x3C] = u32::MAX;
+        bit_counts[0x3C] = 32;
+
+        values[0x3D] = 0b1;
+        bit_counts[0x3D] = 8;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut compressor = Compressor::new(&table);
+
+        compressor.compress_byte(0x3C).unwrap(); // compressed values = 32 bits
+        compressor.compress_byte(0x3D).unwrap(); // compressed values = 40 bits
+
+        assert_eq!(
+            compressor.compress_byte(0x3C),
+            Err(CompressError::Buffer(BufferError::Overflow))
+        );
     }
 
     #[test]
@@ -228,11 +383,11 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0x77); // compressed values = 16 bits
+        compressor.compress_byte(0x77).unwrap(); // compressed values = 16 bits
         compressor.next(); // compressed values = 8 bits
-        compressor.compress_byte(0x77); // compressed values = 24 bits
+        compressor.compress_byte(0x77).unwrap(); // compressed values = 24 bits
         compressor.next(); // compressed values = 16 bits
-        compressor.compress_byte(0x77); // compressed values = 32 bits
+        compressor.compress_byte(0x77).unwrap(); // compressed values = 32 bits
         compressor.next();
     }
 
@@ -248,15 +403,216 @@ mod tests {
 
         let mut compressor = Compressor::new(&table);
 
-        compressor.compress_byte(0x12);
+        compressor.compress_byte(0x12).unwrap();
 
         let termination_code = TerminalCode {
             value: 0b100001,
             bit_count: 6,
         };
 
-        compressor.append_terminal_code(&termination_code);
+        compressor.append_terminal_code(&termination_code).unwrap();
 
         assert_eq!(compressor.next(), Some(0b11_100001));
     }
+
+    #[test]
+    fn it_returns_the_number_of_complete_bytes_available() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x01] = 0b10101;
+        bit_counts[0x01] = 5;
+
+        values[0x02] = 0b11110000;
+        bit_counts[0x02] = 8;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut compressor = Compressor::new(&table);
+
+        compressor.compress_byte(0x01).unwrap();
+        compressor.compress_byte(0x01).unwrap();
+        compressor.compress_byte(0x01).unwrap();
+        compressor.compress_byte(0x01).unwrap(); // 20 bits
+
+        assert_eq!(compressor.available_bytes(), 2);
+
+        compressor.compress_byte(0x02).unwrap(); // 28 bits
+
+        assert_eq!(compressor.available_bytes(), 3);
+    }
+
+    #[test]
+    fn it_errors_when_compressing_a_byte_without_a_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x12] = 0b11;
+        bit_counts[0x12] = 2;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut compressor = Compressor::new(&table);
+
+        assert_eq!(compressor.compress_byte(0x12), Ok(()));
+        assert_eq!(
+            compressor.compress_byte(0x13),
+            Err(CompressError::NoCodeForByte(0x13))
+        );
+    }
+
+    #[test]
+    fn it_writes_the_same_bits_for_repeated_bytes_as_for_fresh_lookups() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x61] = 0b101;
+        bit_counts[0x61] = 3;
+
+        values[0x62] = 0b0;
+        bit_counts[0x62] = 2;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let src = [0x61, 0x61, 0x61, 0x62, 0x61, 0x62, 0x62, 0x62, 0x61, 0x61];
+
+        let mut compressor = Compressor::new(&table);
+        let mut output = Vec::new();
+
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        let mut expected_output = Vec::new();
+
+        for byte in src {
+            compressor.compress_byte(byte).unwrap();
+            output.extend(&mut compressor);
+
+            buffer
+                .write_bits(values[byte as usize], bit_counts[byte as usize])
+                .unwrap();
+            while let Some(expected_byte) = buffer.read_byte() {
+                expected_output.push(expected_byte);
+            }
+        }
+
+        assert_eq!(output, expected_output);
+    }
+
+    #[test]
+    fn it_drains_the_same_bytes_as_iterating() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x0B] = 0b11;
+        bit_counts[0x0B] = 2;
+
+        values[0x11] = 0b0110_1001_1;
+        bit_counts[0x11] = 9;
+
+        values[0x9D] = 0b0010;
+        bit_counts[0x9D] = 4;
+
+        let table = HuffmanTable { values, bit_counts };
+        let src = [0x0B, 0x11, 0x11, 0x9D, 0x0B, 0x11, 0x9D, 0x9D];
+
+        let mut compressor = Compressor::new(&table);
+        let mut output = Vec::new();
+        let mut drained_count = 0;
+        for &byte in &src {
+            compressor.compress_byte(byte).unwrap();
+            drained_count += compressor.drain_into(&mut output);
+        }
+        compressor.end();
+        drained_count += compressor.drain_into(&mut output);
+
+        let mut other_compressor = Compressor::new(&table);
+        let mut expected_output = Vec::new();
+        for &byte in &src {
+            other_compressor.compress_byte(byte).unwrap();
+            expected_output.extend(&mut other_compressor);
+        }
+        other_compressor.end();
+        expected_output.extend(&mut other_compressor);
+
+        assert_eq!(output, expected_output);
+        assert_eq!(drained_count, output.len());
+        assert_eq!(compressor.drain_into(&mut output), 0);
+    }
+
+    #[test]
+    fn it_compresses_the_same_after_a_reset_as_a_new_compressor() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x0B] = 0b11;
+        bit_counts[0x0B] = 2;
+
+        values[0x11] = 0b0110_1001_1;
+        bit_counts[0x11] = 9;
+
+        values[0x9D] = 0b0010;
+        bit_counts[0x9D] = 4;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let message_a = [0x0B, 0x11, 0x9D];
+        let message_b = [0x9D, 0x9D, 0x11, 0x0B, 0x0B];
+
+        let compress = |compressor: &mut Compressor, message: &[u8]| {
+            let mut output = Vec::new();
+            for &byte in message {
+                compressor.compress_byte(byte).unwrap();
+            }
+            compressor.end();
+            compressor.drain_into(&mut output);
+            output
+        };
+
+        let mut compressor = Compressor::new(&table);
+        let output_a = compress(&mut compressor, &message_a);
+        // leave some bits pending so the reset has something to discard
+        compressor.compress_byte(0x9D).unwrap();
+        compressor.reset();
+        let output_b = compress(&mut compressor, &message_b);
+
+        assert_eq!(output_a, compress(&mut Compressor::new(&table), &message_a));
+        assert_eq!(output_b, compress(&mut Compressor::new(&table), &message_b));
+    }
+
+    #[test]
+    fn it_compresses_the_same_with_a_32_bit_accumulator_as_a_64_bit_one() {
+        // fibonacci frequencies limited to 25 bit codes, so a code always fits alongside the
+        // 7 or fewer bits left pending after draining
+        let mut counts = [0; 256];
+        let (mut previous, mut current) = (1u64, 1u64);
+        for count in counts.iter_mut().take(40) {
+            *count = current;
+            (previous, current) = (current, previous + current);
+        }
+        let table = HuffmanTable::length_limited_from_frequencies(&counts, 25);
+        assert_eq!(*table.bit_counts.iter().max().unwrap(), 25);
+
+        let src: Vec<u8> = (0..2000u32).map(|i| (i * 7919 % 40) as u8).collect();
+
+        for bit_order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+            let mut compressor = Compressor::with_bit_order(&table, bit_order);
+            let mut narrow_compressor = Compressor::<u32>::with_accumulator(&table, bit_order);
+            let mut output = Vec::new();
+            let mut narrow_output = Vec::new();
+
+            for &byte in &src {
+                compressor.compress_byte(byte).unwrap();
+                compressor.drain_into(&mut output);
+
+                narrow_compressor.compress_byte(byte).unwrap();
+                narrow_output.extend(&mut narrow_compressor);
+            }
+
+            compressor.end();
+            compressor.drain_into(&mut output);
+            narrow_compressor.end();
+            narrow_output.extend(&mut narrow_compressor);
+
+            assert_eq!(narrow_output, output);
+        }
+    }
 }
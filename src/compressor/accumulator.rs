@@ -0,0 +1,119 @@
+// an unsigned integer that CompressorBuffer collects pending bits in. a wider accumulator fits
+// longer codes alongside the bits waiting to be read, a narrower one is smaller
+pub trait BitAccumulator: Copy {
+    // the number of bits it holds
+    const CAPACITY: u8;
+
+    const ZERO: Self;
+
+    // the low bits of value, dropping any that don't fit
+    fn from_bits(value: u64) -> Self;
+
+    fn to_bits(self) -> u64;
+
+    // shifting by the full width or more gives zero rather than overflowing
+    fn shl_or_zero(self, shift: u8) -> Self;
+
+    fn shr_or_zero(self, shift: u8) -> Self;
+
+    fn or(self, other: Self) -> Self;
+
+    // just the low bit_count bits, or every bit if bit_count is the full width or more
+    fn low_bits(self, bit_count: u8) -> Self;
+}
+
+impl BitAccumulator for u32 {
+    const CAPACITY: u8 = 32;
+
+    const ZERO: u32 = 0;
+
+    fn from_bits(value: u64) -> u32 {
+        value as u32
+    }
+
+    fn to_bits(self) -> u64 {
+        self as u64
+    }
+
+    fn shl_or_zero(self, shift: u8) -> u32 {
+        self.checked_shl(shift as u32).unwrap_or(0)
+    }
+
+    fn shr_or_zero(self, shift: u8) -> u32 {
+        self.checked_shr(shift as u32).unwrap_or(0)
+    }
+
+    fn or(self, other: u32) -> u32 {
+        self | other
+    }
+
+    fn low_bits(self, bit_count: u8) -> u32 {
+        self & u32::MAX
+            .checked_shr(32 - bit_count.min(32) as u32)
+            .unwrap_or(0)
+    }
+}
+
+impl BitAccumulator for u64 {
+    const CAPACITY: u8 = 64;
+
+    const ZERO: u64 = 0;
+
+    fn from_bits(value: u64) -> u64 {
+        value
+    }
+
+    fn to_bits(self) -> u64 {
+        self
+    }
+
+    fn shl_or_zero(self, shift: u8) -> u64 {
+        self.checked_shl(shift as u32).unwrap_or(0)
+    }
+
+    fn shr_or_zero(self, shift: u8) -> u64 {
+        self.checked_shr(shift as u32).unwrap_or(0)
+    }
+
+    fn or(self, other: u64) -> u64 {
+        self | other
+    }
+
+    fn low_bits(self, bit_count: u8) -> u64 {
+        self & u64::MAX
+            .checked_shr(64 - bit_count.min(64) as u32)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_the_low_bits() {
+        assert_eq!(0b1011u32.low_bits(0), 0);
+        assert_eq!(0b1011u32.low_bits(2), 0b11);
+        assert_eq!(u32::MAX.low_bits(32), u32::MAX);
+        assert_eq!(u32::MAX.low_bits(40), u32::MAX);
+
+        assert_eq!(0b1011u64.low_bits(0), 0);
+        assert_eq!(0b1011u64.low_bits(3), 0b011);
+        assert_eq!(u64::MAX.low_bits(64), u64::MAX);
+    }
+
+    #[test]
+    fn it_shifts_to_zero_by_the_full_width() {
+        assert_eq!(1u32.shl_or_zero(32), 0);
+        assert_eq!(u32::MAX.shr_or_zero(32), 0);
+        assert_eq!(1u64.shl_or_zero(64), 0);
+        assert_eq!(u64::MAX.shr_or_zero(64), 0);
+        assert_eq!(1u32.shl_or_zero(31), 1 << 31);
+    }
+
+    #[test]
+    fn it_truncates_bits_that_do_not_fit() {
+        assert_eq!(u32::from_bits(0x1_0000_0001), 1);
+        assert_eq!(u64::from_bits(0x1_0000_0001), 0x1_0000_0001);
+    }
+}
@@ -1,20 +1,73 @@
-pub struct CompressorBuffer {
-    compressed_bits: u32,
+use std::error::Error;
+use std::fmt;
+
+use crate::compressor::accumulator::BitAccumulator;
+
+// the order bits are packed into each output byte:
+// - MsbFirst: from the most significant bit down, with each code's most significant bit first
+// - LsbFirst: from the least significant bit up, with each code's most significant bit first, as
+//   in DEFLATE. every output byte is the bit reversal of the MsbFirst one
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum BitOrder {
+    #[default]
+    MsbFirst,
+    LsbFirst,
+}
+
+// holds at most A::CAPACITY bits at once
+pub struct CompressorBuffer<A: BitAccumulator = u64> {
+    bit_order: BitOrder,
+    compressed_bits: A,
     compressed_bit_count: u8,
 }
 
 impl CompressorBuffer {
-    pub fn new() -> Self {
+    pub fn new(bit_order: BitOrder) -> Self {
+        CompressorBuffer::with_accumulator(bit_order)
+    }
+}
+
+impl<A: BitAccumulator> CompressorBuffer<A> {
+    pub fn with_accumulator(bit_order: BitOrder) -> Self {
         Self {
-            compressed_bits: 0,
+            bit_order,
+            compressed_bits: A::ZERO,
             compressed_bit_count: 0,
         }
     }
 
-    pub fn write_bits(&mut self, value: u32, bit_count: u8) {
-        self.compressed_bits = self.compressed_bits << bit_count;
-        self.compressed_bits = self.compressed_bits | value;
-        self.compressed_bit_count = self.compressed_bit_count + bit_count;
+    pub fn write_bits(&mut self, value: u32, bit_count: u8) -> Result<(), BufferError> {
+        if bit_count > A::CAPACITY - self.compressed_bit_count {
+            return Err(BufferError::Overflow);
+        }
+
+        if bit_count == 0 {
+            return Ok(());
+        }
+
+        // bits of value above bit_count aren't part of the code and would otherwise be ORed over
+        // the pending bits
+        let value = value.low_bits(bit_count) as u64;
+
+        match self.bit_order {
+            BitOrder::MsbFirst => {
+                self.compressed_bits = self
+                    .compressed_bits
+                    .shl_or_zero(bit_count)
+                    .or(A::from_bits(value));
+            }
+            BitOrder::LsbFirst => {
+                // new bits go above the pending ones, reversed so the code's first bit is lowest
+                let reversed_value = value.reverse_bits() >> (64 - bit_count);
+                self.compressed_bits = self
+                    .compressed_bits
+                    .or(A::from_bits(reversed_value).shl_or_zero(self.compressed_bit_count));
+            }
+        }
+
+        self.compressed_bit_count += bit_count;
+
+        Ok(())
     }
 
     pub fn read_byte(&mut self) -> Option<u8> {
@@ -22,93 +75,196 @@ impl CompressorBuffer {
             return None;
         }
 
-        self.compressed_bit_count = self.compressed_bit_count - 8;
+        self.compressed_bit_count -= 8;
 
-        let byte = self.compressed_bits >> self.compressed_bit_count;
-
-        let mask = if self.compressed_bit_count > 0 {
-            u32::MAX >> (32 - self.compressed_bit_count)
-        } else {
-            0
+        let byte = match self.bit_order {
+            BitOrder::MsbFirst => {
+                let byte = self.compressed_bits.shr_or_zero(self.compressed_bit_count);
+                self.compressed_bits = self.compressed_bits.low_bits(self.compressed_bit_count);
+                byte
+            }
+            BitOrder::LsbFirst => {
+                let byte = self.compressed_bits;
+                self.compressed_bits = self.compressed_bits.shr_or_zero(8);
+                byte
+            }
         };
 
-        self.compressed_bits = self.compressed_bits & mask;
+        // casting truncates to the low byte. benches/byte_extraction.rs times it level with
+        // to_be_bytes()[7], and masking with (1 << 8) - 1 before casting no faster and in some
+        // runs noticeably slower, so the plain cast stays
+        Some(byte.to_bits() as u8)
+    }
+
+    // discards every pending bit
+    pub fn reset(&mut self) {
+        self.compressed_bits = A::ZERO;
+        self.compressed_bit_count = 0;
+    }
 
-        Some(byte as u8) // what impact on performance does this casting have?
+    pub fn available_bytes(&self) -> u8 {
+        self.compressed_bit_count / 8
     }
 
     pub fn byte_boundary_offset(&self) -> u8 {
         self.compressed_bit_count % 8
     }
+
+    // the number of bits written but not yet read out as bytes
+    pub fn pending_bit_count(&self) -> u8 {
+        self.compressed_bit_count
+    }
+
+    // the pending bits, without consuming them, in the low pending_bit_count bits of the result.
+    // they're in the buffer's own order: for MsbFirst the earliest written bit is the highest,
+    // for LsbFirst the lowest. if more than 32 bits are pending only the low 32 are returned
+    pub fn peek_pending(&self) -> u32 {
+        self.compressed_bits.to_bits() as u32
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BufferError {
+    // the bits being written don't fit alongside the bits already waiting to be read
+    Overflow,
+}
+
+impl fmt::Display for BufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BufferError::Overflow => write!(f, "too many bits for the compressor buffer"),
+        }
+    }
 }
 
+impl Error for BufferError {}
+
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
 mod tests {
     use super::*;
 
+    // the alternative extraction read_byte was measured against, which must always agree with it
+    fn read_byte_via_be_bytes(buffer: &mut CompressorBuffer) -> Option<u8> {
+        if buffer.compressed_bit_count < 8 {
+            return None;
+        }
+
+        buffer.compressed_bit_count -= 8;
+
+        let byte = (buffer.compressed_bits >> buffer.compressed_bit_count).to_be_bytes()[7];
+
+        buffer.compressed_bits &= (1 << buffer.compressed_bit_count) - 1;
+
+        Some(byte)
+    }
+
     #[test]
     fn it_has_a_constructor_that_intialises_an_empty_buffer() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         assert_eq!(buffer.read_byte(), None);
     }
 
     #[test]
     fn it_can_write_bits() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         let value: u32 = 0xBBB;
         let bit_count = 12;
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
     }
 
     #[test]
     fn it_can_read_bytes() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         let value = 0b1;
         let bit_count = 8;
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.read_byte(), Some(0b00000001))
     }
 
     #[test]
     fn it_consumes_a_byte_when_read_byte_is_called() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         let value = 0b1;
         let bit_count = 8;
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         buffer.read_byte();
         assert_eq!(buffer.read_byte(), None)
     }
 
     #[test]
     fn it_returns_none_when_trying_to_read_bytes_before_8_bits_have_been_written() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         let value = 0b101010;
         let bit_count = 6;
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.read_byte(), None)
     }
 
+    #[test]
+    fn it_ignores_bits_of_the_value_above_the_bit_count() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        buffer.write_bits(0b0, 1).unwrap();
+        buffer.write_bits(0b11, 1).unwrap();
+        buffer.write_bits(0b111, 2).unwrap();
+        buffer.write_bits(0, 4).unwrap();
+        assert_eq!(buffer.read_byte(), Some(0b0_1_11_0000));
+    }
+
+    #[test]
+    fn it_ignores_bits_of_the_value_above_the_bit_count_when_writing_lsb_first() {
+        let mut buffer = CompressorBuffer::new(BitOrder::LsbFirst);
+        buffer.write_bits(0b0, 1).unwrap();
+        buffer.write_bits(0b11, 1).unwrap();
+        buffer.write_bits(0, 6).unwrap();
+        assert_eq!(buffer.read_byte(), Some(0b0000_0010));
+    }
+
+    #[test]
+    fn it_peeks_at_the_pending_bits_without_consuming_them() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        buffer.write_bits(0b1011_0011, 8).unwrap();
+        buffer.write_bits(0b101, 3).unwrap();
+
+        assert_eq!(buffer.pending_bit_count(), 11);
+        assert_eq!(buffer.peek_pending(), 0b1011_0011_101);
+
+        assert_eq!(buffer.read_byte(), Some(0b1011_0011));
+        assert_eq!(buffer.pending_bit_count(), 3);
+        assert_eq!(buffer.peek_pending(), 0b101);
+        assert_eq!(buffer.peek_pending(), 0b101);
+    }
+
+    #[test]
+    fn it_peeks_at_the_pending_bits_lowest_first_when_writing_lsb_first() {
+        let mut buffer = CompressorBuffer::new(BitOrder::LsbFirst);
+        buffer.write_bits(0b110, 3).unwrap();
+
+        assert_eq!(buffer.pending_bit_count(), 3);
+        assert_eq!(buffer.peek_pending(), 0b011);
+    }
+
     #[test]
     fn it_can_read_bytes_in_between_writing_bits() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         let value = 0b101010;
         let bit_count = 6;
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.read_byte(), None);
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.read_byte(), Some(0b10101010));
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.read_byte(), Some(0b10101010))
     }
 
     #[test]
     fn it_can_read_multiple_bytes_in_a_row() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         let value = 0b10101010;
         let bit_count = 8;
-        buffer.write_bits(value, bit_count);
-        buffer.write_bits(value, bit_count);
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
+        buffer.write_bits(value, bit_count).unwrap();
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.read_byte(), Some(0b10101010));
         assert_eq!(buffer.read_byte(), Some(0b10101010));
         assert_eq!(buffer.read_byte(), Some(0b10101010));
@@ -117,34 +273,171 @@ mod tests {
 
     #[test]
     fn it_returns_the_byte_boundary_offset() {
-        let mut buffer = CompressorBuffer::new();
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
         let value = 0b1;
         let bit_count = 3;
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.byte_boundary_offset(), 3);
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.byte_boundary_offset(), 6);
-        buffer.write_bits(value, bit_count);
+        buffer.write_bits(value, bit_count).unwrap();
         assert_eq!(buffer.byte_boundary_offset(), 1);
     }
 
     #[test]
-    #[should_panic(expected = "attempt to shift left with overflow")]
-    fn it_panics_when_attempting_to_write_a_single_32_bit_value() {
-        let mut buffer = CompressorBuffer::new();
-        let value = 0xFFFFFFFF;
-        let bit_count = 32;
-        buffer.write_bits(value, bit_count);
+    fn it_returns_the_number_of_complete_bytes_available() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        assert_eq!(buffer.available_bytes(), 0);
+        buffer.write_bits(0b1, 7).unwrap();
+        assert_eq!(buffer.available_bytes(), 0);
+        buffer.write_bits(0b1, 9).unwrap();
+        assert_eq!(buffer.available_bytes(), 2);
+        buffer.read_byte();
+        assert_eq!(buffer.available_bytes(), 1);
     }
 
     #[test]
-    #[should_panic(expected = "attempt to shift right with overflow")]
-    fn it_panics_on_read_byte_when_buffer_exceeds_32_bits() {
-        let mut buffer = CompressorBuffer::new();
-        let value = 0xFFFFFFF;
-        let bit_count = 28;
-        buffer.write_bits(value, bit_count);
-        buffer.write_bits(value, bit_count);
-        buffer.read_byte();
+    fn it_reads_the_same_bytes_as_extracting_via_be_bytes() {
+        let writes: [&[(u32, u8)]; 4] = [
+            &[(0b1, 8)],
+            &[(0b101010, 6), (0b101010, 6), (0b101010, 6)],
+            &[(0b10101010, 8), (0b10101010, 8), (0b10101010, 8)],
+            &[(0b1, 3), (0xBBB, 12), (0b0, 5), (0x7F, 7)],
+        ];
+
+        for bits in writes {
+            let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+            let mut other_buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+
+            for &(value, bit_count) in bits {
+                buffer.write_bits(value, bit_count).unwrap();
+                other_buffer.write_bits(value, bit_count).unwrap();
+
+                loop {
+                    let byte = buffer.read_byte();
+                    assert_eq!(byte, read_byte_via_be_bytes(&mut other_buffer));
+
+                    if byte.is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_can_write_a_32_bit_value_while_bits_are_pending() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        buffer.write_bits(0b101, 3).unwrap();
+        buffer.write_bits(0xFFFFFFFF, 32).unwrap();
+        buffer.write_bits(0b0, 5).unwrap();
+        assert_eq!(buffer.read_byte(), Some(0b10111111));
+        assert_eq!(buffer.read_byte(), Some(0xFF));
+        assert_eq!(buffer.read_byte(), Some(0xFF));
+        assert_eq!(buffer.read_byte(), Some(0xFF));
+        assert_eq!(buffer.read_byte(), Some(0b11100000));
+        assert_eq!(buffer.read_byte(), None);
+    }
+
+    #[test]
+    fn it_can_fill_the_buffer_to_64_bits() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        buffer.write_bits(0xFFFFFFFF, 32).unwrap();
+        buffer.write_bits(0x1, 32).unwrap();
+        assert_eq!(buffer.available_bytes(), 8);
+        assert_eq!(buffer.read_byte(), Some(0xFF));
+    }
+
+    #[test]
+    fn it_errs_when_writing_more_bits_than_the_buffer_holds() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        assert_eq!(
+            buffer.write_bits(0xFFFFFFFF, 65),
+            Err(BufferError::Overflow)
+        );
+        assert_eq!(buffer.available_bytes(), 0);
+    }
+
+    #[test]
+    fn it_holds_32_bits_in_a_32_bit_accumulator() {
+        let mut buffer = CompressorBuffer::<u32>::with_accumulator(BitOrder::MsbFirst);
+        buffer.write_bits(0b1, 1).unwrap();
+        assert_eq!(
+            buffer.write_bits(0xFFFFFFFF, 32),
+            Err(BufferError::Overflow)
+        );
+        buffer.write_bits(0x8000_0001, 31).unwrap();
+        assert_eq!(buffer.write_bits(0b1, 1), Err(BufferError::Overflow));
+
+        let bytes: Vec<u8> = std::iter::from_fn(|| buffer.read_byte()).collect();
+        assert_eq!(bytes, vec![0x80, 0x00, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn it_errs_when_a_32_bit_value_does_not_fit_alongside_pending_bits() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        buffer.write_bits(0xFFFFFFFF, 32).unwrap();
+        buffer.write_bits(0xFF, 8).unwrap();
+        assert_eq!(
+            buffer.write_bits(0xFFFFFFFF, 32),
+            Err(BufferError::Overflow)
+        );
+
+        // the failed write leaves the pending bits untouched
+        assert_eq!(buffer.available_bytes(), 5);
+    }
+
+    #[test]
+    fn it_mirrors_each_byte_when_writing_lsb_first() {
+        let writes = [
+            (0b1, 3),
+            (0xBBB, 12),
+            (0b0, 5),
+            (0x7F, 7),
+            (0xFFFFFFFF, 32),
+            (0b10, 5),
+        ];
+
+        let mut msb_buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        let mut lsb_buffer = CompressorBuffer::new(BitOrder::LsbFirst);
+
+        for (value, bit_count) in writes {
+            msb_buffer.write_bits(value, bit_count).unwrap();
+            lsb_buffer.write_bits(value, bit_count).unwrap();
+
+            while let Some(msb_byte) = msb_buffer.read_byte() {
+                assert_eq!(lsb_buffer.read_byte(), Some(msb_byte.reverse_bits()));
+            }
+            assert_eq!(lsb_buffer.read_byte(), None);
+        }
+
+        assert_eq!(
+            msb_buffer.byte_boundary_offset(),
+            lsb_buffer.byte_boundary_offset()
+        );
+    }
+
+    #[test]
+    fn it_packs_codes_from_the_least_significant_bit_up() {
+        let mut buffer = CompressorBuffer::new(BitOrder::LsbFirst);
+        buffer.write_bits(0b110, 3).unwrap();
+        buffer.write_bits(0b01, 2).unwrap();
+        buffer.write_bits(0b100, 3).unwrap();
+        assert_eq!(buffer.read_byte(), Some(0b001_10_011));
+    }
+
+    #[test]
+    fn it_defaults_to_msb_first() {
+        assert_eq!(BitOrder::default(), BitOrder::MsbFirst);
+    }
+
+    #[test]
+    fn it_discards_pending_bits_when_reset() {
+        let mut buffer = CompressorBuffer::new(BitOrder::MsbFirst);
+        buffer.write_bits(0b101, 3).unwrap();
+        buffer.reset();
+        buffer.write_bits(0b00001111, 8).unwrap();
+        assert_eq!(buffer.read_byte(), Some(0b00001111));
+        assert_eq!(buffer.byte_boundary_offset(), 0);
     }
 }
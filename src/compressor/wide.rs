@@ -0,0 +1,158 @@
+use crate::compressor::buffer::{BitOrder, CompressorBuffer};
+use crate::compressor::CompressError;
+use crate::terminal_code::TerminalCode;
+use crate::wide_table::WideHuffmanTable;
+
+// compresses u16 symbols with a WideHuffmanTable, producing output in the same format as
+// Compressor
+pub struct WideCompressor<'a> {
+    table: &'a WideHuffmanTable,
+    buffer: CompressorBuffer,
+}
+
+impl<'a> WideCompressor<'a> {
+    pub fn new(table: &'a WideHuffmanTable) -> Self {
+        WideCompressor {
+            table,
+            buffer: CompressorBuffer::new(BitOrder::default()),
+        }
+    }
+
+    pub fn compress_symbol(&mut self, symbol: u16) -> Result<(), CompressError> {
+        let bit_count = self.table.get_compressed_value_bit_count(symbol);
+
+        if bit_count == 0 {
+            return Err(CompressError::NoCodeForSymbol(symbol));
+        }
+
+        let value = self.table.get_compressed_value(symbol);
+        self.buffer.write_bits(value, bit_count)?;
+
+        Ok(())
+    }
+
+    pub fn append_terminal_code(
+        &mut self,
+        terminal_code: &TerminalCode,
+    ) -> Result<(), CompressError> {
+        self.buffer
+            .write_bits(terminal_code.value, terminal_code.bit_count)?;
+
+        Ok(())
+    }
+
+    pub fn end(&mut self) {
+        let byte_boundary_offset = self.buffer.byte_boundary_offset();
+
+        if byte_boundary_offset != 0 {
+            self.buffer
+                .write_bits(0b0, 8 - byte_boundary_offset)
+                .expect("padding overflowed the buffer");
+        }
+    }
+
+    pub fn drain_into(&mut self, out: &mut Vec<u8>) -> usize {
+        let available_bytes = self.buffer.available_bytes() as usize;
+        out.reserve(available_bytes);
+
+        while let Some(compressed_byte) = self.buffer.read_byte() {
+            out.push(compressed_byte);
+        }
+
+        available_bytes
+    }
+}
+
+impl Iterator for WideCompressor<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        self.buffer.read_byte()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn compress(table: &WideHuffmanTable, src: &[u16]) -> Vec<u8> {
+        let mut compressor = WideCompressor::new(table);
+        let mut output = Vec::new();
+
+        for &symbol in src {
+            compressor.compress_symbol(symbol).unwrap();
+            compressor.drain_into(&mut output);
+        }
+
+        compressor.end();
+        compressor.drain_into(&mut output);
+
+        output
+    }
+
+    #[test]
+    fn it_compresses_symbols_wider_than_a_byte() {
+        let mut counts = vec![0; 1000];
+        counts[3] = 1;
+        counts[700] = 2;
+        counts[999] = 1;
+
+        // 700 gets 0, then 3 and 999 get 10 and 11, so the output is 0 11 10 0 then padding
+        let table = WideHuffmanTable::from_frequencies(&counts);
+
+        assert_eq!(compress(&table, &[700, 999, 3, 700]), vec![0b0111_0000]);
+    }
+
+    #[test]
+    fn it_compresses_a_token_stream_that_decodes_back_to_the_original() {
+        let src: Vec<u16> = (0..3000u32).map(|i| ((i * i) % 40_000) as u16).collect();
+
+        let mut counts = vec![0; 40_000];
+        for &symbol in &src {
+            counts[symbol as usize] += 1;
+        }
+        let table = WideHuffmanTable::from_frequencies(&counts);
+
+        let compressed = compress(&table, &src);
+
+        let mut codes = HashMap::new();
+        for symbol in 0..table.symbol_count() {
+            let bit_count = table.bit_counts[symbol];
+            if bit_count > 0 {
+                codes.insert((table.values[symbol], bit_count), symbol as u16);
+            }
+        }
+
+        let mut decoded = Vec::new();
+        let (mut value, mut bit_count) = (0, 0);
+        for bit_offset in 0..compressed.len() * 8 {
+            let bit = (compressed[bit_offset / 8] >> (7 - bit_offset % 8)) & 1;
+            value = value << 1 | bit as u32;
+            bit_count += 1;
+
+            if let Some(&symbol) = codes.get(&(value, bit_count)) {
+                decoded.push(symbol);
+                (value, bit_count) = (0, 0);
+
+                if decoded.len() == src.len() {
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(decoded, src);
+        assert!(compressed.len() < src.len() * 2);
+    }
+
+    #[test]
+    fn it_errors_when_compressing_a_symbol_without_a_code() {
+        let table = WideHuffmanTable::from_frequencies(&[1, 1]);
+        let mut compressor = WideCompressor::new(&table);
+
+        assert_eq!(
+            compressor.compress_symbol(300),
+            Err(CompressError::NoCodeForSymbol(300))
+        );
+    }
+}
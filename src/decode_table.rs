@@ -0,0 +1,219 @@
+use crate::compressor::BitAccumulator;
+use crate::huffman_table::HuffmanTable;
+
+// the most bits a window can look ahead, so a window starting anywhere in a byte fits in a u32
+const MAX_WINDOW_BITS: u8 = 24;
+
+// maps every possible window of upcoming bits straight to the byte whose code starts the window,
+// so codes no longer than the window decode with a single lookup. codes shorter than the window
+// fill every entry that starts with them
+pub struct DecodeTable {
+    window_bits: u8,
+    entries: Vec<Option<(u8, u8)>>,
+}
+
+impl DecodeTable {
+    // window_bits must be between 1 and 24
+    pub fn from_huffman(table: &HuffmanTable, window_bits: u8) -> DecodeTable {
+        assert!(
+            (1..=MAX_WINDOW_BITS).contains(&window_bits),
+            "window_bits must be between 1 and {}",
+            MAX_WINDOW_BITS
+        );
+
+        let mut entries = vec![None; 1 << window_bits];
+
+        for byte in 0..=255 {
+            let bit_count = table.get_compressed_value_bit_count(byte);
+
+            if bit_count == 0 || bit_count > window_bits {
+                continue;
+            }
+
+            // bits of the value above bit_count aren't part of the code, as when compressing
+            let value = table.get_compressed_value(byte).low_bits(bit_count);
+            let free_bits = window_bits - bit_count;
+            let first = (value as usize) << free_bits;
+
+            for entry in &mut entries[first..first + (1 << free_bits)] {
+                *entry = Some((byte, bit_count));
+            }
+        }
+
+        DecodeTable {
+            window_bits,
+            entries,
+        }
+    }
+
+    // the heap bytes taken up by the entries, which double with every extra bit of window
+    pub fn memory_bytes(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<Option<(u8, u8)>>()
+    }
+
+    pub fn window_bits(&self) -> u8 {
+        self.window_bits
+    }
+
+    // the byte and bit count of the code at the start of the window_bits wide window, or None if
+    // the window starts with a longer code or with bits that aren't a code at all
+    pub fn lookup(&self, window: u32) -> Option<(u8, u8)> {
+        self.entries[window as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::Compressor;
+    use crate::decode_tree::DecodeTree;
+    use crate::decompressor::Decompressor;
+    use crate::terminal_code::TerminalCode;
+
+    fn example_table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x02] = 0b1100;
+        bit_counts[0x02] = 4;
+
+        values[0x03] = 0b1101_0110_0101;
+        bit_counts[0x03] = 12;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_replicates_short_codes_across_every_window_they_start() {
+        let decode_table = DecodeTable::from_huffman(&example_table(), 4);
+
+        for window in 0b0000..=0b0111 {
+            assert_eq!(decode_table.lookup(window), Some((0x00, 1)));
+        }
+        for window in 0b1000..=0b1011 {
+            assert_eq!(decode_table.lookup(window), Some((0x01, 2)));
+        }
+        assert_eq!(decode_table.lookup(0b1100), Some((0x02, 4)));
+    }
+
+    #[test]
+    fn it_ignores_value_bits_above_the_bit_count() {
+        let mut table = example_table();
+        table.values[0x00] |= 0xFF00;
+        table.values[0x01] |= 1 << 31;
+
+        let decode_table = DecodeTable::from_huffman(&table, 4);
+
+        assert_eq!(decode_table.lookup(0b0000), Some((0x00, 1)));
+        assert_eq!(decode_table.lookup(0b1000), Some((0x01, 2)));
+    }
+
+    #[test]
+    fn it_has_no_entry_for_windows_starting_with_a_longer_code() {
+        let decode_table = DecodeTable::from_huffman(&example_table(), 4);
+
+        assert_eq!(decode_table.lookup(0b1101), None);
+        assert_eq!(decode_table.lookup(0b1111), None);
+    }
+
+    #[test]
+    fn it_reports_memory_bytes_that_double_with_each_window_bit() {
+        let small = DecodeTable::from_huffman(&example_table(), 8);
+        let large = DecodeTable::from_huffman(&example_table(), 12);
+
+        assert_eq!(
+            small.memory_bytes(),
+            256 * std::mem::size_of::<Option<(u8, u8)>>()
+        );
+        assert_eq!(large.memory_bytes(), 16 * small.memory_bytes());
+    }
+
+    #[test]
+    #[should_panic(expected = "window_bits must be between 1 and 24")]
+    fn it_panics_on_a_window_wider_than_24_bits() {
+        DecodeTable::from_huffman(&example_table(), 25);
+    }
+
+    #[test]
+    fn it_decodes_identically_to_walking_the_tree() {
+        let table = HuffmanTable::from_bytes(
+            b"it was the best of times, it was the worst of times, it was the age of wisdom, \
+              it was the age of foolishness, it was the epoch of belief, it was the epoch of \
+              incredulity, it was the season of light, it was the season of darkness",
+        );
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        // a long pseudorandom input over the bytes the table has codes for
+        let coded_bytes: Vec<u8> = (0..=255)
+            .filter(|&byte| table.get_compressed_value_bit_count(byte) > 0)
+            .collect();
+        let mut state: u32 = 0x2545_F491;
+        let src: Vec<u8> = (0..20_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                coded_bytes[state as usize % coded_bytes.len()]
+            })
+            .collect();
+
+        let mut compressor = Compressor::new(&table);
+        let mut compressed = Vec::new();
+        for &byte in &src {
+            compressor.compress_byte(byte).unwrap();
+            compressor.drain_into(&mut compressed);
+        }
+        compressor.end();
+        compressor.drain_into(&mut compressed);
+
+        let walked: Vec<u8> = Decompressor::new(&tree, &compressed)
+            .with_expected_len(src.len())
+            .collect();
+        assert_eq!(walked, src);
+
+        for window_bits in [1, 4, 8, 12] {
+            let decode_table = DecodeTable::from_huffman(&table, window_bits);
+
+            let looked_up: Vec<u8> = Decompressor::new(&tree, &compressed)
+                .with_decode_table(&decode_table)
+                .with_expected_len(src.len())
+                .collect();
+
+            assert_eq!(looked_up, walked);
+        }
+    }
+
+    #[test]
+    fn it_falls_back_to_the_tree_for_the_terminal_code() {
+        let table = example_table();
+        let terminal_code = TerminalCode {
+            bit_count: 5,
+            value: 0b11100,
+        };
+        let tree = DecodeTree::new(&table, Some(&terminal_code)).unwrap();
+        let decode_table = DecodeTable::from_huffman(&table, 8);
+
+        let src = [0x03, 0x00, 0x02, 0x01, 0x00];
+
+        let mut compressor = Compressor::new(&table);
+        let mut compressed = Vec::new();
+        for &byte in &src {
+            compressor.compress_byte(byte).unwrap();
+        }
+        compressor.append_terminal_code(&terminal_code).unwrap();
+        compressor.end();
+        compressor.drain_into(&mut compressed);
+
+        let decompressed: Vec<u8> = Decompressor::new(&tree, &compressed)
+            .with_decode_table(&decode_table)
+            .collect();
+
+        assert_eq!(decompressed, src);
+    }
+}
@@ -0,0 +1,687 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::huffman_table::HuffmanTable;
+use crate::terminal_code::TerminalCode;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecodedSymbol {
+    Byte(u8),
+    Terminal,
+}
+
+enum Node {
+    Branch([Option<usize>; 2]),
+    Leaf(DecodedSymbol),
+}
+
+// a binary trie of every code in a table, walked one bit at a time from the root to decode.
+// nodes are stored in a vec and refer to their children by index
+pub struct DecodeTree {
+    nodes: Vec<Node>,
+    has_terminal_code: bool,
+}
+
+impl DecodeTree {
+    pub fn new(
+        table: &HuffmanTable,
+        terminal_code: Option<&TerminalCode>,
+    ) -> Result<DecodeTree, DecodeTreeError> {
+        let mut tree = DecodeTree {
+            nodes: vec![Node::Branch([None, None])],
+            has_terminal_code: terminal_code.is_some(),
+        };
+
+        if let Some(terminal_code) = terminal_code {
+            tree.insert(
+                terminal_code.value,
+                terminal_code.bit_count,
+                DecodedSymbol::Terminal,
+            )?;
+        }
+
+        for byte in 0..=255 {
+            let bit_count = table.get_compressed_value_bit_count(byte);
+
+            if bit_count > 0 {
+                let value = table.get_compressed_value(byte);
+                tree.insert(value, bit_count, DecodedSymbol::Byte(byte))?;
+            }
+        }
+
+        tree.nodes.shrink_to_fit();
+
+        Ok(tree)
+    }
+
+    fn insert(
+        &mut self,
+        value: u32,
+        bit_count: u8,
+        symbol: DecodedSymbol,
+    ) -> Result<(), DecodeTreeError> {
+        let conflict = match symbol {
+            DecodedSymbol::Byte(byte) => DecodeTreeError::PrefixConflict(byte),
+            DecodedSymbol::Terminal => DecodeTreeError::InvalidTerminalCode,
+        };
+
+        if bit_count == 0 || bit_count > 32 {
+            return Err(conflict);
+        }
+
+        let mut node = self.root();
+
+        for position in (0..bit_count).rev() {
+            let bit = ((value >> position) & 1) as usize;
+
+            let existing = match &self.nodes[node] {
+                Node::Branch(children) => children[bit],
+                Node::Leaf(_) => return Err(conflict),
+            };
+
+            node = match existing {
+                Some(child) => child,
+                None => {
+                    let child = self.nodes.len();
+
+                    if let Node::Branch(children) = &mut self.nodes[node] {
+                        children[bit] = Some(child);
+                    }
+
+                    self.nodes.push(if position == 0 {
+                        Node::Leaf(symbol)
+                    } else {
+                        Node::Branch([None, None])
+                    });
+
+                    child
+                }
+            };
+
+            if position == 0 && !matches!(self.nodes[node], Node::Leaf(leaf) if leaf == symbol) {
+                return Err(conflict);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    // whether the tree was built with a terminal code, so the data must end with it
+    pub fn has_terminal_code(&self) -> bool {
+        self.has_terminal_code
+    }
+
+    // the tree's shape as bits, most significant first, visiting nodes depth first with each
+    // node's 0 child before its 1 child:
+    // - a branch is a 1 followed by its two children
+    // - a leaf is a 0 then 0 and the byte's eight bits, or 0 then 10 for the terminal code
+    // - a missing child, where no code starts with its bits, is 011
+    // it's padded with zeroes to a whole byte. for a handful of codes this is much smaller than
+    // HuffmanTable::serialize, but every extra code costs ten or so more bits
+    pub fn serialize_shape(&self) -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        let mut stack = vec![Some(self.root())];
+
+        while let Some(node) = stack.pop() {
+            match node.map(|node| &self.nodes[node]) {
+                Some(Node::Branch(children)) => {
+                    bits.write(1, 1);
+                    stack.push(children[1]);
+                    stack.push(children[0]);
+                }
+                Some(Node::Leaf(DecodedSymbol::Byte(byte))) => {
+                    bits.write(0b00, 2);
+                    bits.write(*byte as u32, 8);
+                }
+                Some(Node::Leaf(DecodedSymbol::Terminal)) => bits.write(0b010, 3),
+                None => bits.write(0b011, 3),
+            }
+        }
+
+        bits.into_bytes()
+    }
+
+    // reads a shape written by serialize_shape from the start of bytes, returning the tree and
+    // how many bytes it took up
+    pub fn deserialize_shape(bytes: &[u8]) -> Result<(DecodeTree, usize), DecodeTreeError> {
+        let mut bits = BitReader {
+            bytes,
+            bit_offset: 0,
+        };
+
+        if bits.read(1)? != 1 {
+            return Err(DecodeTreeError::InvalidShape);
+        }
+
+        let mut tree = DecodeTree {
+            nodes: vec![Node::Branch([None, None])],
+            has_terminal_code: false,
+        };
+        let mut has_byte = [false; 256];
+
+        // the parent and which of its children comes next, along with that child's depth
+        let mut stack = vec![(tree.root(), 1, 1), (tree.root(), 0, 1)];
+
+        while let Some((parent, bit, depth)) = stack.pop() {
+            if depth > 32 {
+                return Err(DecodeTreeError::InvalidShape);
+            }
+
+            let node = match bits.read(1)? {
+                1 => Node::Branch([None, None]),
+                _ => match bits.read(1)? {
+                    0 => {
+                        let byte = bits.read(8)? as u8;
+
+                        if std::mem::replace(&mut has_byte[byte as usize], true) {
+                            return Err(DecodeTreeError::InvalidShape);
+                        }
+
+                        Node::Leaf(DecodedSymbol::Byte(byte))
+                    }
+                    _ => match bits.read(1)? {
+                        0 if !tree.has_terminal_code => {
+                            tree.has_terminal_code = true;
+                            Node::Leaf(DecodedSymbol::Terminal)
+                        }
+                        0 => return Err(DecodeTreeError::InvalidShape),
+                        _ => continue,
+                    },
+                },
+            };
+
+            let child = tree.nodes.len();
+
+            if let Node::Branch(_) = node {
+                stack.push((child, 1, depth + 1));
+                stack.push((child, 0, depth + 1));
+            }
+
+            tree.nodes.push(node);
+
+            if let Node::Branch(children) = &mut tree.nodes[parent] {
+                children[bit] = Some(child);
+            }
+        }
+
+        tree.nodes.shrink_to_fit();
+
+        Ok((tree, bits.bit_offset.div_ceil(8)))
+    }
+
+    // a tree for two contexts, where the first bit of each code picks which tree decodes the
+    // rest: this tree for codes starting with discriminator_bit and other for the rest. the
+    // terminal code, if either tree has one, only ends the data within its own context
+    pub fn merge_with(&self, other: &DecodeTree, discriminator_bit: bool) -> DecodeTree {
+        let mut nodes = Vec::with_capacity(1 + self.nodes.len() + other.nodes.len());
+        nodes.push(Node::Branch([None, None]));
+
+        let mut children = [None, None];
+        children[discriminator_bit as usize] = Some(nodes.len() + self.root());
+        self.copy_nodes_into(&mut nodes);
+        children[!discriminator_bit as usize] = Some(nodes.len() + other.root());
+        other.copy_nodes_into(&mut nodes);
+
+        nodes[0] = Node::Branch(children);
+
+        DecodeTree {
+            nodes,
+            has_terminal_code: self.has_terminal_code || other.has_terminal_code,
+        }
+    }
+
+    fn copy_nodes_into(&self, nodes: &mut Vec<Node>) {
+        let offset = nodes.len();
+
+        nodes.extend(self.nodes.iter().map(|node| match node {
+            Node::Branch(children) => {
+                Node::Branch(children.map(|child| child.map(|child| child + offset)))
+            }
+            Node::Leaf(symbol) => Node::Leaf(*symbol),
+        }));
+    }
+
+    // the heap bytes taken up by the nodes, for weighing the tree against a DecodeTable. each
+    // code adds a node per bit that it doesn't share with an earlier code
+    pub fn memory_bytes(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<Node>()
+    }
+
+    // the length of the longest code in the tree, so a lookup decoder knows how many bits its
+    // window has to hold
+    pub fn max_depth(&self) -> u8 {
+        let mut max_depth = 0;
+        let mut stack = vec![(self.root(), 0)];
+
+        while let Some((node, depth)) = stack.pop() {
+            match &self.nodes[node] {
+                Node::Branch(children) => {
+                    for &child in children.iter().flatten() {
+                        stack.push((child, depth + 1));
+                    }
+                }
+                Node::Leaf(_) => max_depth = max_depth.max(depth),
+            }
+        }
+
+        max_depth
+    }
+
+    // decodes the code at the start of the low available bits of bits, most significant bit
+    // first, returning the symbol and how many of the bits its code used. None if the bits run
+    // out part way through a code, or if they aren't the start of any code
+    pub fn decode_one(&self, bits: u32, available: u8) -> Option<(DecodedSymbol, u8)> {
+        assert!(available <= 32, "available must be at most 32");
+
+        let mut node = self.root();
+
+        for consumed in 1..=available {
+            let bit = ((bits >> (available - consumed)) & 1) as u8;
+            node = self.child(node, bit)?;
+
+            if let Some(symbol) = self.symbol(node) {
+                return Some((symbol, consumed));
+            }
+        }
+
+        None
+    }
+
+    pub fn child(&self, node: usize, bit: u8) -> Option<usize> {
+        match &self.nodes[node] {
+            Node::Branch(children) => children[bit as usize],
+            Node::Leaf(_) => None,
+        }
+    }
+
+    pub fn symbol(&self, node: usize) -> Option<DecodedSymbol> {
+        match &self.nodes[node] {
+            Node::Branch(_) => None,
+            Node::Leaf(symbol) => Some(*symbol),
+        }
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_count: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bit_count: u8) {
+        for position in (0..bit_count).rev() {
+            if self.bit_count.is_multiple_of(8) {
+                self.bytes.push(0);
+                self.bit_count = 0;
+            }
+
+            let bit = ((value >> position) & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << (7 - self.bit_count);
+            self.bit_count += 1;
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    bit_offset: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bit_count: u8) -> Result<u32, DecodeTreeError> {
+        let mut value = 0;
+
+        for _ in 0..bit_count {
+            let byte = self
+                .bytes
+                .get(self.bit_offset / 8)
+                .ok_or(DecodeTreeError::InvalidShape)?;
+            let bit = (byte >> (7 - self.bit_offset % 8)) & 1;
+
+            value = (value << 1) | bit as u32;
+            self.bit_offset += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodeTreeError {
+    // the byte's code is the same as, a prefix of, or prefixed by another code
+    PrefixConflict(u8),
+    InvalidTerminalCode,
+    // like PrefixConflict, for a symbol of a WideHuffmanTable
+    SymbolPrefixConflict(u16),
+    // a serialized shape is truncated, deeper than 32 bits, or has a byte or the terminal code at
+    // more than one leaf
+    InvalidShape,
+}
+
+impl fmt::Display for DecodeTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeTreeError::PrefixConflict(byte) => write!(
+                f,
+                "the code for byte 0x{:02X} conflicts with another code",
+                byte
+            ),
+            DecodeTreeError::InvalidTerminalCode => write!(f, "terminal code is invalid"),
+            DecodeTreeError::SymbolPrefixConflict(symbol) => {
+                write!(
+                    f,
+                    "the code for symbol {} conflicts with another code",
+                    symbol
+                )
+            }
+            DecodeTreeError::InvalidShape => write!(f, "serialized tree shape is invalid"),
+        }
+    }
+}
+
+impl Error for DecodeTreeError {}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+    use crate::frequencies::count_frequencies;
+
+    fn walk(tree: &DecodeTree, bits: &[u8]) -> Option<DecodedSymbol> {
+        let mut node = tree.root();
+
+        for &bit in bits {
+            node = tree.child(node, bit)?;
+        }
+
+        tree.symbol(node)
+    }
+
+    #[test]
+    fn it_decodes_each_code_in_the_table() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0xFF] = 0b110;
+        bit_counts[0xFF] = 3;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let tree = DecodeTree::new(&table, Some(&terminal_code)).unwrap();
+
+        assert_eq!(walk(&tree, &[0]), Some(DecodedSymbol::Byte(0x00)));
+        assert_eq!(walk(&tree, &[1, 0]), Some(DecodedSymbol::Byte(0x01)));
+        assert_eq!(walk(&tree, &[1, 1, 0]), Some(DecodedSymbol::Byte(0xFF)));
+        assert_eq!(walk(&tree, &[1, 1, 1]), Some(DecodedSymbol::Terminal));
+        assert_eq!(walk(&tree, &[1, 1]), None);
+    }
+
+    #[test]
+    fn it_returns_the_longest_code_length_as_the_max_depth() {
+        let table = HuffmanTable::from_bytes(b"abracadabra, a magical incantation");
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        assert_eq!(tree.max_depth(), *table.bit_counts.iter().max().unwrap());
+
+        let terminal_code = TerminalCode {
+            bit_count: 20,
+            value: (1 << 20) - 1,
+        };
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+        let table = HuffmanTable { values, bit_counts };
+
+        let tree = DecodeTree::new(&table, Some(&terminal_code)).unwrap();
+
+        assert_eq!(tree.max_depth(), 20);
+    }
+
+    #[test]
+    fn it_reports_memory_bytes_that_scale_with_the_number_of_nodes() {
+        // a complete code over n bytes has n leaves and n - 1 branches
+        for byte_count in [2, 16, 256] {
+            let counts: Vec<u64> = (0..256).map(|byte| (byte < byte_count) as u64).collect();
+            let table = HuffmanTable::from_frequencies(&counts.try_into().unwrap());
+            let tree = DecodeTree::new(&table, None).unwrap();
+
+            assert_eq!(
+                tree.memory_bytes(),
+                (2 * byte_count - 1) as usize * std::mem::size_of::<Node>()
+            );
+        }
+    }
+
+    fn every_code(tree: &DecodeTree) -> Vec<(u32, u8, DecodedSymbol)> {
+        let mut codes = Vec::new();
+
+        for bit_count in 1..=16 {
+            for value in 0..1 << bit_count {
+                if let Some((symbol, consumed)) = tree.decode_one(value, bit_count) {
+                    if consumed == bit_count {
+                        codes.push((value, bit_count, symbol));
+                    }
+                }
+            }
+        }
+
+        codes
+    }
+
+    #[test]
+    fn it_round_trips_the_shape_of_a_tree() {
+        let table = HuffmanTable::from_bytes(b"abracadabra, a magical incantation");
+        let terminal_code = TerminalCode {
+            bit_count: 1,
+            value: 0b1,
+        };
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+        values[0x92] = 0b0;
+        bit_counts[0x92] = 2;
+
+        let trees = [
+            DecodeTree::new(&table, None).unwrap(),
+            DecodeTree::new(&HuffmanTable { values, bit_counts }, Some(&terminal_code)).unwrap(),
+            DecodeTree::new(&HuffmanTable::from_bytes(b""), None).unwrap(),
+        ];
+
+        for tree in trees {
+            let mut shape = tree.serialize_shape();
+            let shape_len = shape.len();
+            shape.extend([0xFF, 0xFF]);
+
+            let (deserialized, len) = DecodeTree::deserialize_shape(&shape).unwrap();
+
+            assert_eq!(len, shape_len);
+            assert_eq!(every_code(&deserialized), every_code(&tree));
+            assert_eq!(deserialized.has_terminal_code(), tree.has_terminal_code());
+            assert_eq!(deserialized.serialize_shape(), tree.serialize_shape());
+        }
+    }
+
+    #[test]
+    fn it_decodes_each_sub_tree_behind_the_discriminator_bit() {
+        let letters = DecodeTree::new(&HuffmanTable::from_bytes(b"abracadabra"), None).unwrap();
+        let (table, terminal_code) =
+            HuffmanTable::from_frequencies_with_eof(&count_frequencies(b"3.14159265358979"));
+        let digits = DecodeTree::new(&table, Some(&terminal_code)).unwrap();
+
+        let merged = letters.merge_with(&digits, true);
+
+        for (value, bit_count, symbol) in every_code(&letters) {
+            assert_eq!(
+                merged.decode_one(1 << bit_count | value, bit_count + 1),
+                Some((symbol, bit_count + 1))
+            );
+        }
+
+        for (value, bit_count, symbol) in every_code(&digits) {
+            assert_eq!(
+                merged.decode_one(value, bit_count + 1),
+                Some((symbol, bit_count + 1))
+            );
+        }
+
+        assert!(merged.has_terminal_code());
+        assert_eq!(
+            merged.max_depth(),
+            letters.max_depth().max(digits.max_depth()) + 1
+        );
+        assert_eq!(
+            merged.decode_one(terminal_code.value, terminal_code.bit_count + 1),
+            Some((DecodedSymbol::Terminal, terminal_code.bit_count + 1))
+        );
+        assert_eq!(walk(&merged, &[1, 0]), Some(DecodedSymbol::Byte(b'a')));
+    }
+
+    #[test]
+    fn it_serializes_a_small_alphabet_smaller_than_the_table() {
+        let table = HuffmanTable::from_bytes(b"abracadabra");
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        // 5 leaves of 10 bits and 4 branches of 1 bit
+        assert_eq!(tree.serialize_shape().len(), 7);
+        assert!(tree.serialize_shape().len() < table.serialize().len());
+    }
+
+    #[test]
+    fn it_errs_on_an_invalid_shape() {
+        let tree = DecodeTree::new(&HuffmanTable::from_bytes(b"abracadabra"), None).unwrap();
+        let shape = tree.serialize_shape();
+
+        // truncated, a leaf at the root, and the same byte at two leaves
+        for shape in [
+            &shape[..shape.len() - 1],
+            &[0b0000_0000],
+            &[0b1_00_00000, 0b00_00_0000, 0b0000_0000],
+        ] {
+            assert_eq!(
+                DecodeTree::deserialize_shape(shape).err(),
+                Some(DecodeTreeError::InvalidShape)
+            );
+        }
+    }
+
+    #[test]
+    fn it_decodes_one_code_from_a_window_of_bits() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0xFF] = 0b110;
+        bit_counts[0xFF] = 3;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let tree = DecodeTree::new(&table, Some(&terminal_code)).unwrap();
+
+        // 0xFF's code followed by the start of the next one
+        assert_eq!(
+            tree.decode_one(0b110_10, 5),
+            Some((DecodedSymbol::Byte(0xFF), 3))
+        );
+        assert_eq!(
+            tree.decode_one(0b0_111, 4),
+            Some((DecodedSymbol::Byte(0x00), 1))
+        );
+        assert_eq!(
+            tree.decode_one(0b111, 3),
+            Some((DecodedSymbol::Terminal, 3))
+        );
+
+        // a prefix of 0xFF's code needs more bits
+        assert_eq!(tree.decode_one(0b11, 2), None);
+        assert_eq!(tree.decode_one(0, 0), None);
+    }
+
+    #[test]
+    fn it_errors_when_a_code_is_a_prefix_of_another() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b1;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        assert_eq!(
+            DecodeTree::new(&table, None).err(),
+            Some(DecodeTreeError::PrefixConflict(0x01))
+        );
+    }
+
+    #[test]
+    fn it_errors_when_two_bytes_share_a_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x10] = 0b01;
+        bit_counts[0x10] = 2;
+
+        values[0x20] = 0b01;
+        bit_counts[0x20] = 2;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        assert_eq!(
+            DecodeTree::new(&table, None).err(),
+            Some(DecodeTreeError::PrefixConflict(0x20))
+        );
+    }
+
+    #[test]
+    fn it_errors_when_a_byte_code_collides_with_the_terminal_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x10] = 0b0110;
+        bit_counts[0x10] = 4;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let terminal_code = TerminalCode {
+            bit_count: 2,
+            value: 0b01,
+        };
+
+        assert_eq!(
+            DecodeTree::new(&table, Some(&terminal_code)).err(),
+            Some(DecodeTreeError::PrefixConflict(0x10))
+        );
+    }
+}
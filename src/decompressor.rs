@@ -0,0 +1,829 @@
+use std::error::Error;
+use std::fmt;
+use std::string::FromUtf8Error;
+
+use crate::decode_table::DecodeTable;
+use crate::decode_tree::{DecodeTree, DecodeTreeError, DecodedSymbol};
+use crate::frequencies::FrequencyCounter;
+
+type ProgressCallback<'a> = Box<dyn FnMut(usize) + 'a>;
+
+// decodes bytes produced by a Compressor using the same table (and terminal code, if any).
+// decoding stops at the terminal code, after expected_len bytes if one was given, or when the
+// input runs out. running out first is an UnexpectedEnd error, except that with neither a
+// terminal code nor an expected length a code left incomplete by fewer than eight zero bits is
+// the padding added by Compressor::end and is dropped. that padding can still decode as extra
+// bytes if some byte's code is seven or fewer zeroes, unless the exact bit length of the data
+// is given with with_bit_len
+pub struct Decompressor<'a> {
+    tree: &'a DecodeTree,
+    decode_table: Option<&'a DecodeTable>,
+    src: &'a [u8],
+    start_bit_offset: u64,
+    bit_offset: u64,
+    expected_len: Option<usize>,
+    bit_len: Option<u64>,
+    decompressed_len: usize,
+    // called with decompressed_len every interval bytes
+    progress: Option<(usize, ProgressCallback<'a>)>,
+    finished: bool,
+    error: Option<DecodeError>,
+}
+
+impl<'a> Decompressor<'a> {
+    pub fn new(tree: &'a DecodeTree, src: &'a [u8]) -> Decompressor<'a> {
+        Decompressor {
+            tree,
+            decode_table: None,
+            src,
+            start_bit_offset: 0,
+            bit_offset: 0,
+            expected_len: None,
+            bit_len: None,
+            decompressed_len: 0,
+            progress: None,
+            finished: false,
+            error: None,
+        }
+    }
+
+    pub fn with_expected_len(mut self, expected_len: usize) -> Decompressor<'a> {
+        self.expected_len = Some(expected_len);
+        self
+    }
+
+    // decodes exactly bit_len bits of src, e.g. as returned by Huffman::compress_exact, ignoring
+    // whatever the rest of the last byte holds. the data must end with a whole code exactly
+    // bit_len bits after where decoding starts
+    pub fn with_bit_len(mut self, bit_len: u64) -> Decompressor<'a> {
+        self.bit_len = Some(bit_len);
+        self
+    }
+
+    // starts decoding bit_offset bits into src rather than at its first bit, e.g. at one of the
+    // row offsets returned by Huffman::compress_grid. error offsets are still counted from the
+    // start of src, and an offset past the end of src is an UnexpectedEnd error
+    pub fn with_bit_offset(mut self, bit_offset: u64) -> Decompressor<'a> {
+        self.start_bit_offset = bit_offset;
+        self.bit_offset = bit_offset;
+        self
+    }
+
+    // decodes codes that fit the table's window with a single lookup, falling back to walking
+    // the tree for longer codes, the terminal code and the end of the input
+    pub fn with_decode_table(mut self, decode_table: &'a DecodeTable) -> Decompressor<'a> {
+        self.decode_table = Some(decode_table);
+        self
+    }
+
+    // calls callback with the number of bytes decoded so far after every interval bytes, e.g. to
+    // move a progress bar while decompressing a large file
+    pub fn with_progress<F>(mut self, interval: usize, callback: F) -> Decompressor<'a>
+    where
+        F: FnMut(usize) + 'a,
+    {
+        assert!(interval > 0, "interval must be more than 0");
+
+        self.progress = Some((interval, Box::new(callback)));
+        self
+    }
+
+    // set once decoding has stopped on a bit sequence that isn't a code in the tree
+    pub fn error(&self) -> Option<&DecodeError> {
+        self.error.as_ref()
+    }
+
+    // decodes the rest of src, passing each byte through f as it's decoded rather than in a
+    // second pass over the output
+    pub fn decode_map<F: FnMut(u8) -> u8>(&mut self, f: F) -> Vec<u8> {
+        self.by_ref().map(f).collect()
+    }
+
+    // decodes the rest of src as UTF-8 text. the bytes are decoded into the buffer the String
+    // takes over, so validating them doesn't copy
+    pub fn decompress_to_string(&mut self) -> Result<String, DecodeError> {
+        let bytes: Vec<u8> = self.by_ref().collect();
+
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+
+        String::from_utf8(bytes).map_err(DecodeError::InvalidUtf8)
+    }
+
+    // counts the decoded bytes as they're decoded, see FrequencyCounter::into_frequencies
+    pub fn counting_frequencies(self) -> FrequencyCounter<Decompressor<'a>> {
+        FrequencyCounter::new(self)
+    }
+
+    // how many bytes of src have been read, counting the byte the last code ends in. once
+    // decoding stops at the terminal code or the expected length, anything in src after these
+    // bytes is left alone, e.g. the padding of an aligned container
+    pub fn consumed_len(&self) -> usize {
+        self.bit_offset.div_ceil(8) as usize
+    }
+
+    // where the data ends, which is the end of src unless with_bit_len says otherwise
+    fn end_bit_offset(&self) -> u64 {
+        match self.bit_len {
+            Some(bit_len) => self.start_bit_offset + bit_len,
+            None => self.src.len() as u64 * 8,
+        }
+    }
+
+    fn read_bit(&mut self) -> Option<u8> {
+        if self.bit_offset >= self.end_bit_offset() {
+            return None;
+        }
+
+        let byte = *self.src.get((self.bit_offset / 8) as usize)?;
+        let bit = (byte >> (7 - self.bit_offset % 8)) & 1;
+
+        self.bit_offset += 1;
+
+        Some(bit)
+    }
+
+    fn look_up(&self, decode_table: &DecodeTable) -> Option<(u8, u8)> {
+        let window_bits = decode_table.window_bits() as u64;
+        let end_bit_offset = self.end_bit_offset();
+
+        // the four bytes the window falls within, with zeroes past the end of the input
+        let first_byte = (self.bit_offset / 8) as usize;
+        let mut bytes = [0; 4];
+        for (byte, &src_byte) in bytes.iter_mut().zip(self.src[first_byte..].iter()) {
+            *byte = src_byte;
+        }
+
+        let window = u32::from_be_bytes(bytes) << (self.bit_offset % 8) >> (32 - window_bits);
+        let (byte, bit_count) = decode_table.lookup(window)?;
+
+        // a code running into the bits past the end is really padding
+        if self.bit_offset + bit_count as u64 > end_bit_offset {
+            return None;
+        }
+
+        Some((byte, bit_count))
+    }
+
+    fn decoded(&mut self, byte: u8) -> Option<u8> {
+        self.decompressed_len += 1;
+
+        if let Some((interval, callback)) = &mut self.progress {
+            if self.decompressed_len.is_multiple_of(*interval) {
+                callback(self.decompressed_len);
+            }
+        }
+
+        Some(byte)
+    }
+
+    // Compressor::end pads the last byte with fewer than eight zero bits. with a terminal code or
+    // an expected length the data must end with those instead, so there's no padding to allow for.
+    // with a bit length the padding is already cut off, so the data has to end right on it
+    fn is_padding(&self, bit_offset: u64) -> bool {
+        if self.bit_len.is_some() {
+            return bit_offset == self.end_bit_offset() && self.expected_len.is_none();
+        }
+
+        if self.tree.has_terminal_code() || self.expected_len.is_some() {
+            return false;
+        }
+
+        let total_bits = self.src.len() as u64 * 8;
+        let padding_bits = total_bits - bit_offset;
+
+        padding_bits == 0
+            || padding_bits < 8 && self.src[self.src.len() - 1] & (0xFF >> (8 - padding_bits)) == 0
+    }
+
+    // stops decoding, recording an error unless the bits from code_offset on are padding
+    fn end(&mut self, code_offset: u64, error: DecodeError) {
+        self.finished = true;
+
+        if !self.is_padding(code_offset) {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl Iterator for Decompressor<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.finished || self.expected_len == Some(self.decompressed_len) {
+            return None;
+        }
+
+        if self.bit_offset > self.src.len() as u64 * 8 {
+            self.finished = true;
+            self.error = Some(DecodeError::UnexpectedEnd {
+                bit_offset: self.bit_offset,
+            });
+            return None;
+        }
+
+        if let Some(decode_table) = self.decode_table {
+            if let Some((byte, bit_count)) = self.look_up(decode_table) {
+                self.bit_offset += bit_count as u64;
+                return self.decoded(byte);
+            }
+        }
+
+        let code_offset = self.bit_offset;
+        let mut node = self.tree.root();
+
+        loop {
+            let Some(bit) = self.read_bit() else {
+                let error = DecodeError::UnexpectedEnd {
+                    bit_offset: code_offset,
+                };
+                self.end(code_offset, error);
+                return None;
+            };
+
+            node = match self.tree.child(node, bit) {
+                Some(child) => child,
+                None => {
+                    let error = DecodeError::InvalidCode {
+                        bit_offset: code_offset,
+                    };
+                    self.end(code_offset, error);
+                    return None;
+                }
+            };
+
+            match self.tree.symbol(node) {
+                Some(DecodedSymbol::Byte(byte)) => return self.decoded(byte),
+                Some(DecodedSymbol::Terminal) => {
+                    self.finished = true;
+                    return None;
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodeError {
+    InvalidCode { bit_offset: u64 },
+    // the input ran out part way through the code at bit_offset, or before the terminal code
+    // or expected length was reached
+    UnexpectedEnd { bit_offset: u64 },
+    Table(DecodeTreeError),
+    // the decoded bytes aren't valid UTF-8, see Decompressor::decompress_to_string
+    InvalidUtf8(FromUtf8Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidCode { bit_offset } => {
+                write!(f, "no code matches the bits at offset {}", bit_offset)
+            }
+            DecodeError::UnexpectedEnd { bit_offset } => {
+                write!(f, "input ended unexpectedly at offset {}", bit_offset)
+            }
+            DecodeError::Table(err) => write!(f, "{}", err),
+            DecodeError::InvalidUtf8(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<DecodeTreeError> for DecodeError {
+    fn from(err: DecodeTreeError) -> DecodeError {
+        DecodeError::Table(err)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+    use crate::compressor::Compressor;
+    use crate::frequencies::count_frequencies;
+    use crate::huffman_table::HuffmanTable;
+    use crate::terminal_code::TerminalCode;
+
+    fn compress(table: &HuffmanTable, terminal_code: Option<&TerminalCode>, src: &[u8]) -> Vec<u8> {
+        let mut compressor = Compressor::new(table);
+        let mut output = Vec::new();
+
+        for &byte in src {
+            compressor.compress_byte(byte).unwrap();
+            compressor.drain_into(&mut output);
+        }
+
+        if let Some(terminal_code) = terminal_code {
+            compressor.append_terminal_code(terminal_code).unwrap();
+        }
+
+        compressor.end();
+        compressor.drain_into(&mut output);
+
+        output
+    }
+
+    fn round_trip(table: &HuffmanTable, terminal_code: Option<&TerminalCode>, src: &[u8]) {
+        let tree = DecodeTree::new(table, terminal_code).unwrap();
+        let compressed = compress(table, terminal_code, src);
+
+        let mut decompressor = Decompressor::new(&tree, &compressed);
+        let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressed, src);
+        assert_eq!(decompressor.error(), None);
+    }
+
+    #[test]
+    fn it_round_trips_a_single_byte() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xE4] = 0b1;
+        bit_counts[0xE4] = 1;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, None, &[0xE4]);
+    }
+
+    #[test]
+    fn it_round_trips_multiple_bytes() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xFF] = 0b1;
+        bit_counts[0xFF] = 1;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, None, &[0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn it_round_trips_across_byte_boundaries() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xDB] = 0b11111;
+        bit_counts[0xDB] = 5;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, None, &[0xDB, 0xDB]);
+    }
+
+    #[test]
+    fn it_round_trips_different_bytes() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b10;
+        bit_counts[0xCB] = 2;
+
+        values[0xB3] = 0b11;
+        bit_counts[0xB3] = 2;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, None, &[0xA0, 0xCB, 0xB3]);
+    }
+
+    #[test]
+    fn it_round_trips_values_with_leading_zeroes() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b1;
+        bit_counts[0x92] = 8;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, None, &[0x92]);
+    }
+
+    #[test]
+    fn it_round_trips_codes_longer_than_a_byte() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0x8000_0001;
+        bit_counts[0x01] = 32;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, None, &[0x01, 0x01]);
+    }
+
+    #[test]
+    fn it_stops_at_the_terminal_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b1010;
+        bit_counts[0x92] = 4;
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, Some(&terminal_code), &[0x92]);
+    }
+
+    #[test]
+    fn it_stops_at_a_terminal_code_in_its_own_byte() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b10000000;
+        bit_counts[0x92] = 8;
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b101,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, Some(&terminal_code), &[0x92]);
+    }
+
+    #[test]
+    fn it_ignores_zero_codes_in_the_padding_after_the_terminal_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b0;
+        bit_counts[0x92] = 2;
+
+        values[0x0C] = 0b10;
+        bit_counts[0x0C] = 2;
+
+        let terminal_code = TerminalCode {
+            bit_count: 2,
+            value: 0b11,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, Some(&terminal_code), &[0x0C, 0x92]);
+    }
+
+    #[test]
+    fn it_stops_after_the_expected_len_when_the_padding_decodes_as_a_byte() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b0;
+        bit_counts[0x92] = 2;
+
+        values[0x0C] = 0b111;
+        bit_counts[0x0C] = 3;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let tree = DecodeTree::new(&table, None).unwrap();
+        let compressed = compress(&table, None, &[0x92, 0x0C]);
+
+        // the three bits of padding start with a full code for 0x92
+        let unbounded: Vec<u8> = Decompressor::new(&tree, &compressed).collect();
+        assert_eq!(unbounded, vec![0x92, 0x0C, 0x92]);
+
+        let decompressed: Vec<u8> = Decompressor::new(&tree, &compressed)
+            .with_expected_len(2)
+            .collect();
+        assert_eq!(decompressed, vec![0x92, 0x0C]);
+    }
+
+    #[test]
+    fn it_stops_at_the_bit_len_when_the_padding_decodes_as_a_byte() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b0;
+        bit_counts[0x92] = 2;
+
+        values[0x0C] = 0b111;
+        bit_counts[0x0C] = 3;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let tree = DecodeTree::new(&table, None).unwrap();
+        let compressed = compress(&table, None, &[0x92, 0x0C]);
+
+        let mut decompressor = Decompressor::new(&tree, &compressed).with_bit_len(5);
+        let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressed, vec![0x92, 0x0C]);
+        assert_eq!(decompressor.error(), None);
+    }
+
+    #[test]
+    fn it_errs_when_the_bit_len_ends_part_way_through_a_code() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        let compressed = compress(&table, None, &[0xA0, 0xCB]);
+
+        for bit_len in [1, 3, 4, 6] {
+            let mut decompressor = Decompressor::new(&tree, &compressed).with_bit_len(bit_len);
+            decompressor.by_ref().for_each(drop);
+
+            assert!(matches!(
+                decompressor.error(),
+                Some(DecodeError::UnexpectedEnd { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn it_decodes_through_a_map_applied_to_each_byte() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        let src = [0xA0, 0xB3, 0xCB, 0xA0];
+        let compressed = compress(&table, None, &src);
+
+        let identity = Decompressor::new(&tree, &compressed).decode_map(|byte| byte);
+        let plain: Vec<u8> = Decompressor::new(&tree, &compressed).collect();
+        assert_eq!(identity, plain);
+
+        let shifted = Decompressor::new(&tree, &compressed).decode_map(|byte| byte + 1);
+        assert_eq!(shifted, vec![0xA1, 0xB4, 0xCC, 0xA1]);
+    }
+
+    #[test]
+    fn it_decompresses_utf8_text_to_a_string() {
+        let text = "naïve café, 東京 ✓";
+        let table = HuffmanTable::from_bytes(text.as_bytes());
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        let compressed = compress(&table, None, text.as_bytes());
+
+        let decompressed = Decompressor::new(&tree, &compressed)
+            .with_expected_len(text.len())
+            .decompress_to_string();
+
+        assert_eq!(decompressed, Ok(text.to_string()));
+    }
+
+    #[test]
+    fn it_errs_when_decompressing_invalid_utf8_to_a_string() {
+        let src = [b'o', b'k', 0xC3, b'(', 0xFF];
+        let table = HuffmanTable::from_bytes(&src);
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        let compressed = compress(&table, None, &src);
+
+        let result = Decompressor::new(&tree, &compressed)
+            .with_expected_len(src.len())
+            .decompress_to_string();
+
+        let Err(DecodeError::InvalidUtf8(err)) = result else {
+            panic!("expected InvalidUtf8, got {:?}", result);
+        };
+        assert_eq!(err.utf8_error().valid_up_to(), 2);
+        assert_eq!(err.into_bytes(), src);
+    }
+
+    #[test]
+    fn it_counts_the_frequencies_of_the_decoded_bytes() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        let src = [0xA0, 0xCB, 0xB3, 0xA0, 0xA0, 0xCB, 0xB3, 0xA0];
+        let compressed = compress(&table, None, &src);
+
+        let mut decompressed = Vec::new();
+        let mut counter = Decompressor::new(&tree, &compressed).counting_frequencies();
+        decompressed.extend(&mut counter);
+
+        assert_eq!(decompressed, src);
+        assert_eq!(counter.inner().error(), None);
+        assert_eq!(counter.into_frequencies(), count_frequencies(&decompressed));
+    }
+
+    #[test]
+    fn it_reports_progress_every_interval_bytes() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        let src = [0xA0, 0xCB, 0xB3, 0xA0, 0xA0, 0xCB, 0xCB, 0xA0, 0xB3, 0xA0];
+        let compressed = compress(&table, None, &src);
+
+        let mut progress = Vec::new();
+        let decompressed: Vec<u8> = Decompressor::new(&tree, &compressed)
+            .with_progress(3, |decompressed_len| progress.push(decompressed_len))
+            .collect();
+
+        assert_eq!(decompressed, src);
+        assert_eq!(progress, vec![3, 6, 9]);
+    }
+
+    #[test]
+    fn it_ignores_trailing_bytes_and_reports_the_bytes_consumed() {
+        let table = truncation_table();
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+        let src = [0xA0, 0xB3, 0xCB, 0xA0];
+
+        for terminal_code in [Some(&terminal_code), None] {
+            let tree = DecodeTree::new(&table, terminal_code).unwrap();
+
+            let compressed = compress(&table, terminal_code, &src);
+            let mut with_trailing_bytes = compressed.clone();
+            with_trailing_bytes.extend([0xFF, 0x00, 0xC3, 0x5A]);
+
+            let mut decompressor = Decompressor::new(&tree, &with_trailing_bytes);
+            if terminal_code.is_none() {
+                decompressor = decompressor.with_expected_len(src.len());
+            }
+            let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+            assert_eq!(decompressed, src);
+            assert_eq!(decompressor.error(), None);
+            assert_eq!(decompressor.consumed_len(), compressed.len());
+        }
+    }
+
+    #[test]
+    fn it_round_trips_an_empty_input() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b1010;
+        bit_counts[0x92] = 4;
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        round_trip(&table, Some(&terminal_code), &[]);
+        round_trip(&table, None, &[]);
+    }
+
+    #[test]
+    fn it_reports_bits_that_do_not_match_any_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b10;
+        bit_counts[0x92] = 2;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let tree = DecodeTree::new(&table, None).unwrap();
+        let compressed = [0b10_11_0000];
+
+        let mut decompressor = Decompressor::new(&tree, &compressed);
+        let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressed, vec![0x92]);
+        assert_eq!(
+            decompressor.error(),
+            Some(&DecodeError::InvalidCode { bit_offset: 2 })
+        );
+    }
+
+    fn truncation_table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b101;
+        bit_counts[0xCB] = 3;
+
+        values[0xB3] = 0b1101_0110_0101;
+        bit_counts[0xB3] = 12;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_errs_when_the_input_ends_part_way_through_a_code() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        // 01 then the first six bits of 0xB3's code
+        let compressed = [0b01_110101];
+
+        let mut decompressor = Decompressor::new(&tree, &compressed);
+        let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressed, vec![0xA0]);
+        assert_eq!(
+            decompressor.error(),
+            Some(&DecodeError::UnexpectedEnd { bit_offset: 2 })
+        );
+    }
+
+    #[test]
+    fn it_errs_when_the_input_ends_before_the_terminal_code() {
+        let table = truncation_table();
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+        let tree = DecodeTree::new(&table, Some(&terminal_code)).unwrap();
+
+        let compressed = compress(&table, Some(&terminal_code), &[0xA0, 0xB3, 0xCB, 0xA0]);
+
+        for len in 0..compressed.len() {
+            let mut decompressor = Decompressor::new(&tree, &compressed[..len]);
+            decompressor.by_ref().for_each(drop);
+
+            assert!(
+                matches!(
+                    decompressor.error(),
+                    Some(DecodeError::UnexpectedEnd { .. })
+                ),
+                "no error decoding the first {} bytes",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn it_errs_on_a_bit_offset_past_the_end_of_the_input() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+        let decode_table = DecodeTable::from_huffman(&table, 8);
+
+        let compressed = compress(&table, None, &[0xA0, 0xCB, 0xA0]);
+        let bit_offset = compressed.len() as u64 * 8 + 9;
+
+        for mut decompressor in [
+            Decompressor::new(&tree, &compressed).with_bit_offset(bit_offset),
+            Decompressor::new(&tree, &compressed)
+                .with_bit_offset(bit_offset)
+                .with_decode_table(&decode_table),
+        ] {
+            let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+            assert!(decompressed.is_empty());
+            assert_eq!(
+                decompressor.error(),
+                Some(&DecodeError::UnexpectedEnd { bit_offset })
+            );
+        }
+    }
+
+    #[test]
+    fn it_errs_when_the_input_ends_before_the_expected_len() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        let src = [0xA0, 0xCB, 0xA0, 0xCB, 0xA0];
+        let compressed = compress(&table, None, &src);
+        assert_eq!(compressed.len(), 2);
+
+        // 01_101_01_1 then 01_01_0000: all five codes but the last fit in the first byte
+        let mut decompressor =
+            Decompressor::new(&tree, &compressed[..1]).with_expected_len(src.len());
+        let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressed, vec![0xA0, 0xCB, 0xA0]);
+        assert_eq!(
+            decompressor.error(),
+            Some(&DecodeError::UnexpectedEnd { bit_offset: 7 })
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_zero_tail_when_more_bytes_are_expected() {
+        let table = truncation_table();
+        let tree = DecodeTree::new(&table, None).unwrap();
+
+        // the zeroes that would be padding without an expected length
+        let compressed = [0b101_00000];
+
+        let mut decompressor = Decompressor::new(&tree, &compressed).with_expected_len(2);
+        let decompressed: Vec<u8> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressed, vec![0xCB]);
+        assert_eq!(
+            decompressor.error(),
+            Some(&DecodeError::InvalidCode { bit_offset: 3 })
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::error::Error;
+use std::fmt;
+
+// the list is whitespace or comma separated hex bytes, each optionally prefixed with 0x.
+// everything after a # on a line is a comment, e.g.
+//
+// # control characters
+// 0x00, 0x1B
+// 7F
+pub fn parse_denylist(src: &[u8]) -> Result<Vec<u8>, DenylistError> {
+    let src = std::str::from_utf8(src).map_err(|_| DenylistError::InvalidUtf8)?;
+    let mut denied = Vec::new();
+
+    for line in src.lines() {
+        let line = line.split('#').next().unwrap();
+
+        for entry in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if entry.is_empty() {
+                continue;
+            }
+
+            let digits = entry
+                .strip_prefix("0x")
+                .or_else(|| entry.strip_prefix("0X"))
+                .unwrap_or(entry);
+
+            let byte = u8::from_str_radix(digits, 16)
+                .map_err(|_| DenylistError::InvalidEntry(entry.to_string()))?;
+
+            denied.push(byte);
+        }
+    }
+
+    Ok(denied)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DenylistError {
+    InvalidUtf8,
+    InvalidEntry(String),
+    // the byte appears more than once in the list
+    Duplicate(u8),
+}
+
+impl fmt::Display for DenylistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DenylistError::InvalidUtf8 => write!(f, "denylist is not valid UTF-8"),
+            DenylistError::InvalidEntry(entry) => {
+                write!(f, "denylist entry {:?} is not a hex byte", entry)
+            }
+            DenylistError::Duplicate(byte) => {
+                write!(f, "byte 0x{:02X} is in the denylist more than once", byte)
+            }
+        }
+    }
+}
+
+impl Error for DenylistError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_hex_bytes_separated_by_whitespace_and_commas() {
+        let denied = parse_denylist(b"0x00, 0x1B\n7f ff").unwrap();
+
+        assert_eq!(denied, vec![0x00, 0x1B, 0x7F, 0xFF]);
+    }
+
+    #[test]
+    fn it_ignores_comments() {
+        let denied = parse_denylist(b"# control characters\n0x00 # null\n0x1B").unwrap();
+
+        assert_eq!(denied, vec![0x00, 0x1B]);
+    }
+
+    #[test]
+    fn it_errors_on_an_entry_that_is_not_a_hex_byte() {
+        assert_eq!(
+            parse_denylist(b"0x00 0x100"),
+            Err(DenylistError::InvalidEntry("0x100".to_string()))
+        );
+        assert_eq!(
+            parse_denylist(b"zz"),
+            Err(DenylistError::InvalidEntry("zz".to_string()))
+        );
+    }
+}
@@ -0,0 +1,722 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Seek, SeekFrom, Write};
+
+use crate::compressor::{BitOrder, Compressor};
+use crate::decode_tree::DecodeTree;
+use crate::decompressor::{DecodeError, Decompressor};
+use crate::frequencies::count_frequencies;
+use crate::huffman_table::HuffmanTable;
+use crate::serialize::{crc32, TableError};
+use crate::terminal_code::TerminalCode;
+
+const MAGIC: &[u8; 4] = b"HUFF";
+const VERSION: u8 = 2;
+// archives from before the table was checksummed, which can still be read
+const UNCHECKED_VERSION: u8 = 1;
+
+// the header of compress_v1 output, whose flags say how the rest is laid out
+const STREAM_MAGIC: &[u8; 4] = b"HUFS";
+const STREAM_VERSION: u8 = 1;
+const LSB_FIRST_FLAG: u8 = 0b001;
+const TERMINAL_CODE_FLAG: u8 = 0b010;
+const LENGTH_PREFIX_FLAG: u8 = 0b100;
+
+// a self describing container: the magic bytes, a version byte, the table as serialize_checked
+// writes it, the length of the original data as a big-endian u64 and then the compressed
+// payload. the payload has no terminal code since the length says where it ends
+pub fn encode_to_vec(data: &[u8]) -> Vec<u8> {
+    let table = HuffmanTable::from_bytes(data);
+
+    let mut bytes = Vec::new();
+    bytes.extend(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend(table.serialize_checked());
+    bytes.extend((data.len() as u64).to_be_bytes());
+
+    let mut compressor = Compressor::new(&table);
+    for &byte in data {
+        // the table was built from data, so every byte has a code
+        compressor
+            .compress_byte(byte)
+            .expect("byte missing from its own table");
+        compressor.drain_into(&mut bytes);
+    }
+    compressor.end();
+    compressor.drain_into(&mut bytes);
+
+    bytes
+}
+
+pub fn decode_from_slice(bytes: &[u8]) -> Result<Vec<u8>, FormatError> {
+    let (data, _) = decode_archive(bytes)?;
+
+    Ok(data)
+}
+
+// the layout options of compress_v1 output, each recorded as a flag in its header
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct StreamFlags {
+    pub bit_order: BitOrder,
+    // end the payload with a terminal code built alongside the table
+    pub terminal_code: bool,
+    // write the length of the original data ahead of the payload
+    pub length_prefix: bool,
+}
+
+impl StreamFlags {
+    fn to_byte(self) -> u8 {
+        let mut byte = 0;
+        if self.bit_order == BitOrder::LsbFirst {
+            byte |= LSB_FIRST_FLAG;
+        }
+        if self.terminal_code {
+            byte |= TERMINAL_CODE_FLAG;
+        }
+        if self.length_prefix {
+            byte |= LENGTH_PREFIX_FLAG;
+        }
+        byte
+    }
+
+    fn from_byte(byte: u8) -> Result<StreamFlags, FormatError> {
+        if byte & !(LSB_FIRST_FLAG | TERMINAL_CODE_FLAG | LENGTH_PREFIX_FLAG) != 0 {
+            return Err(FormatError::UnsupportedFlags(byte));
+        }
+
+        Ok(StreamFlags {
+            bit_order: match byte & LSB_FIRST_FLAG {
+                0 => BitOrder::MsbFirst,
+                _ => BitOrder::LsbFirst,
+            },
+            terminal_code: byte & TERMINAL_CODE_FLAG != 0,
+            length_prefix: byte & LENGTH_PREFIX_FLAG != 0,
+        })
+    }
+}
+
+// a stream with a small checksummed header so later changes to the layout can be told apart: the
+// STREAM_MAGIC bytes, a version byte and a byte of flags, then the terminal code's bit count and
+// big-endian u32 value if it's flagged and the length as a big-endian u64 if that is. a
+// big-endian CRC32 of all of those ends the header, and the table as serialize_checked writes it
+// and the payload follow. with neither flagged the padding at the end can decode as extra bytes
+pub fn compress_v1(src: &[u8], flags: StreamFlags) -> Vec<u8> {
+    let freqs = count_frequencies(src);
+    let (table, terminal_code) = match flags.terminal_code {
+        true => {
+            let (table, terminal_code) = HuffmanTable::from_frequencies_with_eof(&freqs);
+            (table, Some(terminal_code))
+        }
+        false => (HuffmanTable::from_frequencies(&freqs), None),
+    };
+
+    let mut bytes = Vec::new();
+    bytes.extend(STREAM_MAGIC);
+    bytes.push(STREAM_VERSION);
+    bytes.push(flags.to_byte());
+
+    if let Some(terminal_code) = &terminal_code {
+        bytes.push(terminal_code.bit_count);
+        bytes.extend(terminal_code.value.to_be_bytes());
+    }
+
+    if flags.length_prefix {
+        bytes.extend((src.len() as u64).to_be_bytes());
+    }
+
+    bytes.extend(crc32(&bytes).to_be_bytes());
+    bytes.extend(table.serialize_checked());
+
+    let mut compressor = Compressor::with_bit_order(&table, flags.bit_order);
+    for &byte in src {
+        compressor
+            .compress_byte(byte)
+            .expect("byte missing from its own table");
+        compressor.drain_into(&mut bytes);
+    }
+    if let Some(terminal_code) = &terminal_code {
+        compressor
+            .append_terminal_code(terminal_code)
+            .expect("terminal code overflowed the buffer");
+    }
+    compressor.end();
+    compressor.drain_into(&mut bytes);
+
+    bytes
+}
+
+// decodes either compress_v1 or encode_to_vec output, telling them apart by their magic bytes
+pub fn decompress_auto(bytes: &[u8]) -> Result<Vec<u8>, FormatError> {
+    if bytes.starts_with(MAGIC) {
+        return decode_from_slice(bytes);
+    }
+
+    let rest = bytes
+        .strip_prefix(STREAM_MAGIC)
+        .ok_or(FormatError::BadMagic)?;
+
+    let (&version, rest) = rest.split_first().ok_or(FormatError::Truncated)?;
+    if version != STREAM_VERSION {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+
+    let (&flags, mut rest) = rest.split_first().ok_or(FormatError::Truncated)?;
+    let flags = StreamFlags::from_byte(flags)?;
+
+    let mut terminal_code = None;
+    if flags.terminal_code {
+        let (&bit_count, value_rest) = rest.split_first().ok_or(FormatError::Truncated)?;
+        let (value, value_rest) = value_rest
+            .split_first_chunk()
+            .ok_or(FormatError::Truncated)?;
+
+        terminal_code = Some(TerminalCode {
+            bit_count,
+            value: u32::from_be_bytes(*value),
+        });
+        rest = value_rest;
+    }
+
+    let mut len = None;
+    if flags.length_prefix {
+        let (len_bytes, len_rest) = rest.split_first_chunk().ok_or(FormatError::Truncated)?;
+        len = Some(u64::from_be_bytes(*len_bytes));
+        rest = len_rest;
+    }
+
+    let header_len = bytes.len() - rest.len();
+    let (checksum, rest) = rest.split_first_chunk().ok_or(FormatError::Truncated)?;
+    if crc32(&bytes[..header_len]) != u32::from_be_bytes(*checksum) {
+        return Err(FormatError::HeaderChecksumMismatch);
+    }
+
+    let (table, table_len) = HuffmanTable::deserialize_checked(rest)?;
+    let rest = &rest[table_len..];
+
+    // every lsb first byte is the bit reversal of the msb first one
+    let payload: Vec<u8> = match flags.bit_order {
+        BitOrder::MsbFirst => rest.to_vec(),
+        BitOrder::LsbFirst => rest.iter().map(|byte| byte.reverse_bits()).collect(),
+    };
+
+    let tree = DecodeTree::new(&table, terminal_code.as_ref()).map_err(DecodeError::from)?;
+
+    let mut decompressor = Decompressor::new(&tree, &payload);
+    if let Some(len) = len.and_then(|len| usize::try_from(len).ok()) {
+        decompressor = decompressor.with_expected_len(len);
+    }
+
+    let data: Vec<u8> = decompressor.by_ref().collect();
+
+    if let Some(err) = decompressor.error() {
+        return Err(err.clone().into());
+    }
+
+    if len.is_some_and(|len| data.len() as u64 != len) {
+        return Err(FormatError::Truncated);
+    }
+
+    Ok(data)
+}
+
+// decodes every archive in a concatenation of encode_to_vec outputs, e.g. messages appended to a
+// log one after another. each archive's payload ends on a byte boundary, so the next one starts
+// straight after it
+pub fn decompress_multi(blob: &[u8]) -> Result<Vec<Vec<u8>>, FormatError> {
+    let mut messages = Vec::new();
+    let mut rest = blob;
+
+    while !rest.is_empty() {
+        let (data, len) = decode_archive(rest)?;
+        messages.push(data);
+        rest = &rest[len..];
+    }
+
+    Ok(messages)
+}
+
+// writes src as a new archive at the end of file, leaving the archives already in it untouched,
+// so decompress_multi on the whole file gets every message back
+pub fn append_message<W: Write + Seek>(file: &mut W, src: &[u8]) -> io::Result<()> {
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(&encode_to_vec(src))
+}
+
+// decodes the archive at the start of bytes, returning its data and how many bytes it took up
+fn decode_archive(bytes: &[u8]) -> Result<(Vec<u8>, usize), FormatError> {
+    let rest = bytes.strip_prefix(MAGIC).ok_or(FormatError::BadMagic)?;
+
+    let (&version, rest) = rest.split_first().ok_or(FormatError::Truncated)?;
+    let (table, table_len) = match version {
+        VERSION => HuffmanTable::deserialize_checked(rest)?,
+        UNCHECKED_VERSION => HuffmanTable::deserialize(rest)?,
+        version => return Err(FormatError::UnsupportedVersion(version)),
+    };
+    let rest = &rest[table_len..];
+
+    let (len_bytes, payload) = rest.split_first_chunk().ok_or(FormatError::Truncated)?;
+    let len = u64::from_be_bytes(*len_bytes);
+
+    let (data, payload_len) = decode_payload(&table, payload, len)?;
+    let header_len = MAGIC.len() + 1 + table_len + 8;
+
+    Ok((data, header_len + payload_len))
+}
+
+// a lighter self-describing blob than encode_to_vec: only the code lengths are stored, as runs
+// of (count, bit count) byte pairs behind a big-endian u16 of how many bytes the runs take up
+// and followed by a big-endian CRC32 of them. then comes the length of the original data as a big-endian u64 and the payload, compressed
+// with the canonical codes for those lengths
+pub fn compress_self_contained(src: &[u8]) -> Vec<u8> {
+    let table = HuffmanTable::from_bytes(src);
+
+    let mut runs: Vec<u8> = Vec::new();
+    for &bit_count in &table.bit_counts {
+        match runs.last_chunk_mut::<2>() {
+            Some([count, run_bit_count]) if *run_bit_count == bit_count && *count < u8::MAX => {
+                *count += 1
+            }
+            _ => runs.extend([1, bit_count]),
+        }
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend((runs.len() as u16).to_be_bytes());
+    bytes.extend(&runs);
+    bytes.extend(crc32(&runs).to_be_bytes());
+    bytes.extend((src.len() as u64).to_be_bytes());
+
+    let mut compressor = Compressor::new(&table);
+    for &byte in src {
+        compressor
+            .compress_byte(byte)
+            .expect("byte missing from its own table");
+        compressor.drain_into(&mut bytes);
+    }
+    compressor.end();
+    compressor.drain_into(&mut bytes);
+
+    bytes
+}
+
+pub fn decompress_self_contained(blob: &[u8]) -> Result<Vec<u8>, FormatError> {
+    let (runs_len, rest) = blob.split_first_chunk().ok_or(FormatError::Truncated)?;
+    let runs_len = u16::from_be_bytes(*runs_len) as usize;
+
+    let runs = rest.get(..runs_len).ok_or(FormatError::Truncated)?;
+    let rest = &rest[runs_len..];
+
+    let (checksum, rest) = rest.split_first_chunk().ok_or(FormatError::Truncated)?;
+    if crc32(runs) != u32::from_be_bytes(*checksum) {
+        return Err(TableError::ChecksumMismatch.into());
+    }
+
+    let mut lengths = Vec::with_capacity(256);
+    for run in runs.chunks(2) {
+        let &[count, bit_count] = run else {
+            return Err(FormatError::InvalidHeader);
+        };
+
+        if bit_count > 32 || count == 0 {
+            return Err(FormatError::InvalidHeader);
+        }
+
+        lengths.extend(std::iter::repeat_n(bit_count, count as usize));
+    }
+
+    let lengths: [u8; 256] = lengths.try_into().map_err(|_| FormatError::InvalidHeader)?;
+    let table = HuffmanTable::canonical_from_lengths(&lengths);
+
+    let (len_bytes, payload) = rest.split_first_chunk().ok_or(FormatError::Truncated)?;
+    let len = u64::from_be_bytes(*len_bytes);
+
+    let (data, _) = decode_payload(&table, payload, len)?;
+
+    Ok(data)
+}
+
+// decodes len bytes from the start of payload, returning them and how many bytes of payload they
+// took up
+fn decode_payload(
+    table: &HuffmanTable,
+    payload: &[u8],
+    len: u64,
+) -> Result<(Vec<u8>, usize), FormatError> {
+    let tree = DecodeTree::new(table, None).map_err(DecodeError::from)?;
+
+    let mut decompressor = Decompressor::new(&tree, payload);
+    if let Ok(len) = usize::try_from(len) {
+        decompressor = decompressor.with_expected_len(len);
+    }
+
+    let data: Vec<u8> = decompressor.by_ref().collect();
+
+    if let Some(err) = decompressor.error() {
+        return Err(err.clone().into());
+    }
+
+    if data.len() as u64 != len {
+        return Err(FormatError::Truncated);
+    }
+
+    Ok((data, decompressor.consumed_len()))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FormatError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    // a block in adaptive output starts with a byte that isn't a known marker
+    InvalidMarker(u8),
+    // the code length runs of a self-contained blob don't add up to 256 valid bit counts, or an
+    // archive's manifest doesn't match its blocks
+    InvalidHeader,
+    // the archive has fewer blocks than the one asked for
+    NoSuchBlock(usize),
+    // a compress_v1 header has flags set that this version doesn't know
+    UnsupportedFlags(u8),
+    // a compress_v1 header doesn't match the checksum after it
+    HeaderChecksumMismatch,
+    Table(TableError),
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::BadMagic => write!(f, "input does not start with the HUFF magic bytes"),
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "format version {} is not supported", version)
+            }
+            FormatError::Truncated => write!(f, "input is truncated"),
+            FormatError::InvalidMarker(marker) => {
+                write!(f, "invalid block marker 0x{:02X}", marker)
+            }
+            FormatError::InvalidHeader => write!(f, "header is invalid"),
+            FormatError::NoSuchBlock(n) => write!(f, "archive has no block {}", n),
+            FormatError::UnsupportedFlags(flags) => {
+                write!(f, "stream flags 0b{:08b} are not supported", flags)
+            }
+            FormatError::HeaderChecksumMismatch => {
+                write!(f, "stream header does not match its checksum")
+            }
+            FormatError::Table(err) => write!(f, "{}", err),
+            FormatError::Decode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+impl From<TableError> for FormatError {
+    fn from(err: TableError) -> FormatError {
+        match err {
+            TableError::Truncated => FormatError::Truncated,
+            err => FormatError::Table(err),
+        }
+    }
+}
+
+impl From<DecodeError> for FormatError {
+    fn from(err: DecodeError) -> FormatError {
+        match err {
+            DecodeError::UnexpectedEnd { .. } => FormatError::Truncated,
+            err => FormatError::Decode(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &[u8] = b"it was the best of times, it was the worst of times";
+
+    #[test]
+    fn it_round_trips_data_through_the_format() {
+        assert_eq!(decode_from_slice(&encode_to_vec(TEXT)).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn it_round_trips_empty_data() {
+        assert_eq!(
+            decode_from_slice(&encode_to_vec(&[])).unwrap(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn it_round_trips_data_of_a_single_repeated_byte() {
+        let data = [0x00; 100];
+
+        assert_eq!(decode_from_slice(&encode_to_vec(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn it_starts_with_the_magic_and_version() {
+        let bytes = encode_to_vec(TEXT);
+
+        assert_eq!(&bytes[..5], b"HUFF\x02");
+    }
+
+    #[test]
+    fn it_errs_on_corrupt_magic() {
+        let mut bytes = encode_to_vec(TEXT);
+        bytes[0] = b'P';
+
+        assert_eq!(decode_from_slice(&bytes), Err(FormatError::BadMagic));
+    }
+
+    #[test]
+    fn it_errs_on_an_unsupported_version() {
+        let mut bytes = encode_to_vec(TEXT);
+        bytes[4] = 3;
+
+        assert_eq!(
+            decode_from_slice(&bytes),
+            Err(FormatError::UnsupportedVersion(3))
+        );
+    }
+
+    // the offset of the space's bit count in an archive of TEXT. every byte below it has no code
+    // and so takes up a single byte of the table
+    const SPACE_BIT_COUNT_OFFSET: usize = 5 + b' ' as usize;
+
+    #[test]
+    fn it_errs_on_a_table_that_fails_its_checksum() {
+        let mut bytes = encode_to_vec(TEXT);
+        bytes[SPACE_BIT_COUNT_OFFSET] += 1;
+
+        assert_eq!(
+            decode_from_slice(&bytes),
+            Err(FormatError::Table(TableError::ChecksumMismatch))
+        );
+    }
+
+    #[test]
+    fn it_reads_archives_written_before_the_table_checksum() {
+        let table_len = HuffmanTable::from_bytes(TEXT).serialize().len();
+        let mut bytes = encode_to_vec(TEXT);
+        bytes[4] = 1;
+        bytes.drain(5 + table_len..9 + table_len);
+
+        assert_eq!(decode_from_slice(&bytes).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn it_errs_on_a_truncated_payload() {
+        let bytes = encode_to_vec(TEXT);
+
+        assert_eq!(
+            decode_from_slice(&bytes[..bytes.len() - 3]),
+            Err(FormatError::Truncated)
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_truncated_header() {
+        let bytes = encode_to_vec(TEXT);
+
+        for len in [4, 5, 100, bytes.len() - 20] {
+            assert_eq!(
+                decode_from_slice(&bytes[..len]),
+                Err(FormatError::Truncated)
+            );
+        }
+    }
+
+    #[test]
+    fn it_reads_a_stream_header_with_the_terminal_code_flag_set() {
+        let flags = StreamFlags {
+            terminal_code: true,
+            ..StreamFlags::default()
+        };
+        let bytes = compress_v1(TEXT, flags);
+
+        assert_eq!(&bytes[..6], b"HUFS\x01\x02");
+        assert_eq!(decompress_auto(&bytes).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn it_reads_a_stream_header_with_the_length_prefix_flag_set() {
+        let flags = StreamFlags {
+            length_prefix: true,
+            ..StreamFlags::default()
+        };
+        let bytes = compress_v1(TEXT, flags);
+
+        assert_eq!(&bytes[..6], b"HUFS\x01\x04");
+        assert_eq!(decompress_auto(&bytes).unwrap(), TEXT);
+        assert_eq!(
+            decompress_auto(&bytes[..bytes.len() - 3]),
+            Err(FormatError::Truncated)
+        );
+    }
+
+    #[test]
+    fn it_reads_a_stream_with_every_flag_set() {
+        let flags = StreamFlags {
+            bit_order: BitOrder::LsbFirst,
+            terminal_code: true,
+            length_prefix: true,
+        };
+        let bytes = compress_v1(TEXT, flags);
+
+        assert_eq!(bytes[5], 0b111);
+        assert_eq!(decompress_auto(&bytes).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn it_reads_the_original_format_with_decompress_auto() {
+        assert_eq!(decompress_auto(&encode_to_vec(TEXT)).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn it_errs_on_a_stream_table_that_fails_its_checksum() {
+        let mut bytes = compress_v1(TEXT, StreamFlags::default());
+        // the table starts after the magic, version, flags and header checksum
+        bytes[10 + b' ' as usize] += 1;
+
+        assert_eq!(
+            decompress_auto(&bytes),
+            Err(FormatError::Table(TableError::ChecksumMismatch))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_stream_header_that_fails_its_checksum() {
+        let flags = StreamFlags {
+            length_prefix: true,
+            ..StreamFlags::default()
+        };
+        let bytes = compress_v1(TEXT, flags);
+
+        // flagging lsb first order instead, and then a length one byte longer
+        for offset in [5, 13] {
+            let mut corrupt = bytes.clone();
+            corrupt[offset] ^= 1;
+
+            assert_eq!(
+                decompress_auto(&corrupt),
+                Err(FormatError::HeaderChecksumMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn it_errs_on_unknown_stream_flags() {
+        let mut bytes = compress_v1(TEXT, StreamFlags::default());
+        bytes[5] = 0b1000;
+
+        assert_eq!(
+            decompress_auto(&bytes),
+            Err(FormatError::UnsupportedFlags(0b1000))
+        );
+    }
+
+    #[test]
+    fn it_decodes_each_archive_in_a_concatenation() {
+        let messages: [&[u8]; 4] = [TEXT, b"", &[0x00; 100], b"the last message"];
+
+        let blob: Vec<u8> = messages.iter().flat_map(|m| encode_to_vec(m)).collect();
+
+        assert_eq!(decompress_multi(&blob).unwrap(), messages);
+        assert_eq!(decompress_multi(&[]).unwrap(), Vec::<Vec<u8>>::new());
+    }
+
+    #[test]
+    fn it_appends_messages_that_decode_with_decompress_multi() {
+        let mut file = io::Cursor::new(encode_to_vec(b"first"));
+
+        // appending seeks past wherever the cursor was left
+        file.set_position(2);
+        append_message(&mut file, TEXT).unwrap();
+        append_message(&mut file, b"third").unwrap();
+
+        assert_eq!(
+            decompress_multi(file.get_ref()).unwrap(),
+            [&b"first"[..], TEXT, b"third"]
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_truncated_archive_in_a_concatenation() {
+        let mut blob = encode_to_vec(TEXT);
+        blob.extend(&encode_to_vec(TEXT)[..20]);
+
+        assert_eq!(decompress_multi(&blob), Err(FormatError::Truncated));
+    }
+
+    #[test]
+    fn it_round_trips_data_through_a_self_contained_blob() {
+        let mut data = Vec::new();
+        let mut state: u32 = 0x9E37_79B9;
+        for _ in 0..3000 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            data.push((state % 97) as u8 * (state % 3) as u8);
+        }
+
+        for src in [&data[..], TEXT, &[], &[0x00; 100]] {
+            assert_eq!(
+                decompress_self_contained(&compress_self_contained(src)).unwrap(),
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn it_stores_the_code_lengths_as_runs() {
+        let blob = compress_self_contained(b"aaab");
+
+        // 0x00..0x60 have no code, then a and b have 1 bit codes, then the rest have none
+        assert_eq!(&blob[..8], &[0, 6, 97, 0, 2, 1, 157, 0]);
+        assert!(blob.len() < encode_to_vec(b"aaab").len());
+    }
+
+    // rewrites the checksum after a self-contained blob's runs to match them
+    fn refresh_runs_checksum(blob: &mut [u8]) {
+        let runs_len = u16::from_be_bytes([blob[0], blob[1]]) as usize;
+        let checksum = crc32(&blob[2..2 + runs_len]);
+        blob[2 + runs_len..6 + runs_len].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    #[test]
+    fn it_errs_on_a_self_contained_header_that_fails_its_checksum() {
+        let mut blob = compress_self_contained(b"aaab");
+        // gives 0x60 a code as well as a and b
+        blob[3] = 1;
+
+        assert_eq!(
+            decompress_self_contained(&blob),
+            Err(FormatError::Table(TableError::ChecksumMismatch))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_self_contained_header_that_is_invalid() {
+        let blob = compress_self_contained(TEXT);
+
+        let mut short_runs = blob.clone();
+        short_runs[2] -= 1;
+
+        let mut long_code = blob.clone();
+        long_code[3] = 33;
+
+        for mut blob in [short_runs, long_code] {
+            refresh_runs_checksum(&mut blob);
+
+            assert_eq!(
+                decompress_self_contained(&blob),
+                Err(FormatError::InvalidHeader)
+            );
+        }
+
+        assert_eq!(
+            decompress_self_contained(&blob[..blob.len() - 2]),
+            Err(FormatError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::io::{self, ErrorKind, Read};
+
+use crate::huffman_table::HuffmanTable;
+
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+// byte counts accumulated over any number of chunks, for building a table once all the input has
+// been seen
+#[derive(Debug, Clone)]
+pub struct FrequencyTable {
+    counts: [u64; 256],
+}
+
+impl FrequencyTable {
+    pub fn new() -> FrequencyTable {
+        FrequencyTable { counts: [0; 256] }
+    }
+
+    pub fn extend_from_bytes(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.counts[byte as usize] += 1;
+        }
+    }
+
+    // adds other's counts to these, e.g. to build one table shared by several inputs
+    pub fn merge(&mut self, other: &FrequencyTable) {
+        for (count, &other_count) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *count += other_count;
+        }
+    }
+
+    pub fn counts(&self) -> &[u64; 256] {
+        &self.counts
+    }
+
+    // the optimal table for the bytes counted so far, see HuffmanTable::from_frequencies
+    pub fn into_table(self) -> HuffmanTable {
+        HuffmanTable::from_frequencies(&self.counts)
+    }
+}
+
+impl Default for FrequencyTable {
+    fn default() -> FrequencyTable {
+        FrequencyTable::new()
+    }
+}
+
+// passes bytes through from inner while counting them, e.g. to get the frequencies of a
+// Decompressor's output without a second pass over it
+pub struct FrequencyCounter<I> {
+    inner: I,
+    frequencies: FrequencyTable,
+}
+
+impl<I: Iterator<Item = u8>> FrequencyCounter<I> {
+    pub fn new(inner: I) -> FrequencyCounter<I> {
+        FrequencyCounter {
+            inner,
+            frequencies: FrequencyTable::new(),
+        }
+    }
+
+    // e.g. to check Decompressor::error once the bytes have run out
+    pub fn inner(&self) -> &I {
+        &self.inner
+    }
+
+    // the counts of every byte passed through so far
+    pub fn into_frequencies(self) -> [u64; 256] {
+        self.frequencies.counts
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for FrequencyCounter<I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.inner.next()?;
+        self.frequencies.counts[byte as usize] += 1;
+        Some(byte)
+    }
+}
+
+pub fn count_frequencies(data: &[u8]) -> [u64; 256] {
+    let mut frequencies = FrequencyTable::new();
+    frequencies.extend_from_bytes(data);
+    frequencies.counts
+}
+
+pub fn quantize_probabilities(probs: &[f64; 256], scale: u64) -> [u64; 256] {
+    let mut counts = [0; 256];
+
+    for (count, &probability) in counts.iter_mut().zip(probs.iter()) {
+        if probability > 0.0 {
+            // a symbol with any probability at all still needs a code, so it can't round to zero
+            *count = ((probability * scale as f64).round() as u64).max(1);
+        }
+    }
+
+    counts
+}
+
+// counts how often each byte occurs in r, reading it in fixed-size chunks so the whole input
+// never has to be held in memory
+pub fn count_frequencies_reader(r: &mut impl Read) -> io::Result<[u64; 256]> {
+    let mut frequencies = FrequencyTable::new();
+    let mut chunk = [0; READ_CHUNK_SIZE];
+
+    loop {
+        let read_count = match r.read(&mut chunk) {
+            Ok(0) => return Ok(frequencies.counts),
+            Ok(read_count) => read_count,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        frequencies.extend_from_bytes(&chunk[..read_count]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn it_scales_probabilities_to_counts() {
+        let mut probs = [0.0; 256];
+        probs[0x00] = 0.5;
+        probs[0x01] = 0.25;
+        probs[0x02] = 0.25;
+
+        let counts = quantize_probabilities(&probs, 1000);
+
+        assert_eq!(counts[0x00], 500);
+        assert_eq!(counts[0x01], 250);
+        assert_eq!(counts[0x02], 250);
+        assert_eq!(counts[0x03], 0);
+    }
+
+    #[test]
+    fn it_produces_counts_summing_to_roughly_the_scale() {
+        let mut probs = [0.0; 256];
+        for (i, probability) in probs.iter_mut().enumerate().take(7) {
+            *probability = (i + 1) as f64 / 28.0;
+        }
+
+        let scale = 10_000;
+        let counts = quantize_probabilities(&probs, scale);
+        let total: u64 = counts.iter().sum();
+
+        assert!(total.abs_diff(scale) <= 7);
+    }
+
+    #[test]
+    fn it_never_maps_a_nonzero_probability_to_a_zero_count() {
+        let mut probs = [0.0; 256];
+        probs[0x00] = 1.0 - 1e-9;
+        probs[0xFF] = 1e-9;
+
+        let counts = quantize_probabilities(&probs, 100);
+
+        assert_eq!(counts[0x00], 100);
+        assert_eq!(counts[0xFF], 1);
+        assert!(counts[0x01..0xFF].iter().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn it_counts_frequencies_from_a_reader() {
+        let data: Vec<u8> = (0..3 * READ_CHUNK_SIZE + 5)
+            .map(|i| (i % 7) as u8)
+            .collect();
+
+        let counts = count_frequencies_reader(&mut Cursor::new(&data)).unwrap();
+
+        let mut expected_counts = [0; 256];
+        for &byte in &data {
+            expected_counts[byte as usize] += 1;
+        }
+
+        assert_eq!(counts, expected_counts);
+        assert_eq!(counts.iter().sum::<u64>(), data.len() as u64);
+    }
+
+    #[test]
+    fn it_returns_zero_counts_for_an_empty_reader() {
+        let counts = count_frequencies_reader(&mut Cursor::new(Vec::new())).unwrap();
+
+        assert_eq!(counts, [0; 256]);
+    }
+
+    #[test]
+    fn it_counts_how_often_each_byte_occurs() {
+        let counts = count_frequencies(b"abracadabra");
+
+        assert_eq!(counts[b'a' as usize], 5);
+        assert_eq!(counts[b'b' as usize], 2);
+        assert_eq!(counts[b'r' as usize], 2);
+        assert_eq!(counts[b'c' as usize], 1);
+        assert_eq!(counts[b'd' as usize], 1);
+        assert_eq!(counts.iter().sum::<u64>(), 11);
+    }
+
+    #[test]
+    fn it_accumulates_chunks_to_the_same_counts_as_the_concatenation() {
+        let first_chunk = b"the quick brown fox ";
+        let second_chunk = b"jumps over the lazy dog";
+
+        let mut frequencies = FrequencyTable::new();
+        frequencies.extend_from_bytes(first_chunk);
+        frequencies.extend_from_bytes(second_chunk);
+
+        let concatenated = [&first_chunk[..], &second_chunk[..]].concat();
+
+        assert_eq!(frequencies.counts(), &count_frequencies(&concatenated));
+    }
+
+    #[test]
+    fn it_builds_the_same_table_from_merged_counts_as_from_the_concatenation() {
+        let first_input = b"the quick brown fox ";
+        let second_input = b"jumps over the lazy dog";
+
+        let mut first_frequencies = FrequencyTable::new();
+        first_frequencies.extend_from_bytes(first_input);
+
+        let mut second_frequencies = FrequencyTable::new();
+        second_frequencies.extend_from_bytes(second_input);
+
+        first_frequencies.merge(&second_frequencies);
+        let merged_table = first_frequencies.into_table();
+
+        let concatenated = [&first_input[..], &second_input[..]].concat();
+        let concatenated_table = HuffmanTable::from_bytes(&concatenated);
+
+        assert_eq!(merged_table.values, concatenated_table.values);
+        assert_eq!(merged_table.bit_counts, concatenated_table.bit_counts);
+    }
+}
@@ -1,3 +1,12 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::canonical::{canonical_code, canonical_values};
+use crate::compressor::{BitAccumulator, CompressError};
+use crate::denylist::DenylistError;
+use crate::frequencies::count_frequencies;
+use crate::terminal_code::TerminalCode;
+
 pub struct HuffmanTable {
     // the compressed values that will be written for each uncompressed byte.
     // the index in the array is the original byte
@@ -10,7 +19,177 @@ pub struct HuffmanTable {
     pub bit_counts: [u8; 256],
 }
 
+// the byte oriented table, for code that also deals with wide tables
+pub type HuffmanTable256 = HuffmanTable;
+
+// which decoder suits a table, based on its longest code:
+// - Fast: up to 12 bits, small enough for a single lookup table
+// - Medium: up to 20 bits, needs a larger or two-level lookup table
+// - Slow: longer than 20 bits, only practical to decode by walking a tree
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecodeSpeedClass {
+    Fast,
+    Medium,
+    Slow,
+}
+
+// codes are never longer than this, so they always fit the u32 values
+const MAX_CODE_BIT_COUNT: u8 = 32;
+
 impl HuffmanTable {
+    // builds an optimal prefix free table for bytes occurring with the given frequencies, with
+    // canonical codes. bytes with a frequency of 0 get no code
+    pub fn from_frequencies(counts: &[u64; 256]) -> HuffmanTable {
+        let mut lengths = [0; 256];
+        lengths.copy_from_slice(&code_lengths(counts));
+
+        HuffmanTable::canonical_from_lengths(&lengths)
+    }
+
+    // assigns canonical codes from the bit count of each byte alone, so only the lengths need
+    // storing to rebuild the same table. lengths over 32 bits get no code
+    pub fn canonical_from_lengths(lengths: &[u8; 256]) -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values.copy_from_slice(&canonical_values(lengths));
+        for (bit_count, &length) in bit_counts.iter_mut().zip(lengths) {
+            if length <= MAX_CODE_BIT_COUNT {
+                *bit_count = length;
+            }
+        }
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    // like from_frequencies, but with a 257th symbol occurring once for the end of the data,
+    // whose code is returned as the terminal code. it's a leaf of the same tree as the bytes, so
+    // it can never collide with one of their codes
+    pub fn from_frequencies_with_eof(counts: &[u64; 256]) -> (HuffmanTable, TerminalCode) {
+        let mut counts = counts.to_vec();
+        counts.push(1);
+
+        let lengths = code_lengths(&counts);
+        let codes = canonical_values(&lengths);
+
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+        values.copy_from_slice(&codes[..256]);
+        bit_counts.copy_from_slice(&lengths[..256]);
+
+        let terminal_code = TerminalCode {
+            bit_count: lengths[256],
+            value: codes[256],
+        };
+
+        (HuffmanTable { values, bit_counts }, terminal_code)
+    }
+
+    // like from_frequencies, but no code is longer than max_bits. the lengths are the optimal
+    // ones under that limit, found with the package-merge algorithm
+    pub fn length_limited_from_frequencies(counts: &[u64; 256], max_bits: u8) -> HuffmanTable {
+        let mut lengths = [0; 256];
+        lengths.copy_from_slice(&length_limited_code_lengths(counts, max_bits));
+
+        HuffmanTable::canonical_from_lengths(&lengths)
+    }
+
+    // like from_frequencies, but no code is shorter than min_bits, e.g. for hardware decoders
+    // that read at least that many bits per code. the shorter codes are lengthened to the floor,
+    // then the code space that frees up goes back to the most frequent bytes above it
+    pub fn from_frequencies_min_len(counts: &[u64; 256], min_bits: u8) -> HuffmanTable {
+        assert!(
+            (1..=MAX_CODE_BIT_COUNT).contains(&min_bits),
+            "min_bits must be between 1 and 32"
+        );
+
+        let mut lengths = [0; 256];
+        lengths.copy_from_slice(&code_lengths(counts));
+
+        for length in lengths.iter_mut().filter(|length| **length > 0) {
+            *length = (*length).max(min_bits);
+        }
+
+        shorten_into_spare_code_space(&mut lengths, counts, min_bits, &[false; 256]);
+
+        HuffmanTable::canonical_from_lengths(&lengths)
+    }
+
+    // like from_frequencies, but gives the most frequent bytes codes of exactly preferred_bits,
+    // e.g. for decoders that are fastest on 4 or 8 bit codes. bytes are pinned to that length
+    // most frequent first for as long as the data would compress to no more than 1% more bits
+    // than with the optimal table, with the other codes lengthened or shortened to fit
+    pub fn from_frequencies_aligned(counts: &[u64; 256], preferred_bits: u8) -> HuffmanTable {
+        assert!(
+            (1..=MAX_CODE_BIT_COUNT).contains(&preferred_bits),
+            "preferred_bits must be between 1 and 32"
+        );
+
+        let mut optimal = [0; 256];
+        optimal.copy_from_slice(&code_lengths(counts));
+
+        let cost = |lengths: &[u8; 256]| -> u64 {
+            (0..256)
+                .map(|byte| lengths[byte] as u64 * counts[byte])
+                .sum()
+        };
+        let max_cost = cost(&optimal) + cost(&optimal) / 100;
+
+        let mut bytes: Vec<usize> = (0..256).filter(|&byte| optimal[byte] > 0).collect();
+        bytes.sort_by_key(|&byte| Reverse(counts[byte]));
+
+        let mut best = optimal;
+        let mut pinned = [false; 256];
+
+        for byte in bytes {
+            pinned[byte] = true;
+
+            match aligned_code_lengths(&optimal, counts, preferred_bits, &pinned) {
+                Some(lengths) if cost(&lengths) <= max_cost => best = lengths,
+                _ => break,
+            }
+        }
+
+        HuffmanTable::canonical_from_lengths(&best)
+    }
+
+    // a table for the next of a series of similar messages, built from new_freqs blended with
+    // the frequencies this table implies, where a code of n bits stands for a 2^-n share of the
+    // bytes. a blend of 0 ignores this table and 1 keeps its lengths, with anything between
+    // smoothing out the differences from message to message. bytes in either get a code. the
+    // decoder only needs the adapted table's diff against this one to follow along
+    pub fn adapt(&self, new_freqs: &[u64; 256], blend: f64) -> HuffmanTable {
+        assert!(
+            (0.0..=1.0).contains(&blend),
+            "blend must be between 0 and 1"
+        );
+
+        let total = new_freqs.iter().sum::<u64>().max(1) as f64;
+        let mut blended = [0; 256];
+
+        for byte in 0..256 {
+            let bit_count = self.bit_counts[byte];
+            let implied = match bit_count {
+                0 => 0.0,
+                _ => total * 0.5f64.powi(bit_count as i32),
+            };
+
+            let freq = blend * implied + (1.0 - blend) * new_freqs[byte] as f64;
+
+            blended[byte] = match bit_count > 0 || new_freqs[byte] > 0 {
+                true => (freq.round() as u64).max(1),
+                false => 0,
+            };
+        }
+
+        HuffmanTable::from_frequencies(&blended)
+    }
+
+    // builds a table from the frequency of each byte in data. empty data gives a table with no codes
+    pub fn from_bytes(data: &[u8]) -> HuffmanTable {
+        HuffmanTable::from_frequencies(&count_frequencies(data))
+    }
+
     pub fn get_compressed_value(&self, uncompressed_byte: u8) -> u32 {
         self.values[uncompressed_byte as usize]
     }
@@ -18,6 +197,359 @@ impl HuffmanTable {
     pub fn get_compressed_value_bit_count(&self, uncompressed_byte: u8) -> u8 {
         self.bit_counts[uncompressed_byte as usize]
     }
+
+    // removes the codes for the given bytes, so compressing any of them is an error. a byte
+    // listed twice is most likely a typo for another byte, so nothing is removed if one is
+    pub fn with_denylist(&mut self, bytes: &[u8]) -> Result<(), DenylistError> {
+        let mut denied = [false; 256];
+
+        for &byte in bytes {
+            if std::mem::replace(&mut denied[byte as usize], true) {
+                return Err(DenylistError::Duplicate(byte));
+            }
+        }
+
+        for &byte in bytes {
+            self.values[byte as usize] = 0;
+            self.bit_counts[byte as usize] = 0;
+        }
+
+        Ok(())
+    }
+
+    pub fn decode_speed_class(&self) -> DecodeSpeedClass {
+        match self.bit_counts.iter().max().unwrap() {
+            0..=12 => DecodeSpeedClass::Fast,
+            13..=20 => DecodeSpeedClass::Medium,
+            _ => DecodeSpeedClass::Slow,
+        }
+    }
+
+    // the compressor drains every complete byte after each code it writes, so at most 7 bits are
+    // ever left in the buffer when the next code goes in. that next code can be the terminal code,
+    // so it counts towards the longest code too
+    pub fn max_buffered_bits_during_compression(&self, terminal_code: Option<&TerminalCode>) -> u8 {
+        let longest_code = self.bit_counts.iter().max().unwrap();
+        let terminal_bit_count = terminal_code.map_or(0, |terminal_code| terminal_code.bit_count);
+
+        longest_code.max(&terminal_bit_count).saturating_add(7)
+    }
+
+    // the width of the narrowest accumulator a Compressor can use with this table without ever
+    // overflowing its buffer, or None if even a u64 is too narrow
+    pub fn recommended_accumulator_bits(&self, terminal_code: Option<&TerminalCode>) -> Option<u8> {
+        let max_buffered_bits = self.max_buffered_bits_during_compression(terminal_code);
+
+        [u32::CAPACITY, u64::CAPACITY]
+            .into_iter()
+            .find(|&capacity| max_buffered_bits <= capacity)
+    }
+
+    pub fn to_c_header(&self) -> String {
+        let mut header = String::from("#include <stdint.h>\n\n");
+
+        header.push_str("static const uint32_t values[256] = {\n");
+        for row in self.values.chunks(8) {
+            let entries: Vec<String> = row.iter().map(|value| format!("0x{:08X}", value)).collect();
+            header.push_str(&format!("    {},\n", entries.join(", ")));
+        }
+        header.push_str("};\n\n");
+
+        header.push_str("static const uint8_t bit_counts[256] = {\n");
+        for row in self.bit_counts.chunks(16) {
+            let entries: Vec<String> = row.iter().map(|bit_count| bit_count.to_string()).collect();
+            header.push_str(&format!("    {},\n", entries.join(", ")));
+        }
+        header.push_str("};\n");
+
+        header
+    }
+
+    // a table is canonical if every code is the one canonical_code would assign from the bit
+    // counts alone
+    pub fn is_canonical(&self) -> bool {
+        (0..=255).all(|byte| match canonical_code(&self.bit_counts, byte) {
+            Some((value, _)) => self.values[byte as usize] == value,
+            None => self.bit_counts[byte as usize] == 0,
+        })
+    }
+
+    // tables with the same bit counts compress any input to the same size, whatever their values
+    pub fn same_lengths(&self, other: &HuffmanTable) -> bool {
+        self.bit_counts == other.bit_counts
+    }
+
+    // whether compressing bytes occurring with the given frequencies would produce more bits than
+    // writing them uncompressed
+    pub fn is_expanding(&self, freqs: &[u64; 256]) -> bool {
+        let mut compressed_bits = 0;
+        let mut uncompressed_bits = 0;
+
+        for (byte, &freq) in freqs.iter().enumerate() {
+            compressed_bits += freq * self.bit_counts[byte] as u64;
+            uncompressed_bits += freq * 8;
+        }
+
+        compressed_bits > uncompressed_bits
+    }
+
+    // the net bits saved for each byte compared to writing it uncompressed, given how often each
+    // byte occurs. negative values mean the byte's code is longer than the byte itself. a byte
+    // that occurs but has no code can't be compressed at all, so it's a NoCodeForByte error
+    pub fn savings_breakdown(&self, freqs: &[u64; 256]) -> Result<[(u8, i64); 256], CompressError> {
+        let mut savings = [(0, 0); 256];
+
+        for (byte, entry) in savings.iter_mut().enumerate() {
+            if freqs[byte] > 0 && self.bit_counts[byte] == 0 {
+                return Err(CompressError::NoCodeForByte(byte as u8));
+            }
+
+            let saved_bits_per_byte = 8 - self.bit_counts[byte] as i64;
+            *entry = (byte as u8, freqs[byte] as i64 * saved_bits_per_byte);
+        }
+
+        Ok(savings)
+    }
+
+    // how many bits longer each byte's code is than its ideal length of -log2(p), given how often
+    // each byte occurs. bytes that don't occur are left at zero
+    pub fn length_deviation(&self, freqs: &[u64; 256]) -> [f64; 256] {
+        let total: u64 = freqs.iter().sum();
+        let mut deviations = [0.0; 256];
+
+        for (byte, deviation) in deviations.iter_mut().enumerate() {
+            if freqs[byte] == 0 {
+                continue;
+            }
+
+            let probability = freqs[byte] as f64 / total as f64;
+            *deviation = self.bit_counts[byte] as f64 + probability.log2();
+        }
+
+        deviations
+    }
+}
+
+// the depth of each symbol in a huffman tree built from the frequencies. ties between equal
+// weights are broken by the lowest symbol beneath each node, so the same counts always give the
+// same lengths. if the tree is deeper than MAX_CODE_BIT_COUNT the frequencies are halved,
+// flattening the tree, and it's built again
+pub(crate) fn code_lengths(counts: &[u64]) -> Vec<u8> {
+    let mut counts = counts.to_vec();
+
+    loop {
+        let mut nodes: Vec<Option<usize>> = Vec::new();
+        let mut heap = BinaryHeap::new();
+
+        for (symbol, &count) in counts.iter().enumerate() {
+            if count > 0 {
+                heap.push(Reverse((count, symbol, nodes.len())));
+                nodes.push(None);
+            }
+        }
+
+        let mut lengths = vec![0; counts.len()];
+
+        if nodes.len() == 1 {
+            // a lone symbol still needs a 1 bit code
+            let Reverse((_, symbol, _)) = heap.pop().unwrap();
+            lengths[symbol] = 1;
+            return lengths;
+        }
+
+        while heap.len() > 1 {
+            let Reverse((first_weight, first_symbol, first)) = heap.pop().unwrap();
+            let Reverse((second_weight, second_symbol, second)) = heap.pop().unwrap();
+
+            let parent = nodes.len();
+            nodes.push(None);
+            nodes[first] = Some(parent);
+            nodes[second] = Some(parent);
+
+            heap.push(Reverse((
+                first_weight + second_weight,
+                first_symbol.min(second_symbol),
+                parent,
+            )));
+        }
+
+        let symbols = counts.iter().enumerate().filter(|(_, &count)| count > 0);
+        let mut max_length = 0;
+
+        for (leaf, (symbol, _)) in symbols.enumerate() {
+            let mut length = 0;
+            let mut node = leaf;
+
+            while let Some(parent) = nodes[node] {
+                length += 1;
+                node = parent;
+            }
+
+            lengths[symbol] = length.min(u8::MAX as usize) as u8;
+            max_length = max_length.max(length);
+        }
+
+        if max_length <= MAX_CODE_BIT_COUNT as usize {
+            return lengths;
+        }
+
+        for count in counts.iter_mut().filter(|count| **count > 0) {
+            *count = 1 + *count / 2;
+        }
+    }
+}
+
+// the share of the code space a code of the given length takes up, in units of 2^-32, so a
+// set of lengths is prefix free when their shares sum to no more than share(0)
+fn share(length: u8) -> u64 {
+    1 << (MAX_CODE_BIT_COUNT - length)
+}
+
+fn used_code_space(lengths: &[u8; 256]) -> u64 {
+    lengths
+        .iter()
+        .filter(|&&length| length > 0)
+        .map(|&length| share(length))
+        .sum()
+}
+
+// the optimal lengths with the pinned bytes given preferred_bits instead. when that overfills the
+// code space the least frequent unpinned codes are lengthened until it fits, and any space left
+// over goes back to the most frequent. None if the pinned codes can't fit by themselves
+fn aligned_code_lengths(
+    optimal: &[u8; 256],
+    counts: &[u64; 256],
+    preferred_bits: u8,
+    pinned: &[bool; 256],
+) -> Option<[u8; 256]> {
+    let mut lengths = *optimal;
+
+    for byte in (0..256).filter(|&byte| pinned[byte]) {
+        lengths[byte] = preferred_bits;
+    }
+
+    let mut bytes: Vec<usize> = (0..256)
+        .filter(|&byte| lengths[byte] > 0 && !pinned[byte])
+        .collect();
+    bytes.sort_by_key(|&byte| counts[byte]);
+
+    let mut used = used_code_space(&lengths);
+
+    while used > share(0) {
+        // lengthening a code by a bit halves its share
+        let &byte = bytes
+            .iter()
+            .find(|&&byte| lengths[byte] < MAX_CODE_BIT_COUNT)?;
+
+        lengths[byte] += 1;
+        used -= share(lengths[byte]);
+    }
+
+    shorten_into_spare_code_space(&mut lengths, counts, 1, pinned);
+
+    Some(lengths)
+}
+
+// shortens unpinned codes no further than min_bits while the lengths still satisfy the kraft
+// inequality, most frequent bytes first since each bit off their codes saves the most
+fn shorten_into_spare_code_space(
+    lengths: &mut [u8; 256],
+    counts: &[u64; 256],
+    min_bits: u8,
+    pinned: &[bool; 256],
+) {
+    let mut used = used_code_space(lengths);
+
+    let mut bytes: Vec<usize> = (0..256)
+        .filter(|&byte| lengths[byte] > 0 && !pinned[byte])
+        .collect();
+    bytes.sort_by_key(|&byte| Reverse(counts[byte]));
+
+    for byte in bytes {
+        // shortening a code by a bit doubles its share
+        while lengths[byte] > min_bits && used + share(lengths[byte]) <= share(0) {
+            used += share(lengths[byte]);
+            lengths[byte] -= 1;
+        }
+    }
+}
+
+// package-merge: each symbol with a nonzero count is a coin worth its count in every
+// denomination from 2^-1 to 2^-max_bits. starting from the smallest denomination, the cheapest
+// items are paired into packages and merged with the coins of the next denomination up. the
+// cheapest 2n - 2 items of the last list hold the optimal limited code, with each symbol's
+// length being how many of those items contain it. ties favour coins over packages, and the
+// lowest symbol among equal counts
+pub(crate) fn length_limited_code_lengths(counts: &[u64], max_bits: u8) -> Vec<u8> {
+    let mut coins: Vec<(u64, usize)> = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .map(|(symbol, &count)| (count, symbol))
+        .collect();
+    coins.sort();
+
+    assert!(
+        (1..=MAX_CODE_BIT_COUNT).contains(&max_bits),
+        "max_bits must be between 1 and 32"
+    );
+    assert!(
+        coins.len() <= 1 << max_bits,
+        "max_bits is too small to give every symbol a code"
+    );
+
+    let mut lengths = vec![0; counts.len()];
+
+    if coins.len() == 1 {
+        // a lone symbol still needs a 1 bit code
+        lengths[coins[0].1] = 1;
+        return lengths;
+    }
+
+    // each item is its total count and the symbols of the coins inside it
+    let leaves: Vec<(u64, Vec<usize>)> = coins
+        .iter()
+        .map(|&(count, symbol)| (count, vec![symbol]))
+        .collect();
+    let mut items = leaves.clone();
+
+    for _ in 1..max_bits {
+        let packages = items.chunks_exact(2).map(|pair| {
+            let mut symbols = pair[0].1.clone();
+            symbols.extend(&pair[1].1);
+            (pair[0].0 + pair[1].0, symbols)
+        });
+
+        let mut merged = Vec::with_capacity(leaves.len() * 2);
+        let mut leaves = leaves.iter().cloned().peekable();
+        let mut packages = packages.peekable();
+
+        loop {
+            let take_leaf = match (leaves.peek(), packages.peek()) {
+                (Some(leaf), Some(package)) => leaf.0 <= package.0,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
+            };
+
+            let item = if take_leaf {
+                leaves.next()
+            } else {
+                packages.next()
+            };
+            merged.push(item.unwrap());
+        }
+
+        items = merged;
+    }
+
+    for (_, symbols) in items.iter().take(2 * coins.len() - 2) {
+        for &symbol in symbols {
+            lengths[symbol] += 1;
+        }
+    }
+
+    lengths
 }
 
 #[cfg(test)]
@@ -51,4 +583,759 @@ mod tests {
         let compressed_value = huffman_table.get_compressed_value_bit_count(0x33);
         assert_eq!(compressed_value, 12);
     }
+
+    #[test]
+    fn it_returns_the_bits_saved_for_each_byte() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [8; 256];
+        let mut freqs = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+        freqs[0x00] = 1000;
+
+        values[0x01] = 0b1010;
+        bit_counts[0x01] = 4;
+        freqs[0x01] = 10;
+
+        values[0x02] = 0b111111111;
+        bit_counts[0x02] = 9;
+        freqs[0x02] = 3;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        let savings = huffman_table.savings_breakdown(&freqs).unwrap();
+
+        assert_eq!(savings[0x00], (0x00, 7000));
+        assert_eq!(savings[0x01], (0x01, 40));
+        assert_eq!(savings[0x02], (0x02, -3));
+        assert_eq!(savings[0x03], (0x03, 0));
+    }
+
+    #[test]
+    fn it_errs_on_savings_for_a_byte_that_occurs_without_a_code() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+        let mut freqs = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+        freqs[0x00] = 1000;
+
+        freqs[0x01] = 1;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        assert_eq!(
+            huffman_table.savings_breakdown(&freqs),
+            Err(CompressError::NoCodeForByte(0x01))
+        );
+    }
+
+    #[test]
+    fn it_reports_tables_with_the_same_bit_counts_as_having_the_same_lengths() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut other_values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+
+        values[0x10] = 0b0;
+        values[0x20] = 0b1;
+        other_values[0x10] = 0b1;
+        other_values[0x20] = 0b0;
+        bit_counts[0x10] = 1;
+        bit_counts[0x20] = 1;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+        let other_huffman_table = HuffmanTable {
+            values: other_values,
+            bit_counts,
+        };
+
+        assert!(huffman_table.same_lengths(&other_huffman_table));
+    }
+
+    #[test]
+    fn it_reports_tables_with_different_bit_counts_as_not_having_the_same_lengths() {
+        let values: [u32; 256] = [0; 256];
+        let bit_counts: [u8; 256] = [8; 256];
+        let mut other_bit_counts: [u8; 256] = [8; 256];
+
+        other_bit_counts[0xFF] = 9;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+        let other_huffman_table = HuffmanTable {
+            values,
+            bit_counts: other_bit_counts,
+        };
+
+        assert!(!huffman_table.same_lengths(&other_huffman_table));
+    }
+
+    #[test]
+    fn it_returns_the_deviation_of_each_code_length_from_the_ideal() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+        let mut freqs = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+        freqs[0x00] = 2;
+
+        values[0x01] = 0b1000;
+        bit_counts[0x01] = 4;
+        freqs[0x01] = 1;
+
+        values[0x02] = 0b11;
+        bit_counts[0x02] = 2;
+        freqs[0x02] = 1;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        let deviations = huffman_table.length_deviation(&freqs);
+
+        assert!(deviations[0x00].abs() < 1e-9);
+        assert!((deviations[0x01] - 2.0).abs() < 1e-9);
+        assert!(deviations[0x02].abs() < 1e-9);
+        assert_eq!(deviations[0x03], 0.0);
+    }
+
+    fn c_array_entries(header: &str, declaration: &str) -> Vec<String> {
+        let start = header.find(declaration).unwrap() + declaration.len();
+        let end = start + header[start..].find("};").unwrap();
+
+        header[start..end]
+            .split(',')
+            .map(|entry| entry.trim().to_string())
+            .filter(|entry| !entry.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn it_exports_the_table_as_a_c_header() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+
+        values[0x00] = 0b00;
+        bit_counts[0x00] = 2;
+
+        values[0x33] = 0xFFF;
+        bit_counts[0x33] = 12;
+
+        values[0xFF] = 0x1F;
+        bit_counts[0xFF] = 5;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        let header = huffman_table.to_c_header();
+
+        let values = c_array_entries(&header, "static const uint32_t values[256] = {");
+        let bit_counts = c_array_entries(&header, "static const uint8_t bit_counts[256] = {");
+
+        assert_eq!(values.len(), 256);
+        assert_eq!(bit_counts.len(), 256);
+
+        assert_eq!(values[0x33], "0x00000FFF");
+        assert_eq!(values[0xFF], "0x0000001F");
+        assert_eq!(bit_counts[0x00], "2");
+        assert_eq!(bit_counts[0x33], "12");
+        assert_eq!(bit_counts[0xFF], "5");
+        assert_eq!(bit_counts[0x01], "0");
+    }
+
+    #[test]
+    fn it_returns_the_most_bits_that_can_be_buffered_during_compression() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [8; 256];
+
+        values[0xAB] = 0xFFFFF;
+        bit_counts[0xAB] = 20;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        assert_eq!(huffman_table.max_buffered_bits_during_compression(None), 27);
+        assert_eq!(huffman_table.recommended_accumulator_bits(None), Some(32));
+    }
+
+    #[test]
+    fn it_counts_a_terminal_code_longer_than_every_byte_code_towards_the_buffered_bits() {
+        let values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [8; 256];
+        bit_counts[0xAB] = 20;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        let terminal_code = TerminalCode {
+            bit_count: 30,
+            value: 0b1,
+        };
+
+        assert_eq!(
+            huffman_table.max_buffered_bits_during_compression(Some(&terminal_code)),
+            37
+        );
+        assert_eq!(
+            huffman_table.recommended_accumulator_bits(Some(&terminal_code)),
+            Some(64)
+        );
+    }
+
+    #[test]
+    fn it_removes_the_codes_for_denied_bytes() {
+        let values: [u32; 256] = [0xAB; 256];
+        let bit_counts: [u8; 256] = [8; 256];
+
+        let mut huffman_table = HuffmanTable { values, bit_counts };
+
+        huffman_table.with_denylist(&[0x00, 0xFF]).unwrap();
+
+        assert_eq!(huffman_table.get_compressed_value_bit_count(0x00), 0);
+        assert_eq!(huffman_table.get_compressed_value_bit_count(0xFF), 0);
+        assert_eq!(huffman_table.get_compressed_value_bit_count(0x01), 8);
+        assert_eq!(huffman_table.get_compressed_value(0x01), 0xAB);
+    }
+
+    #[test]
+    fn it_errs_on_a_byte_denied_twice_without_removing_any_codes() {
+        let values: [u32; 256] = [0xAB; 256];
+        let bit_counts: [u8; 256] = [8; 256];
+
+        let mut huffman_table = HuffmanTable { values, bit_counts };
+
+        assert_eq!(
+            huffman_table.with_denylist(&[0x00, 0x7F, 0x00]),
+            Err(DenylistError::Duplicate(0x00))
+        );
+        assert_eq!(huffman_table.get_compressed_value_bit_count(0x00), 8);
+        assert_eq!(huffman_table.get_compressed_value_bit_count(0x7F), 8);
+    }
+
+    #[test]
+    fn it_reports_a_table_with_canonical_codes_as_canonical() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+
+        values[0x41] = 0b0;
+        bit_counts[0x41] = 1;
+
+        values[0x00] = 0b10;
+        bit_counts[0x00] = 2;
+
+        values[0x10] = 0b110;
+        bit_counts[0x10] = 3;
+
+        values[0xFF] = 0b111;
+        bit_counts[0xFF] = 3;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        assert!(huffman_table.is_canonical());
+    }
+
+    #[test]
+    fn it_reports_a_table_with_non_canonical_codes_as_not_canonical() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+
+        // a valid prefix code, but 0x10 and 0xFF are the wrong way round
+        values[0x41] = 0b0;
+        bit_counts[0x41] = 1;
+
+        values[0x00] = 0b10;
+        bit_counts[0x00] = 2;
+
+        values[0x10] = 0b111;
+        bit_counts[0x10] = 3;
+
+        values[0xFF] = 0b110;
+        bit_counts[0xFF] = 3;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        assert!(!huffman_table.is_canonical());
+    }
+
+    #[test]
+    fn it_returns_the_decode_speed_class_for_the_longest_code() {
+        let values: [u32; 256] = [0; 256];
+
+        let mut bit_counts: [u8; 256] = [8; 256];
+        bit_counts[0x80] = 12;
+        let huffman_table = HuffmanTable { values, bit_counts };
+        assert_eq!(huffman_table.decode_speed_class(), DecodeSpeedClass::Fast);
+
+        bit_counts[0x80] = 13;
+        let huffman_table = HuffmanTable { values, bit_counts };
+        assert_eq!(huffman_table.decode_speed_class(), DecodeSpeedClass::Medium);
+
+        bit_counts[0x80] = 20;
+        let huffman_table = HuffmanTable { values, bit_counts };
+        assert_eq!(huffman_table.decode_speed_class(), DecodeSpeedClass::Medium);
+
+        bit_counts[0x80] = 21;
+        let huffman_table = HuffmanTable { values, bit_counts };
+        assert_eq!(huffman_table.decode_speed_class(), DecodeSpeedClass::Slow);
+    }
+
+    #[test]
+    fn it_reports_a_table_of_long_codes_as_expanding() {
+        let values: [u32; 256] = [0; 256];
+        let bit_counts: [u8; 256] = [9; 256];
+
+        let mut freqs = [0; 256];
+        freqs[b'e' as usize] = 12;
+        freqs[b't' as usize] = 9;
+        freqs[b'a' as usize] = 8;
+        freqs[b' ' as usize] = 15;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        assert!(huffman_table.is_expanding(&freqs));
+    }
+
+    #[test]
+    fn it_does_not_report_a_table_as_expanding_when_frequent_bytes_have_short_codes() {
+        let values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [9; 256];
+        bit_counts[b' ' as usize] = 2;
+
+        let mut freqs = [0; 256];
+        freqs[b' ' as usize] = 10;
+        freqs[b'e' as usize] = 5;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        assert!(!huffman_table.is_expanding(&freqs));
+    }
+
+    fn fixed_length_bits(counts: &[u64; 256]) -> u64 {
+        counts.iter().sum::<u64>() * 8
+    }
+
+    fn compressed_bits(table: &HuffmanTable, counts: &[u64; 256]) -> u64 {
+        let bits = counts.iter().zip(table.bit_counts.iter());
+        bits.map(|(&count, &bit_count)| count * bit_count as u64)
+            .sum()
+    }
+
+    #[test]
+    fn it_builds_a_table_smaller_than_a_fixed_length_encoding_for_skewed_frequencies() {
+        let mut counts = [0; 256];
+        counts[b'e' as usize] = 120;
+        counts[b't' as usize] = 90;
+        counts[b'a' as usize] = 80;
+        counts[b'q' as usize] = 2;
+        counts[b'z' as usize] = 1;
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        assert!(compressed_bits(&table, &counts) < fixed_length_bits(&counts));
+        assert_eq!(
+            compressed_bits(&table, &counts),
+            120 + 90 * 2 + 80 * 3 + 2 * 4 + 4
+        );
+    }
+
+    #[test]
+    fn it_builds_a_table_no_larger_than_a_fixed_length_encoding_for_uniform_frequencies() {
+        let counts = [10; 256];
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        assert_eq!(table.bit_counts, [8; 256]);
+        assert_eq!(compressed_bits(&table, &counts), fixed_length_bits(&counts));
+    }
+
+    #[test]
+    fn it_gives_bytes_with_no_occurrences_no_code() {
+        let mut counts = [0; 256];
+        counts[0x10] = 3;
+        counts[0x20] = 1;
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        for byte in 0..=255u8 {
+            let expected = if byte == 0x10 || byte == 0x20 { 1 } else { 0 };
+            assert_eq!(table.get_compressed_value_bit_count(byte), expected);
+        }
+    }
+
+    #[test]
+    fn it_gives_a_lone_byte_a_one_bit_code() {
+        let mut counts = [0; 256];
+        counts[0x41] = 7;
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        assert_eq!(table.get_compressed_value_bit_count(0x41), 1);
+        assert_eq!(
+            table.bit_counts.iter().filter(|&&count| count > 0).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn it_builds_prefix_free_canonical_codes() {
+        let mut counts = [0; 256];
+        for (byte, count) in counts.iter_mut().enumerate() {
+            *count = (byte as u64 * 7919) % 61;
+        }
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        assert!(table.is_canonical());
+        assert!(crate::decode_tree::DecodeTree::new(&table, None).is_ok());
+    }
+
+    #[test]
+    fn it_breaks_ties_by_byte_value() {
+        let mut counts = [0; 256];
+        counts[0x03] = 1;
+        counts[0x01] = 1;
+        counts[0x02] = 1;
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        // the two lowest bytes are merged first, leaving the highest with the shortest code
+        assert_eq!(table.bit_counts[0x01], 2);
+        assert_eq!(table.bit_counts[0x02], 2);
+        assert_eq!(table.bit_counts[0x03], 1);
+    }
+
+    #[test]
+    fn it_limits_codes_to_32_bits() {
+        // fibonacci frequencies give the deepest possible tree, one level per byte
+        let mut counts = [0; 256];
+        let (mut previous, mut current) = (1u64, 1u64);
+        for count in counts.iter_mut().take(40) {
+            *count = current;
+            (previous, current) = (current, previous + current);
+        }
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        assert!(*table.bit_counts.iter().max().unwrap() <= 32);
+        assert!(table
+            .bit_counts
+            .iter()
+            .take(40)
+            .all(|&bit_count| bit_count > 0));
+        assert!(crate::decode_tree::DecodeTree::new(&table, None).is_ok());
+    }
+
+    #[test]
+    fn it_builds_a_terminal_code_that_does_not_collide_with_any_byte() {
+        for src in [
+            &b"the quick brown fox jumps over the lazy dog"[..],
+            b"aaaaaaaaaaaaaaab",
+            b"z",
+            b"",
+        ] {
+            let (table, terminal_code) =
+                HuffmanTable::from_frequencies_with_eof(&count_frequencies(src));
+
+            assert!(terminal_code.bit_count > 0);
+            assert_eq!(terminal_code.validate(&table), Ok(()));
+            assert!(crate::decode_tree::DecodeTree::new(&table, Some(&terminal_code)).is_ok());
+        }
+    }
+
+    #[test]
+    fn it_round_trips_with_the_terminal_code_built_alongside_the_table() {
+        let src = b"the quick brown fox jumps over the lazy dog";
+        let (table, terminal_code) =
+            HuffmanTable::from_frequencies_with_eof(&count_frequencies(src));
+        let mut huffman = crate::Huffman::new(table, Some(terminal_code));
+
+        let mut compressed = Vec::new();
+        huffman.compress(src, &mut compressed).unwrap();
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress(&compressed, None, &mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    // 2^max_bits times the sum of 2^-length over every code, which is 2^max_bits exactly when
+    // the codes fill the whole tree
+    fn kraft_sum(table: &HuffmanTable, max_bits: u8) -> u64 {
+        let bit_counts = table.bit_counts.iter().filter(|&&bit_count| bit_count > 0);
+        bit_counts
+            .map(|&bit_count| 1 << (max_bits - bit_count))
+            .sum()
+    }
+
+    #[test]
+    fn it_gives_no_code_shorter_than_the_floor() {
+        let mut counts = [0; 256];
+        for (byte, count) in counts.iter_mut().enumerate().take(20) {
+            *count = 1 << byte;
+        }
+
+        let plain = HuffmanTable::from_frequencies(&counts);
+        let floored = HuffmanTable::from_frequencies_min_len(&counts, 4);
+
+        assert_eq!(
+            *plain.bit_counts.iter().filter(|&&b| b > 0).min().unwrap(),
+            1
+        );
+        assert!(floored
+            .bit_counts
+            .iter()
+            .take(20)
+            .all(|&bit_count| bit_count >= 4));
+        assert!(floored
+            .bit_counts
+            .iter()
+            .skip(20)
+            .all(|&bit_count| bit_count == 0));
+        assert_eq!(kraft_sum(&floored, 32), 1 << 32);
+        assert!(floored.is_canonical());
+        assert!(crate::decode_tree::DecodeTree::new(&floored, None).is_ok());
+    }
+
+    #[test]
+    fn it_builds_an_optimal_table_when_the_floor_is_not_reached() {
+        let text = b"it was the best of times, it was the worst of times";
+        let counts = count_frequencies(text);
+
+        let plain = HuffmanTable::from_frequencies(&counts);
+        let floored = HuffmanTable::from_frequencies_min_len(&counts, 2);
+
+        assert_eq!(floored.bit_counts, plain.bit_counts);
+    }
+
+    #[test]
+    fn it_gives_the_most_frequent_bytes_the_preferred_length_when_it_costs_little() {
+        let mut counts = [0; 256];
+        counts[0] = 150;
+        for count in counts.iter_mut().skip(1).take(12) {
+            *count = 100;
+        }
+
+        let plain = HuffmanTable::from_frequencies(&counts);
+        let aligned = HuffmanTable::from_frequencies_aligned(&counts, 4);
+
+        assert_eq!(plain.bit_counts[0], 3);
+        assert_eq!(aligned.bit_counts[0], 4);
+        assert!(compressed_bits(&aligned, &counts) * 100 <= compressed_bits(&plain, &counts) * 101);
+        assert!(aligned
+            .bit_counts
+            .iter()
+            .take(13)
+            .all(|&bit_count| bit_count > 0));
+        assert!(kraft_sum(&aligned, 32) <= 1 << 32);
+        assert!(crate::decode_tree::DecodeTree::new(&aligned, None).is_ok());
+    }
+
+    #[test]
+    fn it_keeps_the_optimal_table_when_the_preferred_length_costs_too_much() {
+        let mut counts = [0; 256];
+        counts[0] = 1000;
+        for count in counts.iter_mut().skip(1).take(20) {
+            *count = 1;
+        }
+
+        let plain = HuffmanTable::from_frequencies(&counts);
+        let aligned = HuffmanTable::from_frequencies_aligned(&counts, 4);
+
+        assert_eq!(plain.bit_counts[0], 1);
+        assert_eq!(aligned.bit_counts, plain.bit_counts);
+    }
+
+    #[test]
+    fn it_adapts_a_table_towards_new_frequencies() {
+        let previous = HuffmanTable::from_bytes(b"it was the best of times");
+        let new_freqs = count_frequencies(b"zzzzzzzz it was the worst of times");
+
+        let kept = previous.adapt(&new_freqs, 1.0);
+        let replaced = previous.adapt(&new_freqs, 0.0);
+        let blended = previous.adapt(&new_freqs, 0.5);
+
+        // z only occurs in the new message, so the less of the previous table is kept the
+        // shorter its code gets
+        assert!(kept.bit_counts[b'z' as usize] > blended.bit_counts[b'z' as usize]);
+        assert!(blended.bit_counts[b'z' as usize] >= replaced.bit_counts[b'z' as usize]);
+
+        for table in [kept, replaced, blended] {
+            // every byte of either message has a code, and the codes are prefix free
+            for byte in b"zit was the best worst of times" {
+                assert!(table.bit_counts[*byte as usize] > 0);
+            }
+            assert!(kraft_sum(&table, 32) <= 1 << 32);
+            assert!(crate::decode_tree::DecodeTree::new(&table, None).is_ok());
+        }
+    }
+
+    #[test]
+    fn it_limits_the_code_lengths_of_a_geometric_distribution() {
+        let mut counts = [0; 256];
+        for (byte, count) in counts.iter_mut().enumerate().take(40) {
+            *count = 1 << byte;
+        }
+
+        let plain = HuffmanTable::from_frequencies(&counts);
+        let limited = HuffmanTable::length_limited_from_frequencies(&counts, 12);
+
+        assert!(*plain.bit_counts.iter().max().unwrap() > 20);
+        assert!(limited.bit_counts.iter().all(|&bit_count| bit_count <= 12));
+        assert!(limited
+            .bit_counts
+            .iter()
+            .take(40)
+            .all(|&bit_count| bit_count > 0));
+        assert_eq!(kraft_sum(&limited, 12), 1 << 12);
+        assert!(limited.is_canonical());
+        assert!(crate::decode_tree::DecodeTree::new(&limited, None).is_ok());
+    }
+
+    #[test]
+    fn it_builds_an_optimal_table_when_the_limit_is_not_reached() {
+        let mut counts = [0; 256];
+        counts[b'e' as usize] = 120;
+        counts[b't' as usize] = 90;
+        counts[b'a' as usize] = 80;
+        counts[b'q' as usize] = 2;
+        counts[b'z' as usize] = 1;
+
+        let plain = HuffmanTable::from_frequencies(&counts);
+        let limited = HuffmanTable::length_limited_from_frequencies(&counts, 8);
+
+        assert_eq!(
+            compressed_bits(&limited, &counts),
+            compressed_bits(&plain, &counts)
+        );
+    }
+
+    #[test]
+    fn it_gives_every_byte_max_bits_when_the_limit_is_as_tight_as_possible() {
+        let mut counts = [0; 256];
+        for (byte, count) in counts.iter_mut().enumerate() {
+            *count = 1 + byte as u64 * byte as u64;
+        }
+
+        let table = HuffmanTable::length_limited_from_frequencies(&counts, 8);
+
+        assert_eq!(table.bit_counts, [8; 256]);
+    }
+
+    #[test]
+    fn it_gives_a_lone_byte_a_one_bit_length_limited_code() {
+        let mut counts = [0; 256];
+        counts[0x41] = 7;
+
+        let table = HuffmanTable::length_limited_from_frequencies(&counts, 4);
+
+        assert_eq!(table.get_compressed_value_bit_count(0x41), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "max_bits is too small to give every symbol a code")]
+    fn it_panics_when_max_bits_cannot_fit_every_byte() {
+        HuffmanTable::length_limited_from_frequencies(&[1; 256], 7);
+    }
+
+    #[test]
+    fn it_builds_a_table_with_no_codes_from_empty_bytes() {
+        let table = HuffmanTable::from_bytes(&[]);
+
+        assert_eq!(table.bit_counts, [0; 256]);
+    }
+
+    #[test]
+    fn it_builds_a_one_bit_code_from_bytes_with_a_single_distinct_value() {
+        let table = HuffmanTable::from_bytes(&[0x7F; 12]);
+
+        assert_eq!(table.get_compressed_value_bit_count(0x7F), 1);
+        assert_eq!(
+            table
+                .bit_counts
+                .iter()
+                .map(|&count| count as u32)
+                .sum::<u32>(),
+            1
+        );
+    }
+
+    #[test]
+    fn it_compresses_skewed_bytes_smaller_with_a_table_built_from_them() {
+        let mut data = Vec::new();
+        data.extend(std::iter::repeat_n(b'a', 200));
+        data.extend(std::iter::repeat_n(b'b', 40));
+        data.extend(b"cdefgh");
+
+        let table = HuffmanTable::from_bytes(&data);
+
+        let mut compressor = crate::compressor::Compressor::new(&table);
+        let mut output = Vec::new();
+        for &byte in &data {
+            compressor.compress_byte(byte).unwrap();
+            compressor.drain_into(&mut output);
+        }
+        compressor.end();
+        compressor.drain_into(&mut output);
+
+        assert!(output.len() < data.len());
+    }
+
+    #[test]
+    fn it_assigns_the_same_canonical_values_to_tables_with_the_same_lengths() {
+        let mut lengths = [0; 256];
+        lengths[0x41] = 1;
+        lengths[0x00] = 2;
+        lengths[0x42] = 3;
+        lengths[0x43] = 3;
+
+        let table = HuffmanTable::canonical_from_lengths(&lengths);
+        let other_table = HuffmanTable::canonical_from_lengths(&lengths);
+
+        assert_eq!(table.values, other_table.values);
+        assert_eq!(table.bit_counts, lengths);
+        assert!(table.is_canonical());
+
+        assert_eq!(table.values[0x41], 0b0);
+        assert_eq!(table.values[0x00], 0b10);
+        assert_eq!(table.values[0x42], 0b110);
+        assert_eq!(table.values[0x43], 0b111);
+    }
+
+    #[test]
+    fn it_assigns_the_codes_canonical_code_would_to_every_byte() {
+        let mut counts = [0; 256];
+        for (byte, count) in counts.iter_mut().enumerate() {
+            *count = (byte as u64 % 17 + 1) * (byte as u64 % 5 + 1);
+        }
+
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        for byte in 0..=255 {
+            assert_eq!(
+                canonical_code(&table.bit_counts, byte),
+                Some((table.values[byte as usize], table.bit_counts[byte as usize]))
+            );
+        }
+    }
+
+    #[test]
+    fn it_rebuilds_a_table_from_its_lengths() {
+        let table = HuffmanTable::from_bytes(b"abracadabra alakazam");
+
+        let rebuilt = HuffmanTable::canonical_from_lengths(&table.bit_counts);
+
+        assert_eq!(rebuilt.values, table.values);
+        assert_eq!(rebuilt.bit_counts, table.bit_counts);
+    }
+
+    #[test]
+    fn it_gives_no_code_to_lengths_over_32_bits() {
+        let mut lengths = [0; 256];
+        lengths[0x01] = 1;
+        lengths[0x02] = 33;
+
+        let table = HuffmanTable::canonical_from_lengths(&lengths);
+
+        assert_eq!(table.bit_counts[0x01], 1);
+        assert_eq!(table.bit_counts[0x02], 0);
+    }
 }
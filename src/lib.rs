@@ -1,11 +1,94 @@
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+mod adaptive;
+mod analysis;
+mod archive;
+#[cfg(feature = "async")]
+mod async_writer;
+mod burrows_wheeler;
+mod byte_pair;
+mod canonical;
+mod checksum;
+mod chunked;
+mod code_map;
+mod codec;
+mod compress_iter;
 mod compressor;
+mod decode_table;
+mod decode_tree;
+mod decompressor;
+mod denylist;
+mod format;
+mod frequencies;
 mod huffman_table;
+mod lz;
+mod move_to_front;
+mod packed_table;
+mod reader;
+mod report;
+mod serialize;
+mod stats;
+mod streaming_decoder;
+mod table_builder;
+mod table_diff;
 mod terminal_code;
-
-pub use crate::huffman_table::HuffmanTable;
-pub use crate::terminal_code::TerminalCode;
-
-use crate::compressor::Compressor;
+mod tree_builder;
+mod wide_decode_table;
+mod wide_decompressor;
+mod wide_table;
+mod writer;
+
+pub use crate::adaptive::AdaptiveCompressor;
+pub use crate::analysis::{
+    huffman_vs_entropy_gap, optimal_split, output_byte_histogram, symbol_output_bits,
+};
+pub use crate::archive::{Archive, BlockEntry};
+#[cfg(feature = "async")]
+pub use crate::async_writer::{AsyncHuffmanWriter, AsyncWrite};
+pub use crate::burrows_wheeler::{burrows_wheeler_decode, burrows_wheeler_encode};
+pub use crate::byte_pair::MergeTable;
+pub use crate::canonical::{canonical_code, canonical_code_ordered};
+pub use crate::chunked::{compress_chunked_adaptive, ChunkedArchive};
+pub use crate::code_map::CodeMap;
+pub use crate::codec::Codec;
+pub use crate::compress_iter::CompressIter;
+pub use crate::compressor::{
+    BitAccumulator, BitOrder, BufferError, CompressError, Compressor, WideCompressor,
+};
+pub use crate::decode_table::DecodeTable;
+pub use crate::decode_tree::{DecodeTree, DecodeTreeError, DecodedSymbol};
+pub use crate::decompressor::{DecodeError, Decompressor};
+pub use crate::denylist::{parse_denylist, DenylistError};
+pub use crate::format::{
+    append_message, compress_self_contained, compress_v1, decode_from_slice, decompress_auto,
+    decompress_multi, decompress_self_contained, encode_to_vec, FormatError, StreamFlags,
+};
+pub use crate::frequencies::{
+    count_frequencies, count_frequencies_reader, quantize_probabilities, FrequencyCounter,
+    FrequencyTable,
+};
+pub use crate::huffman_table::{DecodeSpeedClass, HuffmanTable, HuffmanTable256};
+pub use crate::lz::{LzTable, LzToken, MAX_DISTANCE, MAX_MATCH_LEN, MIN_MATCH_LEN};
+pub use crate::move_to_front::{move_to_front_decode, move_to_front_encode};
+pub use crate::packed_table::PackedTable;
+pub use crate::reader::HuffmanReader;
+pub use crate::report::{CompressionReport, SymbolContribution, TOP_SYMBOL_COUNT};
+pub use crate::serialize::TableError;
+pub use crate::stats::CompressionStats;
+pub use crate::streaming_decoder::StreamingDecoder;
+pub use crate::table_builder::{HuffmanTableBuilder, TableBuilderError};
+pub use crate::table_diff::TableDiff;
+pub use crate::terminal_code::{TerminalCode, TerminalCodeError};
+pub use crate::tree_builder::{LengthLimitedBuilder, OptimalBuilder, StaticBuilder, TreeBuilder};
+pub use crate::wide_decode_table::WideDecodeTable;
+pub use crate::wide_decompressor::{WideDecodeTree, WideDecompressor};
+pub use crate::wide_table::WideHuffmanTable;
+pub use crate::writer::HuffmanWriter;
+
+use crate::checksum::{Crc32, Fnv1a};
+
+#[cfg(feature = "allocator_api")]
+use std::alloc::Allocator;
 
 pub struct Huffman {
     pub table: HuffmanTable,
@@ -14,36 +97,521 @@ pub struct Huffman {
 
 impl Huffman {
     pub fn new(table: HuffmanTable, terminal_code: Option<TerminalCode>) -> Huffman {
-        return Huffman {
+        Huffman {
             terminal_code,
             table,
-        };
+        }
+    }
+
+    pub fn try_new(
+        table: HuffmanTable,
+        terminal_code: Option<TerminalCode>,
+    ) -> Result<Huffman, TerminalCodeError> {
+        if let Some(terminal_code) = &terminal_code {
+            terminal_code.validate(&table)?;
+        }
+
+        Ok(Huffman::new(table, terminal_code))
+    }
+
+    pub fn compress(&mut self, src: &[u8], output: &mut Vec<u8>) -> Result<(), CompressError> {
+        self.compress_each(
+            src.iter().copied(),
+            self.terminal_code.as_ref(),
+            |compressed_byte| output.push(compressed_byte),
+        )
+    }
+
+    // compresses src lazily, for piping into a consumer without collecting the output first
+    pub fn compress_iter<'a>(&'a self, src: &'a [u8]) -> CompressIter<'a> {
+        CompressIter::new(&self.table, self.terminal_code.as_ref(), src)
+    }
+
+    // compresses src into out without allocating, returning how many bytes were written. errs
+    // with OutputTooSmall as soon as out is full and there's more to write
+    pub fn compress_into_slice(&self, src: &[u8], out: &mut [u8]) -> Result<usize, CompressError> {
+        let mut compressed = self.compress_iter(src);
+        let mut len = 0;
+
+        for compressed_byte in &mut compressed {
+            *out.get_mut(len).ok_or(CompressError::OutputTooSmall)? = compressed_byte;
+            len += 1;
+        }
+
+        match compressed.error() {
+            Some(err) => Err(err.clone()),
+            None => Ok(len),
+        }
+    }
+
+    // the number of bytes compress would output for src, including the terminal code and
+    // padding, without collecting them
+    pub fn compressed_len(&self, src: &[u8]) -> Result<usize, CompressError> {
+        let mut len = 0;
+
+        self.compress_each(src.iter().copied(), self.terminal_code.as_ref(), |_| {
+            len += 1
+        })?;
+
+        Ok(len)
+    }
+
+    // refuses to compress src if the table would make it bigger rather than smaller
+    pub fn compress_unless_expanding(
+        &mut self,
+        src: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), CompressError> {
+        if self.table.is_expanding(&count_frequencies(src)) {
+            return Err(CompressError::Expanding);
+        }
+
+        self.compress(src, output)
+    }
+
+    // builds a table from the frequencies in src with builder and compresses src with it. there's
+    // no terminal code, so decompressing needs src's length as expected_len
+    pub fn compress_optimal(
+        src: &[u8],
+        builder: &dyn TreeBuilder,
+        output: &mut Vec<u8>,
+    ) -> Result<Huffman, CompressError> {
+        let mut huffman = Huffman::new(builder.build(&count_frequencies(src)), None);
+        huffman.compress(src, output)?;
+
+        Ok(huffman)
+    }
+
+    // compresses src with a table from each builder and keeps the smallest, returning the table
+    // as serialize_checked writes it followed by the payload, and the index of the builder that won. the table
+    // counts towards the size, so a builder with slightly longer but cheaper to store codes can
+    // beat the optimal one on short inputs. builders that can't code every byte in src are
+    // skipped, and if none can the last one's error is returned. panics if builders is empty
+    pub fn compress_best_of(
+        src: &[u8],
+        builders: &[&dyn TreeBuilder],
+    ) -> Result<(Vec<u8>, usize), CompressError> {
+        assert!(!builders.is_empty(), "builders must not be empty");
+
+        let mut best: Option<(Vec<u8>, usize)> = None;
+        let mut last_err = None;
+
+        for (index, builder) in builders.iter().enumerate() {
+            let mut payload = Vec::new();
+
+            match Huffman::compress_optimal(src, *builder, &mut payload) {
+                Ok(huffman) => {
+                    let mut output = huffman.table.serialize_checked();
+                    output.extend(payload);
+
+                    if best
+                        .as_ref()
+                        .is_none_or(|(best, _)| output.len() < best.len())
+                    {
+                        best = Some((output, index));
+                    }
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        best.ok_or_else(|| last_err.unwrap())
+    }
+
+    // compresses application symbols by mapping each one to the byte whose code it should be
+    // written with
+    pub fn compress_mapped<T, I, F>(
+        &mut self,
+        src: I,
+        map: F,
+        output: &mut Vec<u8>,
+    ) -> Result<(), CompressError>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(T) -> u8,
+    {
+        self.compress_each(
+            src.into_iter().map(map),
+            self.terminal_code.as_ref(),
+            |compressed_byte| output.push(compressed_byte),
+        )
+    }
+
+    #[cfg(feature = "allocator_api")]
+    pub fn compress_in<A: Allocator>(
+        &mut self,
+        src: &[u8],
+        alloc: A,
+    ) -> Result<Vec<u8, A>, CompressError> {
+        let mut output = Vec::new_in(alloc);
+        self.compress_each(
+            src.iter().copied(),
+            self.terminal_code.as_ref(),
+            |compressed_byte| output.push(compressed_byte),
+        )?;
+        Ok(output)
+    }
+
+    // compresses as normal and also returns an FNV-1a hash of the uncompressed bytes, computed
+    // in the same pass, for deduplicating identical inputs
+    pub fn compress_with_hash(
+        &mut self,
+        src: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<u64, CompressError> {
+        let mut hasher = Fnv1a::new();
+
+        let src = src.iter().copied().inspect(|&byte| hasher.write_byte(byte));
+        self.compress_each(src, self.terminal_code.as_ref(), |compressed_byte| {
+            output.push(compressed_byte)
+        })?;
+
+        Ok(hasher.finish())
+    }
+
+    // compresses src and returns the output along with a CRC32 of the uncompressed bytes,
+    // computed in the same pass
+    pub fn compress_with_checksum(&mut self, src: &[u8]) -> Result<(Vec<u8>, u32), CompressError> {
+        let mut crc = Crc32::new();
+        let mut output = Vec::new();
+
+        let src = src.iter().copied().inspect(|&byte| crc.write_byte(byte));
+        self.compress_each(src, self.terminal_code.as_ref(), |compressed_byte| {
+            output.push(compressed_byte)
+        })?;
+
+        Ok((output, crc.finish()))
+    }
+
+    // applies a move-to-front transform before compressing, which suits tables that give the
+    // smallest bytes the shortest codes. decompressed output must be passed through
+    // move_to_front_decode to get the original bytes back
+    pub fn compress_mtf(&mut self, src: &[u8], output: &mut Vec<u8>) -> Result<(), CompressError> {
+        self.compress(&move_to_front_encode(src), output)
+    }
+
+    // compresses each byte of src as two 4-bit symbols, high nibble first, with a table built
+    // from how often each of the 16 nibbles occurs. suits data like hex digits where the
+    // nibbles are far more skewed than the bytes. there's no terminal code, so
+    // decompress_nibbles needs src's length
+    pub fn compress_nibbles(src: &[u8], output: &mut Vec<u8>) -> Result<Huffman, CompressError> {
+        let nibbles = || src.iter().flat_map(|&byte| [byte >> 4, byte & 0x0F]);
+
+        let mut counts = [0; 256];
+        for nibble in nibbles() {
+            counts[nibble as usize] += 1;
+        }
+
+        let huffman = Huffman::new(HuffmanTable::from_frequencies(&counts), None);
+        huffman.compress_each(nibbles(), None, |compressed_byte| {
+            output.push(compressed_byte)
+        })?;
+
+        Ok(huffman)
+    }
+
+    // the inverse of compress_nibbles, recombining each pair of nibbles into a byte. len is the
+    // number of bytes originally compressed
+    pub fn decompress_nibbles(
+        &self,
+        src: &[u8],
+        len: usize,
+        output: &mut Vec<u8>,
+    ) -> Result<(), DecodeError> {
+        let mut high = None;
+
+        self.decompress_each(src, Some(len * 2), |nibble| match high.take() {
+            Some(high) => output.push(high << 4 | nibble & 0x0F),
+            None => high = Some(nibble),
+        })
+    }
+
+    // applies a burrows-wheeler transform and then move to front before compressing, as bzip2
+    // does, so repetitive data becomes mostly zeroes. output gets the transform's primary index
+    // as a big-endian u64, then the transformed bytes compressed with a table built from them and
+    // a terminal code, so decompress_bwt needs nothing but the returned Huffman
+    pub fn compress_bwt(src: &[u8], output: &mut Vec<u8>) -> Result<Huffman, CompressError> {
+        let (last, primary_index) = burrows_wheeler_encode(src);
+        let transformed = move_to_front_encode(&last);
+
+        let (table, terminal_code) =
+            HuffmanTable::from_frequencies_with_eof(&count_frequencies(&transformed));
+        let mut huffman = Huffman::new(table, Some(terminal_code));
+
+        output.extend((primary_index as u64).to_be_bytes());
+        huffman.compress(&transformed, output)?;
+
+        Ok(huffman)
+    }
+
+    // the inverse of compress_bwt
+    pub fn decompress_bwt(&self, src: &[u8], output: &mut Vec<u8>) -> Result<(), FormatError> {
+        let (primary_index, payload) = src.split_first_chunk().ok_or(FormatError::Truncated)?;
+        let primary_index = u64::from_be_bytes(*primary_index) as usize;
+
+        let mut transformed = Vec::new();
+        self.decompress(payload, None, &mut transformed)?;
+
+        if primary_index >= transformed.len().max(1) {
+            return Err(FormatError::Truncated);
+        }
+
+        let last = move_to_front_decode(&transformed);
+        output.extend(burrows_wheeler_decode(&last, primary_index));
+
+        Ok(())
+    }
+
+    // merges up to merges of the most frequent byte pairs in src into unused bytes before
+    // compressing, see MergeTable. output gets the serialized merge table, then the merged bytes
+    // compressed with a table built from them and a terminal code, so decompress_bpe needs
+    // nothing but the returned Huffman
+    pub fn compress_bpe(
+        src: &[u8],
+        merges: usize,
+        output: &mut Vec<u8>,
+    ) -> Result<Huffman, CompressError> {
+        let merge_table = MergeTable::learn(src, merges);
+        let tokens = merge_table.apply(src);
+
+        let (table, terminal_code) =
+            HuffmanTable::from_frequencies_with_eof(&count_frequencies(&tokens));
+        let mut huffman = Huffman::new(table, Some(terminal_code));
+
+        output.extend(merge_table.serialize());
+        huffman.compress(&tokens, output)?;
+
+        Ok(huffman)
+    }
+
+    // the inverse of compress_bpe, undoing the merges stored at the start of src
+    pub fn decompress_bpe(&self, src: &[u8], output: &mut Vec<u8>) -> Result<(), FormatError> {
+        let (merge_table, merge_table_len) = MergeTable::deserialize(src)?;
+
+        let mut tokens = Vec::new();
+        self.decompress(&src[merge_table_len..], None, &mut tokens)?;
+        output.extend(merge_table.undo(&tokens));
+
+        Ok(())
+    }
+
+    // compresses src into output, tallying the bits written for each byte along the way
+    pub fn compress_with_stats(
+        &self,
+        src: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<CompressionStats, CompressError> {
+        let src_len = src.len();
+        let output_len = output.len();
+        let mut code_bits = 0;
+
+        let src = src.iter().copied().inspect(|&byte| {
+            code_bits += self.table.get_compressed_value_bit_count(byte) as u64;
+        });
+        self.compress_each(src, self.terminal_code.as_ref(), |compressed_byte| {
+            output.push(compressed_byte)
+        })?;
+
+        Ok(CompressionStats::new(
+            src_len,
+            output.len() - output_len,
+            code_bits,
+        ))
+    }
+
+    // compresses src into output, reporting the code lengths used and the bytes that took the
+    // most bits
+    pub fn compress_with_report(
+        &self,
+        src: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<CompressionReport, CompressError> {
+        let output_len = output.len();
+
+        self.compress_each(
+            src.iter().copied(),
+            self.terminal_code.as_ref(),
+            |compressed_byte| output.push(compressed_byte),
+        )?;
+
+        Ok(CompressionReport::new(
+            &self.table,
+            src,
+            output.len() - output_len,
+        ))
+    }
+
+    // compresses without a terminal code, returning the exact number of significant bits in
+    // output so the caller can store it elsewhere (e.g. in a protocol's own header). the final
+    // byte is still emitted but only its top bits are meaningful
+    pub fn compress_exact(
+        &mut self,
+        src: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<u64, CompressError> {
+        let mut bit_len = 0;
+
+        let src = src.iter().copied().inspect(|&byte| {
+            bit_len += self.table.get_compressed_value_bit_count(byte) as u64;
+        });
+        self.compress_each(src, None, |compressed_byte| output.push(compressed_byte))?;
+
+        Ok(bit_len)
+    }
+
+    // compresses src without the given prefix, for records that all start with the same bytes.
+    // the prefix has to be added back on to the decompressed output
+    pub fn compress_stripping_prefix(
+        &mut self,
+        src: &[u8],
+        prefix: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<(), CompressError> {
+        let stripped = src
+            .strip_prefix(prefix)
+            .ok_or(CompressError::MissingPrefix)?;
+
+        self.compress(stripped, output)
+    }
+
+    // the inverse of compress_stripping_prefix, writing the prefix to output ahead of the
+    // decompressed bytes. expected_len is the length of the record without its prefix
+    pub fn decompress_restoring_prefix(
+        &self,
+        src: &[u8],
+        prefix: &[u8],
+        expected_len: Option<usize>,
+        output: &mut Vec<u8>,
+    ) -> Result<(), DecodeError> {
+        output.extend(prefix);
+
+        self.decompress(src, expected_len, output)
+    }
+
+    // compresses every row one after the other with the same table, returning the bit offset
+    // that each row starts at in the output so rows can be decoded individually
+    pub fn compress_grid(&mut self, rows: &[&[u8]]) -> Result<(Vec<u8>, Vec<u64>), CompressError> {
+        let mut row_bit_offsets = Vec::with_capacity(rows.len());
+        let mut bit_offset = 0;
+
+        for row in rows {
+            row_bit_offsets.push(bit_offset);
+
+            for &byte in row.iter() {
+                bit_offset += self.table.get_compressed_value_bit_count(byte) as u64;
+            }
+        }
+
+        let mut output = Vec::new();
+
+        let src = rows.iter().flat_map(|row| row.iter().copied());
+        self.compress_each(src, self.terminal_code.as_ref(), |compressed_byte| {
+            output.push(compressed_byte)
+        })?;
+
+        Ok((output, row_bit_offsets))
     }
 
-    pub fn compress(&mut self, src: Vec<u8>, output: &mut Vec<u8>) {
+    // decodes src with the same table and terminal code it was compressed with. without a
+    // terminal code, expected_len (the number of bytes originally compressed) is needed if the
+    // padding could decode as a byte. src running out before expected_len bytes have been
+    // decoded is an UnexpectedEnd error
+    pub fn decompress(
+        &self,
+        src: &[u8],
+        expected_len: Option<usize>,
+        output: &mut Vec<u8>,
+    ) -> Result<(), DecodeError> {
+        self.decompress_each(src, expected_len, |byte| output.push(byte))
+    }
+
+    // the inverse of compress_mapped, passing each decoded byte back through inverse to get the
+    // application symbol it was mapped from
+    pub fn decompress_mapped<T, F>(
+        &self,
+        src: &[u8],
+        expected_len: Option<usize>,
+        mut inverse: F,
+        output: &mut Vec<T>,
+    ) -> Result<(), DecodeError>
+    where
+        F: FnMut(u8) -> T,
+    {
+        self.decompress_each(src, expected_len, |byte| output.push(inverse(byte)))
+    }
+
+    fn decompress_each<F>(
+        &self,
+        src: &[u8],
+        expected_len: Option<usize>,
+        mut emit: F,
+    ) -> Result<(), DecodeError>
+    where
+        F: FnMut(u8),
+    {
+        let tree = DecodeTree::new(&self.table, self.terminal_code.as_ref())?;
+
+        let mut decompressor = Decompressor::new(&tree, src);
+        if let Some(expected_len) = expected_len {
+            decompressor = decompressor.with_expected_len(expected_len);
+        }
+
+        let mut decompressed_len = 0;
+        for byte in &mut decompressor {
+            emit(byte);
+            decompressed_len += 1;
+        }
+
+        if let Some(err) = decompressor.error() {
+            return Err(err.clone());
+        }
+
+        match expected_len {
+            Some(expected_len) if decompressed_len < expected_len => {
+                Err(DecodeError::UnexpectedEnd {
+                    bit_offset: src.len() as u64 * 8,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn compress_each<I, F>(
+        &self,
+        src: I,
+        terminal_code: Option<&TerminalCode>,
+        mut emit: F,
+    ) -> Result<(), CompressError>
+    where
+        I: IntoIterator<Item = u8>,
+        F: FnMut(u8),
+    {
         let mut compressor = Compressor::new(&self.table);
 
         for byte in src {
-            compressor.compress_byte(byte);
+            compressor.compress_byte(byte)?;
 
             for compressed_byte in &mut compressor {
-                output.push(compressed_byte);
+                emit(compressed_byte);
             }
         }
 
-        if let Some(terminal_code) = &self.terminal_code {
-            compressor.append_terminal_code(terminal_code);
+        if let Some(terminal_code) = terminal_code {
+            compressor.append_terminal_code(terminal_code)?;
         }
 
         compressor.end();
 
         for compressed_byte in &mut compressor {
-            output.push(compressed_byte);
+            emit(compressed_byte);
         }
+
+        Ok(())
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
 mod tests {
     use super::*;
 
@@ -64,7 +632,7 @@ mod tests {
         let src = vec![uncompressed_byte];
         let mut output = Vec::new();
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b10000000]);
     }
@@ -86,7 +654,7 @@ mod tests {
         let src = vec![uncompressed_byte, uncompressed_byte, uncompressed_byte];
         let mut output = Vec::new();
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b11100000]);
     }
@@ -108,7 +676,7 @@ mod tests {
         let src = vec![uncompressed_byte, uncompressed_byte];
         let mut output = Vec::new();
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b11111111, 0b11000000]);
     }
@@ -134,7 +702,7 @@ mod tests {
 
         let mut huffman = Huffman::new(table, None);
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b01101100]);
     }
@@ -159,7 +727,7 @@ mod tests {
         let src = vec![uncompressed_byte, uncompressed_byte_2];
         let mut output = Vec::new();
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b00111000]);
     }
@@ -180,7 +748,7 @@ mod tests {
         let src = vec![uncompressed_byte];
         let mut output = Vec::new();
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b00000001]);
     }
@@ -206,7 +774,7 @@ mod tests {
         let src = vec![uncompressed_byte];
         let mut output = Vec::new();
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b1010_111_0]);
     }
@@ -232,8 +800,996 @@ mod tests {
         let src = vec![uncompressed_byte];
         let mut output = Vec::new();
 
-        huffman.compress(src, &mut output);
+        huffman.compress(&src, &mut output).unwrap();
 
         assert_eq!(output, vec![0b10000000, 0b10100000]);
     }
+
+    #[test]
+    #[cfg(feature = "allocator_api")]
+    fn it_compresses_into_a_vec_with_the_given_allocator() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b101;
+        bit_counts[0xCB] = 3;
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, Some(terminal_code));
+
+        let src = vec![0xA0, 0xCB, 0xCB, 0xA0];
+        let mut output = Vec::new();
+
+        huffman.compress(&src, &mut output).unwrap();
+
+        let output_in = huffman.compress_in(&src, std::alloc::Global).unwrap();
+
+        assert_eq!(output_in.as_slice(), output.as_slice());
+    }
+
+    #[test]
+    fn it_returns_the_same_hash_for_identical_inputs() {
+        let values = [0b1; 256];
+        let bit_counts = [2; 256];
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let mut output = Vec::new();
+        let hash = huffman
+            .compress_with_hash(&[0x01, 0x02, 0x03], &mut output)
+            .unwrap();
+
+        let mut other_output = Vec::new();
+        let other_hash = huffman
+            .compress_with_hash(&[0x01, 0x02, 0x03], &mut other_output)
+            .unwrap();
+
+        assert_eq!(hash, other_hash);
+        assert_eq!(output, other_output);
+    }
+
+    #[test]
+    fn it_returns_different_hashes_for_different_inputs() {
+        let values = [0b1; 256];
+        let bit_counts = [2; 256];
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let mut output = Vec::new();
+        let hash = huffman
+            .compress_with_hash(&[0x01, 0x02, 0x03], &mut output)
+            .unwrap();
+
+        let mut other_output = Vec::new();
+        let other_hash = huffman
+            .compress_with_hash(&[0x03, 0x02, 0x01], &mut other_output)
+            .unwrap();
+
+        // every byte has the same code, so only the hash can tell these inputs apart
+        assert_eq!(output, other_output);
+        assert_ne!(hash, other_hash);
+    }
+
+    #[test]
+    fn it_rejects_a_terminal_code_that_collides_with_a_byte_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b1010;
+        bit_counts[0x92] = 4;
+
+        let terminal_code = TerminalCode {
+            bit_count: 4,
+            value: 0b1010,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let result = Huffman::try_new(table, Some(terminal_code));
+
+        assert_eq!(
+            result.err(),
+            Some(TerminalCodeError::ConflictsWithByte(0x92))
+        );
+    }
+
+    #[test]
+    fn it_accepts_a_terminal_code_that_does_not_collide_with_any_byte_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b1010;
+        bit_counts[0x92] = 4;
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        assert!(Huffman::try_new(table, Some(terminal_code)).is_ok());
+    }
+
+    #[test]
+    fn it_compresses_bytes_with_strong_locality_smaller_after_move_to_front() {
+        let mut values = [0; 256];
+        let mut bit_counts = [9; 256];
+
+        // 0x00 gets a single bit code, every other byte is a 1 followed by the byte itself
+        for (byte, value) in values.iter_mut().enumerate().skip(1) {
+            *value = 0x100 | byte as u32;
+        }
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let mut src = Vec::new();
+        for byte in [0x61, 0x62, 0x61, 0xF0, 0x0A] {
+            src.extend(std::iter::repeat_n(byte, 16));
+        }
+
+        let mut output = Vec::new();
+        huffman.compress(&src, &mut output).unwrap();
+
+        let mut mtf_output = Vec::new();
+        huffman.compress_mtf(&src, &mut mtf_output).unwrap();
+
+        assert!(mtf_output.len() < output.len());
+        assert_eq!(move_to_front_decode(&move_to_front_encode(&src)), src);
+    }
+
+    #[test]
+    fn it_round_trips_bytes_compressed_as_nibbles() {
+        // bytes made up of only three nibble values
+        let mut src = Vec::new();
+        for i in 0..400 {
+            src.push([0x00, 0x0F, 0xF0, 0x33, 0x3F][i % 5]);
+        }
+
+        let mut output = Vec::new();
+        let huffman = Huffman::compress_nibbles(&src, &mut output).unwrap();
+
+        assert!(output.len() < src.len() / 2);
+        assert!(huffman.table.bit_counts.iter().skip(16).all(|&b| b == 0));
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress_nibbles(&output, src.len(), &mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_round_trips_every_byte_compressed_as_nibbles() {
+        let src: Vec<u8> = (0..=255).collect();
+
+        let mut output = Vec::new();
+        let huffman = Huffman::compress_nibbles(&src, &mut output).unwrap();
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress_nibbles(&output, src.len(), &mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_merges_a_repeated_pair_and_round_trips_through_the_stored_merge_table() {
+        let mut src = Vec::new();
+        for i in 0..200 {
+            src.extend(b"ab");
+            src.push(b"xyz"[i % 3]);
+        }
+
+        let mut output = Vec::new();
+        let huffman = Huffman::compress_bpe(&src, 4, &mut output).unwrap();
+
+        let (merge_table, _) = MergeTable::deserialize(&output).unwrap();
+        assert_eq!(merge_table.merges[0], (0x00, *b"ab"));
+
+        let mut plain_output = Vec::new();
+        Huffman::compress_optimal(&src, &OptimalBuilder, &mut plain_output).unwrap();
+        assert!(output.len() < plain_output.len());
+
+        let mut decompressed = Vec::new();
+        huffman.decompress_bpe(&output, &mut decompressed).unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_compresses_repetitive_input_much_smaller_with_burrows_wheeler() {
+        let mut src = Vec::new();
+        for i in 0..300 {
+            src.extend(b"the quick brown fox jumps over the lazy dog ");
+            src.extend(i.to_string().as_bytes());
+        }
+
+        let mut output = Vec::new();
+        let huffman = Huffman::compress_bwt(&src, &mut output).unwrap();
+
+        let mut plain_output = Vec::new();
+        Huffman::compress_optimal(&src, &OptimalBuilder, &mut plain_output).unwrap();
+        assert!(output.len() * 3 < plain_output.len());
+
+        let mut decompressed = Vec::new();
+        huffman.decompress_bwt(&output, &mut decompressed).unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_round_trips_empty_input_with_burrows_wheeler() {
+        let mut output = Vec::new();
+        let huffman = Huffman::compress_bwt(&[], &mut output).unwrap();
+
+        let mut decompressed = Vec::new();
+        huffman.decompress_bwt(&output, &mut decompressed).unwrap();
+
+        assert_eq!(decompressed, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn it_returns_a_crc32_of_the_input_alongside_the_compressed_output() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x31] = 0b01;
+        bit_counts[0x31] = 2;
+
+        values[0x32] = 0b10;
+        bit_counts[0x32] = 2;
+
+        values[0x33] = 0b110;
+        bit_counts[0x33] = 3;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let src = b"123321".to_vec();
+
+        let mut expected_output = Vec::new();
+        huffman.compress(&src, &mut expected_output).unwrap();
+
+        let (output, crc) = huffman.compress_with_checksum(&src).unwrap();
+
+        assert_eq!(output, expected_output);
+        assert_eq!(crc, 0xDD18C680); // CRC32 of "123321"
+    }
+
+    #[test]
+    fn it_returns_stats_for_the_compressed_output() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x02] = 0b11;
+        bit_counts[0x02] = 2;
+
+        let huffman = Huffman::new(
+            HuffmanTable { values, bit_counts },
+            Some(TerminalCode {
+                bit_count: 3,
+                value: 0b111,
+            }),
+        );
+
+        let src = [0x00, 0x00, 0x01, 0x00, 0x02, 0x00];
+        let mut output = Vec::new();
+
+        let stats = huffman.compress_with_stats(&src, &mut output).unwrap();
+
+        // 0_0_10_0_11_0 then the terminal code 111 and padding
+        assert_eq!(output, vec![0b0_0_10_0_11_0, 0b111_00000]);
+        assert_eq!(stats.input_bytes, 6);
+        assert_eq!(stats.output_bytes, 2);
+        assert_eq!(
+            stats.ratio,
+            stats.output_bytes as f64 / stats.input_bytes as f64
+        );
+        assert_eq!(stats.avg_code_bits, (1 + 1 + 2 + 1 + 2 + 1) as f64 / 6.0);
+    }
+
+    #[test]
+    fn it_reports_the_histogram_and_top_symbols_of_a_compress() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x02] = 0b11;
+        bit_counts[0x02] = 2;
+
+        let huffman = Huffman::new(
+            HuffmanTable { values, bit_counts },
+            Some(TerminalCode {
+                bit_count: 3,
+                value: 0b111,
+            }),
+        );
+
+        let src = [0x00, 0x00, 0x01, 0x00, 0x02, 0x00];
+        let mut output = Vec::new();
+
+        let report = huffman.compress_with_report(&src, &mut output).unwrap();
+
+        assert_eq!(output, vec![0b0_0_10_0_11_0, 0b111_00000]);
+        assert_eq!(report.input_bytes, 6);
+        assert_eq!(report.output_bytes, 2);
+        assert_eq!(report.ratio, 2.0 / 6.0);
+        assert_eq!(report.length_histogram, vec![(1, 4), (2, 2)]);
+        assert_eq!(
+            report.top_symbols,
+            vec![
+                SymbolContribution {
+                    byte: 0x00,
+                    count: 4,
+                    bits: 4
+                },
+                SymbolContribution {
+                    byte: 0x01,
+                    count: 1,
+                    bits: 2
+                },
+                SymbolContribution {
+                    byte: 0x02,
+                    count: 1,
+                    bits: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_returns_zeroed_stats_for_empty_input() {
+        let huffman = Huffman::new(HuffmanTable::from_bytes(b"abc"), None);
+
+        let stats = huffman.compress_with_stats(&[], &mut Vec::new()).unwrap();
+
+        assert_eq!(
+            stats,
+            CompressionStats {
+                input_bytes: 0,
+                output_bytes: 0,
+                ratio: 0.0,
+                avg_code_bits: 0.0,
+            }
+        );
+    }
+
+    #[test]
+    fn it_returns_the_exact_bit_length_and_skips_the_terminal_code() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x92] = 0b1010;
+        bit_counts[0x92] = 4;
+
+        values[0x93] = 0b11;
+        bit_counts[0x93] = 2;
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, Some(terminal_code));
+
+        let src = vec![0x92, 0x93, 0x92, 0x93];
+        let mut output = Vec::new();
+
+        let bit_len = huffman.compress_exact(&src, &mut output).unwrap();
+
+        assert_eq!(bit_len, 12);
+        assert_eq!(output, vec![0b1010_11_10, 0b10_11_0000]);
+    }
+
+    #[test]
+    fn it_decompresses_with_the_exact_bit_length() {
+        let table = table_from_codes(&[(0x00, 0b0, 2), (0x01, 0b01, 2), (0x02, 0b1, 1)]);
+        let tree = DecodeTree::new(&table, None).unwrap();
+        let mut huffman = Huffman::new(table, None);
+
+        // the five bits of padding would otherwise decode as two more 0x00 bytes
+        let src = [0x02, 0x01];
+        let mut output = Vec::new();
+        let bit_len = huffman.compress_exact(&src, &mut output).unwrap();
+
+        let decompressed: Vec<u8> = Decompressor::new(&tree, &output)
+            .with_bit_len(bit_len)
+            .collect();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_compresses_a_grid_and_returns_the_bit_offset_of_each_row() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x7F] = 0b10;
+        bit_counts[0x7F] = 2;
+
+        values[0xFF] = 0b110;
+        bit_counts[0xFF] = 3;
+
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, Some(terminal_code));
+
+        let rows: [&[u8]; 3] = [&[0x00, 0xFF, 0x7F], &[0xFF, 0xFF], &[0x7F, 0x00, 0x00]];
+
+        let (output, row_bit_offsets) = huffman.compress_grid(&rows).unwrap();
+
+        assert_eq!(row_bit_offsets, vec![0, 6, 12]);
+
+        let mut expected_output = Vec::new();
+        huffman
+            .compress(&rows.concat(), &mut expected_output)
+            .unwrap();
+        assert_eq!(output, expected_output);
+
+        // each row decodes on its own from its recorded bit offset
+        let tree = DecodeTree::new(&huffman.table, huffman.terminal_code.as_ref()).unwrap();
+        for (row, &row_bit_offset) in rows.iter().zip(&row_bit_offsets) {
+            let decompressed: Vec<u8> = Decompressor::new(&tree, &output)
+                .with_bit_offset(row_bit_offset)
+                .with_expected_len(row.len())
+                .collect();
+
+            assert_eq!(decompressed, *row);
+        }
+
+        // or up to the next row's offset, without knowing the row's length
+        let decompressed: Vec<u8> = Decompressor::new(&tree, &output)
+            .with_bit_offset(row_bit_offsets[1])
+            .with_bit_len(row_bit_offsets[2] - row_bit_offsets[1])
+            .collect();
+
+        assert_eq!(decompressed, rows[1]);
+    }
+
+    #[test]
+    fn it_compresses_a_record_without_its_prefix() {
+        let values = [0b1; 256];
+        let bit_counts = [4; 256];
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let prefix = b"2026-10-14T";
+        let src = b"2026-10-14T12:00:00 ok";
+
+        let mut output = Vec::new();
+        huffman.compress(src, &mut output).unwrap();
+
+        let mut stripped_output = Vec::new();
+        huffman
+            .compress_stripping_prefix(src, prefix, &mut stripped_output)
+            .unwrap();
+
+        let mut expected_output = Vec::new();
+        huffman
+            .compress(b"12:00:00 ok", &mut expected_output)
+            .unwrap();
+
+        assert!(stripped_output.len() < output.len());
+        assert_eq!(stripped_output, expected_output);
+    }
+
+    #[test]
+    fn it_round_trips_with_a_table_from_each_tree_builder() {
+        let src = b"it was the best of times, it was the worst of times";
+        let builders: [&dyn TreeBuilder; 3] = [
+            &OptimalBuilder,
+            &LengthLimitedBuilder { max_bits: 5 },
+            &StaticBuilder {
+                table: HuffmanTable::from_bytes(&(0..=255).collect::<Vec<u8>>()),
+            },
+        ];
+
+        for builder in builders {
+            let mut output = Vec::new();
+            let huffman = Huffman::compress_optimal(src, builder, &mut output).unwrap();
+
+            let mut decompressed = Vec::new();
+            huffman
+                .decompress(&output, Some(src.len()), &mut decompressed)
+                .unwrap();
+
+            assert_eq!(decompressed, src);
+        }
+    }
+
+    #[test]
+    fn it_picks_the_length_limited_builder_when_its_table_is_cheaper_to_store() {
+        // fibonacci frequencies give the optimal table codes too long to store in a byte
+        let mut src = Vec::new();
+        let (mut a, mut b) = (1, 1);
+        for byte in 0..16 {
+            src.extend(std::iter::repeat_n(byte, a));
+            (a, b) = (b, a + b);
+        }
+
+        let builders: [&dyn TreeBuilder; 2] =
+            [&OptimalBuilder, &LengthLimitedBuilder { max_bits: 8 }];
+
+        let (output, chosen) = Huffman::compress_best_of(&src, &builders).unwrap();
+
+        assert_eq!(chosen, 1);
+
+        let (table, table_len) = HuffmanTable::deserialize_checked(&output).unwrap();
+        let mut decompressed = Vec::new();
+        Huffman::new(table, None)
+            .decompress(&output[table_len..], Some(src.len()), &mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_skips_builders_that_cannot_code_every_byte() {
+        let src = b"abracadabra";
+        let builders: [&dyn TreeBuilder; 2] = [
+            &StaticBuilder {
+                table: HuffmanTable::from_bytes(b"abc"),
+            },
+            &OptimalBuilder,
+        ];
+
+        let (_, chosen) = Huffman::compress_best_of(src, &builders).unwrap();
+
+        assert_eq!(chosen, 1);
+        assert_eq!(
+            Huffman::compress_best_of(src, &builders[..1]),
+            Err(CompressError::NoCodeForByte(b'r'))
+        );
+    }
+
+    #[test]
+    fn it_restores_the_stripped_prefix_when_decompressing() {
+        let prefix = b"2026-10-14T";
+        let src = b"2026-10-14T12:00:00 ok";
+
+        let mut huffman = Huffman::new(HuffmanTable::from_bytes(src), None);
+
+        let mut stripped_output = Vec::new();
+        huffman
+            .compress_stripping_prefix(src, prefix, &mut stripped_output)
+            .unwrap();
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress_restoring_prefix(
+                &stripped_output,
+                prefix,
+                Some(src.len() - prefix.len()),
+                &mut decompressed,
+            )
+            .unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_errors_when_a_record_does_not_start_with_the_prefix() {
+        let values = [0b1; 256];
+        let bit_counts = [4; 256];
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let mut output = Vec::new();
+        let result = huffman.compress_stripping_prefix(b"2025-01-01", b"2026-", &mut output);
+
+        assert_eq!(result, Err(CompressError::MissingPrefix));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn it_errors_when_compressing_a_denied_byte() {
+        let values = [0b1010; 256];
+        let bit_counts = [4; 256];
+
+        let mut table = HuffmanTable { values, bit_counts };
+        table
+            .with_denylist(&parse_denylist(b"0x00 0x7F").unwrap())
+            .unwrap();
+
+        let mut huffman = Huffman::new(table, None);
+
+        let mut output = Vec::new();
+        huffman.compress(&[0x41, 0x42], &mut output).unwrap();
+        assert_eq!(output, vec![0b1010_1010]);
+
+        let mut output = Vec::new();
+        let result = huffman.compress(&[0x41, 0x7F], &mut output);
+        assert_eq!(result, Err(CompressError::NoCodeForByte(0x7F)));
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Direction {
+        North,
+        East,
+        South,
+        West,
+    }
+
+    impl Direction {
+        fn from_byte(byte: u8) -> Direction {
+            [
+                Direction::North,
+                Direction::East,
+                Direction::South,
+                Direction::West,
+            ][byte as usize]
+        }
+    }
+
+    #[test]
+    fn it_compresses_symbols_mapped_to_bytes() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x02] = 0b110;
+        bit_counts[0x02] = 3;
+
+        values[0x03] = 0b111;
+        bit_counts[0x03] = 3;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let src = vec![
+            Direction::North,
+            Direction::North,
+            Direction::West,
+            Direction::East,
+            Direction::South,
+        ];
+
+        let mut output = Vec::new();
+        huffman
+            .compress_mapped(src, |direction| direction as u8, &mut output)
+            .unwrap();
+
+        assert_eq!(output, vec![0b0_0_111_10_1, 0b10_000000]);
+    }
+
+    #[test]
+    fn it_round_trips_symbols_through_the_mapping_and_its_inverse() {
+        let table = table_from_codes(&[(0x00, 0b0, 1), (0x01, 0b10, 2), (0x02, 0b110, 3)]);
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let src = vec![
+            Direction::South,
+            Direction::North,
+            Direction::North,
+            Direction::East,
+            Direction::South,
+        ];
+
+        let mut compressor = Compressor::new(&table);
+        let mut output = Vec::new();
+        compressor
+            .compress_mapped(
+                src.iter().copied(),
+                |direction| direction as u8,
+                &mut output,
+            )
+            .unwrap();
+        compressor.append_terminal_code(&terminal_code).unwrap();
+        compressor.end();
+        compressor.drain_into(&mut output);
+
+        let huffman = Huffman::new(table, Some(terminal_code));
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress_mapped(&output, None, Direction::from_byte, &mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_refuses_to_compress_when_the_table_would_expand_the_input() {
+        let mut values = [0; 256];
+        let bit_counts = [10; 256];
+
+        values[0x41] = 0b1111111111;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let mut output = Vec::new();
+        let result = huffman.compress_unless_expanding(&[0x41, 0x41, 0x42], &mut output);
+
+        assert_eq!(result, Err(CompressError::Expanding));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn it_compresses_when_the_table_would_not_expand_the_input() {
+        let mut values = [0; 256];
+        let mut bit_counts = [10; 256];
+
+        values[0x41] = 0b1;
+        bit_counts[0x41] = 1;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let mut huffman = Huffman::new(table, None);
+
+        let mut output = Vec::new();
+        huffman
+            .compress_unless_expanding(&[0x41, 0x41, 0x41], &mut output)
+            .unwrap();
+
+        assert_eq!(output, vec![0b111_00000]);
+    }
+
+    #[test]
+    fn it_compresses_into_a_slice_that_fits_the_output_exactly() {
+        let mut huffman = Huffman::new(
+            HuffmanTable::from_bytes(b"the quick brown fox jumps over the lazy dog"),
+            None,
+        );
+        let src = b"over the quick brown fox";
+
+        let mut output = Vec::new();
+        huffman.compress(src, &mut output).unwrap();
+
+        let mut out = vec![0; output.len()];
+        let len = huffman.compress_into_slice(src, &mut out).unwrap();
+
+        assert_eq!(len, output.len());
+        assert_eq!(out, output);
+    }
+
+    #[test]
+    fn it_errs_when_compressing_into_a_slice_one_byte_too_small() {
+        let mut huffman = Huffman::new(
+            HuffmanTable::from_bytes(b"the quick brown fox jumps over the lazy dog"),
+            None,
+        );
+        let src = b"the lazy dog";
+
+        let mut output = Vec::new();
+        huffman.compress(src, &mut output).unwrap();
+
+        let mut out = vec![0; output.len() - 1];
+
+        assert_eq!(
+            huffman.compress_into_slice(src, &mut out),
+            Err(CompressError::OutputTooSmall)
+        );
+        assert_eq!(out, output[..output.len() - 1]);
+    }
+
+    #[test]
+    fn it_returns_the_compressed_len_without_compressing_into_an_output() {
+        let huffmans = [
+            Huffman::new(
+                HuffmanTable {
+                    values: [0b1; 256],
+                    bit_counts: [1; 256],
+                },
+                None,
+            ),
+            Huffman::new(
+                HuffmanTable {
+                    values: [0b101; 256],
+                    bit_counts: [3; 256],
+                },
+                Some(TerminalCode {
+                    bit_count: 6,
+                    value: 0b111111,
+                }),
+            ),
+            Huffman::new(
+                HuffmanTable::from_bytes(b"the quick brown fox jumps over the lazy dog"),
+                None,
+            ),
+        ];
+
+        for mut huffman in huffmans {
+            for src in [&b""[..], b"t", b"the lazy dog", b"over the quick brown fox"] {
+                let mut output = Vec::new();
+                huffman.compress(src, &mut output).unwrap();
+
+                assert_eq!(huffman.compressed_len(src).unwrap(), output.len());
+            }
+        }
+    }
+
+    fn table_from_codes(codes: &[(u8, u32, u8)]) -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        for &(byte, value, bit_count) in codes {
+            values[byte as usize] = value;
+            bit_counts[byte as usize] = bit_count;
+        }
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_decompresses_the_output_of_every_compress_case() {
+        let cases = [
+            (vec![(0xE4, 0b1, 1)], None, vec![0xE4]),
+            (vec![(0xFF, 0b1, 1)], None, vec![0xFF, 0xFF, 0xFF]),
+            (vec![(0xDB, 0b11111, 5)], None, vec![0xDB, 0xDB]),
+            (
+                vec![(0xA0, 0b01, 2), (0xCB, 0b10, 2), (0xB3, 0b11, 2)],
+                None,
+                vec![0xA0, 0xCB, 0xB3],
+            ),
+            (
+                vec![(0x92, 0b0, 2), (0x0C, 0b111, 3)],
+                None,
+                vec![0x92, 0x0C],
+            ),
+            (vec![(0x92, 0b1, 8)], None, vec![0x92]),
+            (vec![(0x92, 0b1010, 4)], Some((0b111, 3)), vec![0x92]),
+            (vec![(0x92, 0b10000000, 8)], Some((0b101, 3)), vec![0x92]),
+        ];
+
+        for (codes, terminal_code, src) in cases {
+            let terminal_code =
+                terminal_code.map(|(value, bit_count)| TerminalCode { bit_count, value });
+            let mut huffman = Huffman::new(table_from_codes(&codes), terminal_code);
+
+            let mut output = Vec::new();
+            huffman.compress(&src, &mut output).unwrap();
+
+            let mut decompressed = Vec::new();
+            huffman
+                .decompress(&output, Some(src.len()), &mut decompressed)
+                .unwrap();
+
+            assert_eq!(decompressed, src);
+        }
+    }
+
+    #[test]
+    fn it_decompresses_up_to_the_terminal_code_without_an_expected_len() {
+        let table = table_from_codes(&[(0x00, 0b0, 2), (0x01, 0b01, 2), (0x02, 0b10, 2)]);
+        let terminal_code = TerminalCode {
+            bit_count: 2,
+            value: 0b11,
+        };
+        let mut huffman = Huffman::new(table, Some(terminal_code));
+
+        // the padding after the terminal code would otherwise decode as 0x00
+        let src = [0x01, 0x02, 0x00];
+        let mut output = Vec::new();
+        huffman.compress(&src, &mut output).unwrap();
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress(&output, None, &mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_errs_when_decompressing_fewer_bytes_than_the_expected_len() {
+        let mut huffman = Huffman::new(
+            HuffmanTable::from_bytes(b"the quick brown fox jumps over the lazy dog"),
+            None,
+        );
+        let src = b"over the lazy dog";
+
+        let mut compressed = Vec::new();
+        huffman.compress(src, &mut compressed).unwrap();
+        compressed.pop();
+
+        let mut decompressed = Vec::new();
+        let result = huffman.decompress(&compressed, Some(src.len()), &mut decompressed);
+
+        assert!(matches!(result, Err(DecodeError::UnexpectedEnd { .. })));
+        assert!(decompressed.len() < src.len());
+    }
+
+    #[test]
+    fn it_errs_when_decompressing_bits_that_are_not_a_code() {
+        let huffman = Huffman::new(table_from_codes(&[(0x00, 0b10, 2)]), None);
+
+        let mut decompressed = Vec::new();
+        let result = huffman.decompress(&[0b10_11_0000], None, &mut decompressed);
+
+        assert_eq!(result, Err(DecodeError::InvalidCode { bit_offset: 2 }));
+        assert_eq!(decompressed, vec![0x00]);
+    }
+
+    #[test]
+    fn it_round_trips_input_of_a_single_distinct_byte() {
+        let src = vec![0x61; 1000];
+        let table = HuffmanTable::from_bytes(&src);
+
+        assert_eq!(table.get_compressed_value(0x61), 0b0);
+        assert_eq!(table.get_compressed_value_bit_count(0x61), 1);
+
+        // the one bit code leaves the other branch free for a terminal code
+        let terminal_code = TerminalCode {
+            bit_count: 1,
+            value: 0b1,
+        };
+        let mut huffman = Huffman::try_new(table, Some(terminal_code)).unwrap();
+
+        let mut output = Vec::new();
+        huffman.compress(&src, &mut output).unwrap();
+        assert_eq!(output.len(), 126);
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress(&output, None, &mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, src);
+
+        huffman.terminal_code = None;
+        let mut output = Vec::new();
+        huffman.compress(&src, &mut output).unwrap();
+
+        let mut decompressed = Vec::new();
+        huffman
+            .decompress(&output, Some(src.len()), &mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, src);
+    }
 }
@@ -0,0 +1,257 @@
+use crate::compressor::{CompressError, WideCompressor};
+use crate::decode_tree::DecodeTreeError;
+use crate::decompressor::DecodeError;
+use crate::wide_decompressor::{WideDecodeTree, WideDecompressor};
+use crate::wide_table::WideHuffmanTable;
+
+pub const MIN_MATCH_LEN: u16 = 3;
+pub const MAX_MATCH_LEN: u16 = 258;
+pub const MAX_DISTANCE: u16 = 32768;
+
+// the extended alphabet: literal bytes are symbols 0 to 255, then one symbol ending the
+// tokens, one for each match length and one for each distance
+const END_OF_BLOCK: u16 = 256;
+const FIRST_LENGTH_SYMBOL: u16 = END_OF_BLOCK + 1;
+const FIRST_DISTANCE_SYMBOL: u16 = FIRST_LENGTH_SYMBOL + MAX_MATCH_LEN - MIN_MATCH_LEN + 1;
+const SYMBOL_COUNT: usize = FIRST_DISTANCE_SYMBOL as usize + MAX_DISTANCE as usize;
+
+// the output of an LZ77 match finder: either a byte to copy through, or a copy of length bytes
+// starting distance bytes back in the output
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LzToken {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+// a wide table over the literals, lengths and distances of LZ tokens, so the crate can be the
+// entropy stage after a match finder. a match is written as its length symbol followed by its
+// distance symbol, and the tokens end with an end of block symbol
+pub struct LzTable {
+    table: WideHuffmanTable,
+    tree: WideDecodeTree,
+}
+
+impl LzTable {
+    // how often each symbol of the extended alphabet occurs in tokens, including the end of
+    // block symbol once. panics on a match length or distance out of range
+    pub fn symbol_frequencies(tokens: &[LzToken]) -> Vec<u64> {
+        let mut counts = vec![0; SYMBOL_COUNT];
+
+        for &token in tokens {
+            for symbol in symbols(token).into_iter().flatten() {
+                counts[symbol as usize] += 1;
+            }
+        }
+        counts[END_OF_BLOCK as usize] += 1;
+
+        counts
+    }
+
+    pub fn from_tokens(tokens: &[LzToken]) -> LzTable {
+        let table = WideHuffmanTable::from_frequencies(&LzTable::symbol_frequencies(tokens));
+
+        LzTable::new(table).expect("built codes are prefix free")
+    }
+
+    // errs if the codes in the table aren't prefix free
+    pub fn new(table: WideHuffmanTable) -> Result<LzTable, DecodeTreeError> {
+        let tree = WideDecodeTree::new(&table)?;
+
+        Ok(LzTable { table, tree })
+    }
+
+    pub fn table(&self) -> &WideHuffmanTable {
+        &self.table
+    }
+
+    pub fn compress(&self, tokens: &[LzToken]) -> Result<Vec<u8>, CompressError> {
+        let mut compressor = WideCompressor::new(&self.table);
+        let mut output = Vec::new();
+
+        for &token in tokens {
+            for symbol in symbols(token).into_iter().flatten() {
+                compressor.compress_symbol(symbol)?;
+            }
+            compressor.drain_into(&mut output);
+        }
+
+        compressor.compress_symbol(END_OF_BLOCK)?;
+        compressor.end();
+        compressor.drain_into(&mut output);
+
+        Ok(output)
+    }
+
+    // decodes tokens up to the end of block symbol. a distance symbol that doesn't follow a
+    // length symbol, or a length symbol followed by anything else, is an InvalidCode error
+    pub fn decompress(&self, src: &[u8]) -> Result<Vec<LzToken>, DecodeError> {
+        let mut decompressor = WideDecompressor::new(&self.tree, src);
+        let mut tokens = Vec::new();
+
+        loop {
+            let bit_offset = decompressor.bit_offset();
+            let invalid = DecodeError::InvalidCode { bit_offset };
+
+            let Some(symbol) = decompressor.next() else {
+                return Err(match decompressor.error() {
+                    Some(err) => err.clone(),
+                    None => DecodeError::UnexpectedEnd { bit_offset },
+                });
+            };
+
+            let token = match symbol {
+                0..END_OF_BLOCK => LzToken::Literal(symbol as u8),
+                END_OF_BLOCK => return Ok(tokens),
+                FIRST_LENGTH_SYMBOL..FIRST_DISTANCE_SYMBOL => {
+                    let distance = match decompressor.next() {
+                        Some(distance) if distance >= FIRST_DISTANCE_SYMBOL => distance,
+                        _ => return Err(invalid),
+                    };
+
+                    LzToken::Match {
+                        length: symbol - FIRST_LENGTH_SYMBOL + MIN_MATCH_LEN,
+                        distance: distance - FIRST_DISTANCE_SYMBOL + 1,
+                    }
+                }
+                _ => return Err(invalid),
+            };
+
+            tokens.push(token);
+        }
+    }
+}
+
+fn symbols(token: LzToken) -> [Option<u16>; 2] {
+    match token {
+        LzToken::Literal(byte) => [Some(byte as u16), None],
+        LzToken::Match { length, distance } => {
+            assert!(
+                (MIN_MATCH_LEN..=MAX_MATCH_LEN).contains(&length),
+                "match length must be between {} and {}",
+                MIN_MATCH_LEN,
+                MAX_MATCH_LEN
+            );
+            assert!(
+                (1..=MAX_DISTANCE).contains(&distance),
+                "match distance must be between 1 and {}",
+                MAX_DISTANCE
+            );
+
+            [
+                Some(FIRST_LENGTH_SYMBOL + length - MIN_MATCH_LEN),
+                Some(FIRST_DISTANCE_SYMBOL + distance - 1),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_tokens() -> Vec<LzToken> {
+        let mut tokens: Vec<LzToken> = b"abracad".iter().map(|&b| LzToken::Literal(b)).collect();
+        tokens.push(LzToken::Match {
+            length: 4,
+            distance: 7,
+        });
+        for _ in 0..20 {
+            tokens.push(LzToken::Literal(b'!'));
+            tokens.push(LzToken::Match {
+                length: MAX_MATCH_LEN,
+                distance: 12,
+            });
+        }
+        tokens.push(LzToken::Match {
+            length: MIN_MATCH_LEN,
+            distance: MAX_DISTANCE,
+        });
+        tokens.push(LzToken::Literal(0xFF));
+        tokens
+    }
+
+    #[test]
+    fn it_round_trips_a_token_stream_of_literals_and_matches() {
+        let tokens = example_tokens();
+        let table = LzTable::from_tokens(&tokens);
+
+        let compressed = table.compress(&tokens).unwrap();
+
+        assert_eq!(table.decompress(&compressed).unwrap(), tokens);
+        assert!(compressed.len() < tokens.len() * 2);
+    }
+
+    #[test]
+    fn it_round_trips_no_tokens() {
+        let table = LzTable::from_tokens(&[]);
+
+        let compressed = table.compress(&[]).unwrap();
+
+        assert_eq!(table.decompress(&compressed).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn it_counts_a_match_as_its_length_and_distance_symbols() {
+        let counts = LzTable::symbol_frequencies(&example_tokens());
+
+        assert_eq!(counts[b'!' as usize], 20);
+        assert_eq!(counts[END_OF_BLOCK as usize], 1);
+        assert_eq!(
+            counts[(FIRST_LENGTH_SYMBOL + MAX_MATCH_LEN - MIN_MATCH_LEN) as usize],
+            20
+        );
+        assert_eq!(counts[(FIRST_DISTANCE_SYMBOL + 11) as usize], 20);
+        assert_eq!(counts[SYMBOL_COUNT - 1], 1);
+    }
+
+    #[test]
+    fn it_errs_on_a_token_without_a_code() {
+        let table = LzTable::from_tokens(&example_tokens());
+
+        assert_eq!(
+            table.compress(&[LzToken::Literal(b'z')]),
+            Err(CompressError::NoCodeForSymbol(b'z' as u16))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_distance_without_a_length() {
+        let tokens = example_tokens();
+        let table = LzTable::from_tokens(&tokens);
+
+        // a distance symbol on its own, then the end of block
+        let mut compressor = WideCompressor::new(table.table());
+        compressor
+            .compress_symbol(FIRST_DISTANCE_SYMBOL + 11)
+            .unwrap();
+        compressor.compress_symbol(END_OF_BLOCK).unwrap();
+        compressor.end();
+        let compressed: Vec<u8> = compressor.collect();
+
+        assert_eq!(
+            table.decompress(&compressed),
+            Err(DecodeError::InvalidCode { bit_offset: 0 })
+        );
+    }
+
+    #[test]
+    fn it_errs_on_tokens_missing_the_end_of_block() {
+        let tokens = example_tokens();
+        let table = LzTable::from_tokens(&tokens);
+        let compressed = table.compress(&tokens).unwrap();
+
+        assert!(matches!(
+            table.decompress(&compressed[..compressed.len() / 2]),
+            Err(DecodeError::UnexpectedEnd { .. })
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "match length must be between 3 and 258")]
+    fn it_panics_on_a_match_too_long() {
+        LzTable::symbol_frequencies(&[LzToken::Match {
+            length: 259,
+            distance: 1,
+        }]);
+    }
+}
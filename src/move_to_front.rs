@@ -0,0 +1,58 @@
+// each byte is replaced by its position in a list of recently seen bytes, and then moved to the
+// front of that list. runs of the same byte, or bytes that recur close together, become runs of
+// small positions which can then be given short codes
+pub fn move_to_front_encode(src: &[u8]) -> Vec<u8> {
+    let mut recent: Vec<u8> = (0..=255).collect();
+
+    src.iter()
+        .map(|&byte| {
+            let position = recent
+                .iter()
+                .position(|&recent_byte| recent_byte == byte)
+                .unwrap();
+            recent.remove(position);
+            recent.insert(0, byte);
+            position as u8
+        })
+        .collect()
+}
+
+pub fn move_to_front_decode(src: &[u8]) -> Vec<u8> {
+    let mut recent: Vec<u8> = (0..=255).collect();
+
+    src.iter()
+        .map(|&position| {
+            let byte = recent.remove(position as usize);
+            recent.insert(0, byte);
+            byte
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encodes_repeated_bytes_as_zeroes() {
+        assert_eq!(
+            move_to_front_encode(&[0x41, 0x41, 0x41]),
+            vec![0x41, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn it_encodes_recently_seen_bytes_as_small_positions() {
+        assert_eq!(
+            move_to_front_encode(&[0x02, 0x01, 0x02, 0x01]),
+            vec![0x02, 0x02, 0x01, 0x01]
+        );
+    }
+
+    #[test]
+    fn it_decodes_back_to_the_original_bytes() {
+        let src = vec![0x00, 0xFF, 0xFF, 0x10, 0x00, 0x10, 0xFF, 0x7F];
+
+        assert_eq!(move_to_front_decode(&move_to_front_encode(&src)), src);
+    }
+}
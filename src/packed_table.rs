@@ -0,0 +1,94 @@
+use crate::huffman_table::HuffmanTable;
+
+const MAX_PACKED_BIT_COUNT: u8 = 12;
+
+// each entry holds the bit count in its top 4 bits and the compressed value in its low 12 bits
+pub struct PackedTable {
+    entries: [u16; 256],
+}
+
+impl PackedTable {
+    pub fn get_compressed_value(&self, uncompressed_byte: u8) -> u32 {
+        (self.entries[uncompressed_byte as usize] & 0x0FFF) as u32
+    }
+
+    pub fn get_compressed_value_bit_count(&self, uncompressed_byte: u8) -> u8 {
+        (self.entries[uncompressed_byte as usize] >> 12) as u8
+    }
+
+    pub fn unpack(&self) -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        for byte in 0..=255 {
+            values[byte as usize] = self.get_compressed_value(byte);
+            bit_counts[byte as usize] = self.get_compressed_value_bit_count(byte);
+        }
+
+        HuffmanTable { values, bit_counts }
+    }
+}
+
+impl HuffmanTable {
+    pub fn try_pack(&self) -> Option<PackedTable> {
+        let mut entries = [0; 256];
+
+        for (byte, entry) in entries.iter_mut().enumerate() {
+            let value = self.values[byte];
+            let bit_count = self.bit_counts[byte];
+
+            if bit_count > MAX_PACKED_BIT_COUNT || value >> MAX_PACKED_BIT_COUNT != 0 {
+                return None;
+            }
+
+            *entry = ((bit_count as u16) << 12) | value as u16;
+        }
+
+        Some(PackedTable { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_packs_and_unpacks_a_table_with_codes_of_up_to_12_bits() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x33] = 0xFFF;
+        bit_counts[0x33] = 12;
+
+        values[0xFF] = 0b1;
+        bit_counts[0xFF] = 12;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        let packed_table = huffman_table.try_pack().unwrap();
+
+        assert_eq!(packed_table.get_compressed_value(0x33), 0xFFF);
+        assert_eq!(packed_table.get_compressed_value_bit_count(0x33), 12);
+
+        let unpacked_table = packed_table.unpack();
+
+        assert_eq!(unpacked_table.values, huffman_table.values);
+        assert_eq!(unpacked_table.bit_counts, huffman_table.bit_counts);
+    }
+
+    #[test]
+    fn it_does_not_pack_a_table_with_a_13_bit_code() {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+
+        values[0x33] = 0x1FFF;
+        bit_counts[0x33] = 13;
+
+        let huffman_table = HuffmanTable { values, bit_counts };
+
+        assert!(huffman_table.try_pack().is_none());
+    }
+}
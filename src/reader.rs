@@ -0,0 +1,222 @@
+use std::io::{self, Read};
+
+use crate::decode_tree::{DecodeTree, DecodedSymbol};
+use crate::decompressor::DecodeError;
+
+// decompresses a stream of compressed bytes as it's read. compressed bytes are pulled from the
+// inner reader one at a time, so unbuffered sources should be wrapped in a BufReader
+pub struct HuffmanReader<'a, R: Read> {
+    inner: R,
+    tree: &'a DecodeTree,
+    byte: u8,
+    // bits of byte not decoded yet
+    remaining_bit_count: u8,
+    // how far through the tree the current code has got, which can carry over between reads
+    node: usize,
+    code_bit_count: u64,
+    bit_offset: u64,
+    finished: bool,
+    // held back until the bytes decoded before it have been returned
+    error: Option<DecodeError>,
+}
+
+impl<'a, R: Read> HuffmanReader<'a, R> {
+    pub fn new(inner: R, tree: &'a DecodeTree) -> HuffmanReader<'a, R> {
+        HuffmanReader {
+            inner,
+            tree,
+            byte: 0,
+            remaining_bit_count: 0,
+            node: tree.root(),
+            code_bit_count: 0,
+            bit_offset: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    fn read_compressed_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+
+        loop {
+            match self.inner.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Compressor::end pads the last byte with zeroes, which can walk off the tree when no code
+    // is all zeroes. that's only padding if the code started in the last byte and every bit from
+    // there on is zero
+    fn is_padding(&mut self) -> io::Result<bool> {
+        let padding_bit_count = self.code_bit_count + self.remaining_bit_count as u64;
+
+        if padding_bit_count >= 8 {
+            return Ok(false);
+        }
+
+        let mask = (1 << padding_bit_count) - 1;
+
+        Ok(self.byte & mask == 0 && self.read_compressed_byte()?.is_none())
+    }
+}
+
+impl<R: Read> Read for HuffmanReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+
+        while len < buf.len() && !self.finished {
+            if self.remaining_bit_count == 0 {
+                match self.read_compressed_byte()? {
+                    Some(byte) => {
+                        self.byte = byte;
+                        self.remaining_bit_count = 8;
+                    }
+                    None => {
+                        // an incomplete code at the end of the stream is padding
+                        self.finished = true;
+                        break;
+                    }
+                }
+            }
+
+            self.remaining_bit_count -= 1;
+            self.code_bit_count += 1;
+            self.bit_offset += 1;
+
+            let bit = (self.byte >> self.remaining_bit_count) & 1;
+
+            self.node = match self.tree.child(self.node, bit) {
+                Some(child) => child,
+                None => {
+                    self.finished = true;
+
+                    if !self.is_padding()? {
+                        self.error = Some(DecodeError::InvalidCode {
+                            bit_offset: self.bit_offset - self.code_bit_count,
+                        });
+                    }
+
+                    break;
+                }
+            };
+
+            match self.tree.symbol(self.node) {
+                Some(DecodedSymbol::Byte(byte)) => {
+                    buf[len] = byte;
+                    len += 1;
+
+                    self.node = self.tree.root();
+                    self.code_bit_count = 0;
+                }
+                Some(DecodedSymbol::Terminal) => self.finished = true,
+                None => {}
+            }
+        }
+
+        if len == 0 {
+            if let Some(err) = self.error.take() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
+        }
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+    use crate::huffman_table::HuffmanTable;
+    use crate::terminal_code::TerminalCode;
+    use crate::Huffman;
+
+    fn table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b101;
+        bit_counts[0xCB] = 3;
+
+        values[0xB3] = 0b1101_0110_0101;
+        bit_counts[0xB3] = 12;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    fn compress(terminal_code: Option<TerminalCode>, src: &[u8]) -> Vec<u8> {
+        let mut huffman = Huffman::new(table(), terminal_code);
+        let mut output = Vec::new();
+        huffman.compress(src, &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn it_decompresses_a_stream_back_to_the_original() {
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let src = [0xA0, 0xCB, 0xB3, 0xB3, 0xA0, 0xCB, 0xA0];
+        let tree = DecodeTree::new(&table(), Some(&terminal_code)).unwrap();
+        let compressed = compress(Some(terminal_code), &src);
+        let mut reader = HuffmanReader::new(compressed.as_slice(), &tree);
+
+        let mut output = Vec::new();
+        io::copy(&mut reader, &mut output).unwrap();
+
+        assert_eq!(output, src);
+    }
+
+    #[test]
+    fn it_ignores_the_padding_at_the_end_of_a_stream_without_a_terminal_code() {
+        let src = [0xCB, 0xB3, 0xA0];
+        let compressed = compress(None, &src);
+
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let mut reader = HuffmanReader::new(compressed.as_slice(), &tree);
+
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).unwrap();
+
+        assert_eq!(output, src);
+    }
+
+    #[test]
+    fn it_carries_a_partly_decoded_code_over_between_reads() {
+        let src = [0xB3, 0xA0, 0xB3, 0xCB];
+        let compressed = compress(None, &src);
+
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let mut reader = HuffmanReader::new(compressed.as_slice(), &tree);
+
+        let mut output = Vec::new();
+        let mut buf = [0; 1];
+        while reader.read(&mut buf).unwrap() > 0 {
+            output.push(buf[0]);
+        }
+
+        assert_eq!(output, src);
+    }
+
+    #[test]
+    fn it_errs_on_bits_that_do_not_match_any_code() {
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let compressed: &[u8] = &[0b01_00_0000, 0xFF];
+        let mut reader = HuffmanReader::new(compressed, &tree);
+
+        let mut output = Vec::new();
+        let err = reader.read_to_end(&mut output).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(output, vec![0xA0]);
+    }
+}
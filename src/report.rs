@@ -0,0 +1,179 @@
+use crate::frequencies::count_frequencies;
+use crate::huffman_table::HuffmanTable;
+
+// how many symbols top_symbols keeps
+pub const TOP_SYMBOL_COUNT: usize = 8;
+
+// a byte's share of a compress: how often it appeared and the code bits it was written with
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SymbolContribution {
+    pub byte: u8,
+    pub count: u64,
+    pub bits: u64,
+}
+
+// a machine-readable breakdown of a single compress, e.g. for dashboards. unlike
+// CompressionStats it says where the output bits went: length_histogram pairs each code length
+// used with how many input bytes were written with it, shortest first, and top_symbols holds the
+// TOP_SYMBOL_COUNT bytes that took the most bits, most first and by byte value on a tie
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompressionReport {
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    pub ratio: f64,
+    pub length_histogram: Vec<(u8, u64)>,
+    pub top_symbols: Vec<SymbolContribution>,
+}
+
+impl CompressionReport {
+    pub(crate) fn new(table: &HuffmanTable, src: &[u8], output_bytes: usize) -> CompressionReport {
+        let counts = count_frequencies(src);
+
+        let mut histogram = [0u64; 33];
+        let mut contributions = Vec::new();
+        for byte in 0..=255u8 {
+            let count = counts[byte as usize];
+            if count == 0 {
+                continue;
+            }
+
+            let bit_count = table.get_compressed_value_bit_count(byte);
+            histogram[bit_count as usize] += count;
+            contributions.push(SymbolContribution {
+                byte,
+                count,
+                bits: count * bit_count as u64,
+            });
+        }
+
+        contributions.sort_by(|a, b| b.bits.cmp(&a.bits).then(a.byte.cmp(&b.byte)));
+        contributions.truncate(TOP_SYMBOL_COUNT);
+
+        let ratio = match src.len() {
+            0 => 0.0,
+            input_bytes => output_bytes as f64 / input_bytes as f64,
+        };
+
+        CompressionReport {
+            input_bytes: src.len(),
+            output_bytes,
+            ratio,
+            length_histogram: (0..=32u8)
+                .zip(histogram)
+                .filter(|&(_, count)| count > 0)
+                .collect(),
+            top_symbols: contributions,
+        }
+    }
+
+    // an object with a field per entry, the histogram as {"bit_count","count"} objects and the
+    // symbols as {"byte","count","bits"} ones. ratio is always finite so it's a plain json number
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        let length_histogram: Vec<String> = self
+            .length_histogram
+            .iter()
+            .map(|(bit_count, count)| {
+                format!("{{\"bit_count\":{},\"count\":{}}}", bit_count, count)
+            })
+            .collect();
+
+        let top_symbols: Vec<String> = self
+            .top_symbols
+            .iter()
+            .map(|symbol| {
+                format!(
+                    "{{\"byte\":{},\"count\":{},\"bits\":{}}}",
+                    symbol.byte, symbol.count, symbol.bits
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"input_bytes\":{},\"output_bytes\":{},\"ratio\":{},\"length_histogram\":[{}],\"top_symbols\":[{}]}}",
+            self.input_bytes,
+            self.output_bytes,
+            self.ratio,
+            length_histogram.join(","),
+            top_symbols.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[b'a' as usize] = 0b0;
+        bit_counts[b'a' as usize] = 1;
+
+        values[b'b' as usize] = 0b10;
+        bit_counts[b'b' as usize] = 2;
+
+        values[b'c' as usize] = 0b110;
+        bit_counts[b'c' as usize] = 3;
+
+        values[b'd' as usize] = 0b111;
+        bit_counts[b'd' as usize] = 3;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_counts_the_bytes_written_with_each_code_length() {
+        let report = CompressionReport::new(&table(), b"aaaabbcd", 2);
+
+        assert_eq!(report.input_bytes, 8);
+        assert_eq!(report.output_bytes, 2);
+        assert_eq!(report.ratio, 0.25);
+        assert_eq!(report.length_histogram, vec![(1, 4), (2, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn it_orders_the_top_symbols_by_the_bits_they_took() {
+        let report = CompressionReport::new(&table(), b"aaaabbcd", 2);
+
+        let top_symbols: Vec<(u8, u64, u64)> = report
+            .top_symbols
+            .iter()
+            .map(|symbol| (symbol.byte, symbol.count, symbol.bits))
+            .collect();
+
+        // a and b both took 4 bits, so a comes first by byte value
+        assert_eq!(
+            top_symbols,
+            vec![(b'a', 4, 4), (b'b', 2, 4), (b'c', 1, 3), (b'd', 1, 3)]
+        );
+    }
+
+    #[test]
+    fn it_keeps_only_the_top_symbol_count_contributors() {
+        let table = HuffmanTable::from_bytes(&(0..=255).collect::<Vec<u8>>());
+        let src: Vec<u8> = (0..=255).collect();
+
+        let report = CompressionReport::new(&table, &src, 256);
+
+        assert_eq!(report.top_symbols.len(), TOP_SYMBOL_COUNT);
+        assert_eq!(report.length_histogram, vec![(8, 256)]);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn it_writes_every_field_as_json() {
+        let report = CompressionReport::new(&table(), b"aaaabbcd", 2);
+
+        assert_eq!(
+            report.to_json(),
+            concat!(
+                r#"{"input_bytes":8,"output_bytes":2,"ratio":0.25,"#,
+                r#""length_histogram":[{"bit_count":1,"count":4},{"bit_count":2,"count":2},{"bit_count":3,"count":2}],"#,
+                r#""top_symbols":[{"byte":97,"count":4,"bits":4},{"byte":98,"count":2,"bits":4},"#,
+                r#"{"byte":99,"count":1,"bits":3},{"byte":100,"count":1,"bits":3}]}"#
+            )
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::checksum::Crc32;
+use crate::huffman_table::HuffmanTable;
+
+impl HuffmanTable {
+    // writes the bit count of every byte, each followed by its value in as few big-endian bytes
+    // as the bit count needs. bytes with a bit count of 0 have no value written
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for (&value, &bit_count) in self.values.iter().zip(self.bit_counts.iter()) {
+            bytes.push(bit_count);
+
+            let value_len = value_len(bit_count);
+            bytes.extend(&value.to_be_bytes()[4 - value_len..]);
+        }
+
+        bytes
+    }
+
+    // reads a table written by serialize from the start of bytes, returning it along with the
+    // number of bytes it took up so whatever follows can be read next
+    pub fn deserialize(bytes: &[u8]) -> Result<(HuffmanTable, usize), TableError> {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+        let mut offset = 0;
+
+        for byte in 0..=255 {
+            let bit_count = *bytes.get(offset).ok_or(TableError::Truncated)?;
+            offset += 1;
+
+            if bit_count > 32 {
+                return Err(TableError::InvalidBitCount(byte));
+            }
+
+            let value_len = value_len(bit_count);
+            let value_bytes = bytes
+                .get(offset..offset + value_len)
+                .ok_or(TableError::Truncated)?;
+            offset += value_len;
+
+            let value = value_bytes
+                .iter()
+                .fold(0, |value, &value_byte| value << 8 | value_byte as u32);
+
+            if bit_count < 32 && value >> bit_count != 0 {
+                return Err(TableError::ValueTooLong(byte));
+            }
+
+            values[byte as usize] = value;
+            bit_counts[byte as usize] = bit_count;
+        }
+
+        Ok((HuffmanTable { values, bit_counts }, offset))
+    }
+
+    // serialize followed by a big-endian CRC32 of the serialized bytes, for tables read back
+    // from somewhere that could corrupt them
+    pub fn serialize_checked(&self) -> Vec<u8> {
+        let mut bytes = self.serialize();
+        let checksum = crc32(&bytes);
+        bytes.extend(checksum.to_be_bytes());
+
+        bytes
+    }
+
+    // reads a table written by serialize_checked, erring with ChecksumMismatch if the bytes it
+    // was read from don't match the checksum after them
+    pub fn deserialize_checked(bytes: &[u8]) -> Result<(HuffmanTable, usize), TableError> {
+        let (table, table_len) = HuffmanTable::deserialize(bytes)?;
+
+        let checksum_bytes = bytes
+            .get(table_len..table_len + 4)
+            .ok_or(TableError::Truncated)?;
+        let checksum = u32::from_be_bytes(checksum_bytes.try_into().unwrap());
+
+        if checksum != crc32(&bytes[..table_len]) {
+            return Err(TableError::ChecksumMismatch);
+        }
+
+        Ok((table, table_len + 4))
+    }
+}
+
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    for &byte in bytes {
+        crc.write_byte(byte);
+    }
+    crc.finish()
+}
+
+fn value_len(bit_count: u8) -> usize {
+    bit_count.div_ceil(8) as usize
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TableError {
+    Truncated,
+    // the bit count for the byte is more than 32
+    InvalidBitCount(u8),
+    // the value for the byte has more bits set than its bit count allows
+    ValueTooLong(u8),
+    // the table read by deserialize_checked doesn't match the checksum stored after it
+    ChecksumMismatch,
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TableError::Truncated => write!(f, "serialized table is truncated"),
+            TableError::InvalidBitCount(byte) => {
+                write!(f, "bit count for byte 0x{:02X} is more than 32", byte)
+            }
+            TableError::ValueTooLong(byte) => write!(
+                f,
+                "value for byte 0x{:02X} is longer than its bit count",
+                byte
+            ),
+            TableError::ChecksumMismatch => {
+                write!(f, "serialized table does not match its checksum")
+            }
+        }
+    }
+}
+
+impl Error for TableError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x7F] = 0b1101_0110_0101;
+        bit_counts[0x7F] = 12;
+
+        values[0xFF] = u32::MAX;
+        bit_counts[0xFF] = 32;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_round_trips_a_table() {
+        let table = example_table();
+
+        let (deserialized, len) = HuffmanTable::deserialize(&table.serialize()).unwrap();
+
+        assert_eq!(deserialized.values, table.values);
+        assert_eq!(deserialized.bit_counts, table.bit_counts);
+        assert_eq!(len, table.serialize().len());
+    }
+
+    #[test]
+    fn it_round_trips_a_checked_table() {
+        let table = example_table();
+        let bytes = table.serialize_checked();
+
+        let (deserialized, len) = HuffmanTable::deserialize_checked(&bytes).unwrap();
+
+        assert_eq!(deserialized.values, table.values);
+        assert_eq!(deserialized.bit_counts, table.bit_counts);
+        assert_eq!(len, table.serialize().len() + 4);
+        assert_eq!(len, bytes.len());
+    }
+
+    #[test]
+    fn it_errs_on_a_corrupted_checked_table() {
+        let bytes = example_table().serialize_checked();
+
+        // a value, a bit count that still parses, and the checksum itself
+        for offset in [1, 2, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[offset] ^= 0x01;
+
+            assert_eq!(
+                HuffmanTable::deserialize_checked(&corrupted).err(),
+                Some(TableError::ChecksumMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn it_errs_on_a_checked_table_missing_its_checksum() {
+        let bytes = example_table().serialize_checked();
+
+        assert_eq!(
+            HuffmanTable::deserialize_checked(&bytes[..bytes.len() - 2]).err(),
+            Some(TableError::Truncated)
+        );
+    }
+
+    #[test]
+    fn it_round_trips_a_table_built_from_frequencies() {
+        let mut counts = [0; 256];
+        for (byte, count) in counts.iter_mut().enumerate() {
+            *count = (byte as u64 * 31) % 17;
+        }
+        let table = HuffmanTable::from_frequencies(&counts);
+
+        let (deserialized, _) = HuffmanTable::deserialize(&table.serialize()).unwrap();
+
+        assert_eq!(deserialized.values, table.values);
+        assert_eq!(deserialized.bit_counts, table.bit_counts);
+    }
+
+    #[test]
+    fn it_writes_only_as_many_value_bytes_as_each_bit_count_needs() {
+        // 256 bit counts, plus 1 + 1 + 2 + 4 value bytes
+        assert_eq!(example_table().serialize().len(), 256 + 8);
+    }
+
+    #[test]
+    fn it_returns_the_bytes_consumed_so_a_payload_can_follow() {
+        let mut bytes = example_table().serialize();
+        let table_len = bytes.len();
+        bytes.extend([0xAB, 0xCD]);
+
+        let (_, len) = HuffmanTable::deserialize(&bytes).unwrap();
+
+        assert_eq!(len, table_len);
+        assert_eq!(&bytes[len..], &[0xAB, 0xCD]);
+    }
+
+    #[test]
+    fn it_errs_on_a_truncated_table() {
+        let bytes = example_table().serialize();
+
+        assert_eq!(
+            HuffmanTable::deserialize(&bytes[..bytes.len() - 1]).err(),
+            Some(TableError::Truncated)
+        );
+        assert_eq!(
+            HuffmanTable::deserialize(&bytes[..100]).err(),
+            Some(TableError::Truncated)
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_bit_count_over_32() {
+        let mut bytes = vec![0; 256];
+        bytes[0x05] = 33;
+
+        assert_eq!(
+            HuffmanTable::deserialize(&bytes).err(),
+            Some(TableError::InvalidBitCount(0x05))
+        );
+    }
+
+    #[test]
+    fn it_errs_on_a_value_longer_than_its_bit_count() {
+        let mut bytes = vec![0; 257];
+        bytes[0x00] = 3;
+        bytes[0x01] = 0b1000;
+
+        assert_eq!(
+            HuffmanTable::deserialize(&bytes).err(),
+            Some(TableError::ValueTooLong(0x00))
+        );
+    }
+}
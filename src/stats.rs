@@ -0,0 +1,30 @@
+// metrics for a single compress, e.g. for logging. ratio is output_bytes / input_bytes, so
+// below 1 means the output is smaller. avg_code_bits is the bits written per input byte, not
+// counting the terminal code or padding. both are 0 for empty input
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CompressionStats {
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    pub ratio: f64,
+    pub avg_code_bits: f64,
+}
+
+impl CompressionStats {
+    pub(crate) fn new(input_bytes: usize, output_bytes: usize, code_bits: u64) -> CompressionStats {
+        let (ratio, avg_code_bits) = if input_bytes == 0 {
+            (0.0, 0.0)
+        } else {
+            (
+                output_bytes as f64 / input_bytes as f64,
+                code_bits as f64 / input_bytes as f64,
+            )
+        };
+
+        CompressionStats {
+            input_bytes,
+            output_bytes,
+            ratio,
+            avg_code_bits,
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use crate::decode_tree::{DecodeTree, DecodedSymbol};
+use crate::decompressor::DecodeError;
+
+// decompresses compressed bytes pushed in as they arrive, e.g. from a socket. a code split
+// across two calls to feed is carried over, so the output doesn't depend on how the input is
+// chunked. decoding stops at the terminal code or the first bit sequence that isn't a code
+pub struct StreamingDecoder<'a> {
+    tree: &'a DecodeTree,
+    // how far through the tree the current code has got, which can carry over between feeds
+    node: usize,
+    code_value: u64,
+    code_bit_count: u64,
+    bit_offset: u64,
+    // the offset of a code that walked off the tree in the zeroes at the end of the last byte
+    // fed. it's the padding added by Compressor::end unless more bytes follow
+    padding_offset: Option<u64>,
+    finished: bool,
+    error: Option<DecodeError>,
+}
+
+impl<'a> StreamingDecoder<'a> {
+    pub fn new(tree: &'a DecodeTree) -> StreamingDecoder<'a> {
+        StreamingDecoder {
+            tree,
+            node: tree.root(),
+            code_value: 0,
+            code_bit_count: 0,
+            bit_offset: 0,
+            padding_offset: None,
+            finished: false,
+            error: None,
+        }
+    }
+
+    // pushes every byte fully decoded from bytes onto out. once decoding has stopped any more
+    // bytes are ignored
+    pub fn feed(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        for &byte in bytes {
+            if self.finished {
+                return;
+            }
+
+            if let Some(bit_offset) = self.padding_offset.take() {
+                self.fail(bit_offset);
+                return;
+            }
+
+            self.decode_byte(byte, out);
+        }
+    }
+
+    // ends the stream. a code left incomplete is an UnexpectedEnd error unless it's the padding
+    // added by Compressor::end: fewer than eight zero bits, without a terminal code to expect
+    pub fn finish(self) -> Result<(), DecodeError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let code_offset = self.bit_offset - self.code_bit_count;
+
+        if self.tree.has_terminal_code() {
+            if self.finished {
+                return Ok(());
+            }
+
+            return Err(DecodeError::UnexpectedEnd {
+                bit_offset: self.padding_offset.unwrap_or(code_offset),
+            });
+        }
+
+        let is_padding = self.code_bit_count < 8 && self.code_value == 0;
+
+        if self.padding_offset.is_some() || self.node == self.tree.root() || is_padding {
+            Ok(())
+        } else {
+            Err(DecodeError::UnexpectedEnd {
+                bit_offset: code_offset,
+            })
+        }
+    }
+
+    // set once decoding has stopped on a bit sequence that isn't a code in the tree
+    pub fn error(&self) -> Option<&DecodeError> {
+        self.error.as_ref()
+    }
+
+    fn decode_byte(&mut self, byte: u8, out: &mut Vec<u8>) {
+        for remaining_bit_count in (0..8).rev() {
+            self.code_bit_count += 1;
+            self.bit_offset += 1;
+
+            let bit = (byte >> remaining_bit_count) & 1;
+            self.code_value = self.code_value << 1 | bit as u64;
+            let code_offset = self.bit_offset - self.code_bit_count;
+
+            self.node = match self.tree.child(self.node, bit) {
+                Some(child) => child,
+                None => {
+                    // Compressor::end pads the last byte with zeroes, which can walk off the
+                    // tree when no code is all zeroes. that's only padding if the code started
+                    // in this byte and every bit from there on is zero
+                    let padding_bit_count = self.code_bit_count + remaining_bit_count as u64;
+
+                    if padding_bit_count < 8 && byte & ((1 << padding_bit_count) - 1) == 0 {
+                        self.padding_offset = Some(code_offset);
+                    } else {
+                        self.fail(code_offset);
+                    }
+
+                    return;
+                }
+            };
+
+            match self.tree.symbol(self.node) {
+                Some(DecodedSymbol::Byte(byte)) => {
+                    out.push(byte);
+
+                    self.node = self.tree.root();
+                    self.code_value = 0;
+                    self.code_bit_count = 0;
+                }
+                Some(DecodedSymbol::Terminal) => {
+                    self.finished = true;
+                    return;
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn fail(&mut self, bit_offset: u64) {
+        self.error = Some(DecodeError::InvalidCode { bit_offset });
+        self.finished = true;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unusual_byte_groupings)]
+mod tests {
+    use super::*;
+    use crate::huffman_table::HuffmanTable;
+    use crate::terminal_code::TerminalCode;
+    use crate::Huffman;
+
+    fn table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b101;
+        bit_counts[0xCB] = 3;
+
+        values[0xB3] = 0b1101_0110_0101;
+        bit_counts[0xB3] = 12;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    fn terminal_code() -> TerminalCode {
+        TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        }
+    }
+
+    fn compress(terminal_code: Option<TerminalCode>, src: &[u8]) -> Vec<u8> {
+        let mut huffman = Huffman::new(table(), terminal_code);
+        let mut output = Vec::new();
+        huffman.compress(src, &mut output).unwrap();
+        output
+    }
+
+    #[test]
+    fn it_decodes_the_same_one_byte_at_a_time_as_all_at_once() {
+        let src = [0xB3, 0xA0, 0xB3, 0xCB, 0xCB, 0xA0, 0xB3, 0xB3, 0xA0];
+
+        for terminal_code in [None, Some(terminal_code())] {
+            let tree = DecodeTree::new(&table(), terminal_code.as_ref()).unwrap();
+            let compressed = compress(terminal_code, &src);
+
+            let mut decoder = StreamingDecoder::new(&tree);
+            let mut all_at_once = Vec::new();
+            decoder.feed(&compressed, &mut all_at_once);
+            decoder.finish().unwrap();
+
+            let mut decoder = StreamingDecoder::new(&tree);
+            let mut one_at_a_time = Vec::new();
+            for byte in compressed.chunks(1) {
+                decoder.feed(byte, &mut one_at_a_time);
+            }
+            decoder.finish().unwrap();
+
+            assert_eq!(all_at_once, src);
+            assert_eq!(one_at_a_time, all_at_once);
+        }
+    }
+
+    #[test]
+    fn it_emits_a_code_split_across_feeds_once_it_is_complete() {
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let mut decoder = StreamingDecoder::new(&tree);
+        let mut output = Vec::new();
+
+        // 01_110101 then 100101_00 is 0xA0, 0xB3 and padding
+        decoder.feed(&[0b01_110101], &mut output);
+        assert_eq!(output, vec![0xA0]);
+
+        decoder.feed(&[0b100101_00], &mut output);
+        assert_eq!(output, vec![0xA0, 0xB3]);
+
+        decoder.finish().unwrap();
+    }
+
+    #[test]
+    fn it_ignores_bytes_after_the_terminal_code() {
+        let tree = DecodeTree::new(&table(), Some(&terminal_code())).unwrap();
+        let mut compressed = compress(Some(terminal_code()), &[0xCB, 0xA0]);
+        compressed.push(0xFF);
+
+        let mut decoder = StreamingDecoder::new(&tree);
+        let mut output = Vec::new();
+        decoder.feed(&compressed, &mut output);
+
+        assert_eq!(output, vec![0xCB, 0xA0]);
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+
+    #[test]
+    fn it_errs_when_more_bytes_follow_what_looked_like_padding() {
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let mut decoder = StreamingDecoder::new(&tree);
+        let mut output = Vec::new();
+
+        // 101_00000 ends in what could be padding, until another byte arrives
+        decoder.feed(&[0b101_00000], &mut output);
+        assert_eq!(decoder.error(), None);
+
+        decoder.feed(&[0b01_000000], &mut output);
+
+        assert_eq!(output, vec![0xCB]);
+        assert_eq!(
+            decoder.finish(),
+            Err(DecodeError::InvalidCode { bit_offset: 3 })
+        );
+    }
+
+    #[test]
+    fn it_errs_on_bits_that_are_not_a_code() {
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let mut decoder = StreamingDecoder::new(&tree);
+        let mut output = Vec::new();
+
+        decoder.feed(&[0b01_00_1010, 0b1000_0000], &mut output);
+
+        assert_eq!(output, vec![0xA0]);
+        assert_eq!(
+            decoder.error(),
+            Some(&DecodeError::InvalidCode { bit_offset: 2 })
+        );
+    }
+
+    #[test]
+    fn it_errs_when_finished_part_way_through_a_code() {
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let mut decoder = StreamingDecoder::new(&tree);
+        let mut output = Vec::new();
+
+        // 0xA0 then the first six bits of 0xB3's code
+        decoder.feed(&[0b01_110101], &mut output);
+
+        assert_eq!(output, vec![0xA0]);
+        assert_eq!(
+            decoder.finish(),
+            Err(DecodeError::UnexpectedEnd { bit_offset: 2 })
+        );
+    }
+
+    #[test]
+    fn it_drops_padding_that_is_a_prefix_of_a_code() {
+        let tree = DecodeTree::new(&table(), None).unwrap();
+        let mut decoder = StreamingDecoder::new(&tree);
+        let mut output = Vec::new();
+
+        // 101_01_01 then a single zero of padding, which starts 0xA0's code
+        decoder.feed(&[0b101_01_01_0], &mut output);
+
+        assert_eq!(output, vec![0xCB, 0xA0, 0xA0]);
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+
+    #[test]
+    fn it_errs_when_finished_before_the_terminal_code() {
+        let tree = DecodeTree::new(&table(), Some(&terminal_code())).unwrap();
+        let compressed = compress(Some(terminal_code()), &[0xCB, 0xA0, 0xB3]);
+
+        let mut decoder = StreamingDecoder::new(&tree);
+        let mut output = Vec::new();
+        decoder.feed(&compressed[..compressed.len() - 1], &mut output);
+
+        assert!(matches!(
+            decoder.finish(),
+            Err(DecodeError::UnexpectedEnd { .. })
+        ));
+    }
+}
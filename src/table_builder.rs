@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::huffman_table::HuffmanTable;
+
+// builds a table one (byte, value, bit_count) entry at a time instead of from two parallel
+// arrays. the first invalid entry is remembered and returned from build
+pub struct HuffmanTableBuilder {
+    values: [u32; 256],
+    bit_counts: [u8; 256],
+    assigned: [bool; 256],
+    error: Option<TableBuilderError>,
+}
+
+impl HuffmanTableBuilder {
+    pub fn new() -> HuffmanTableBuilder {
+        HuffmanTableBuilder {
+            values: [0; 256],
+            bit_counts: [0; 256],
+            assigned: [false; 256],
+            error: None,
+        }
+    }
+
+    pub fn entry(&mut self, byte: u8, value: u32, bit_count: u8) -> &mut HuffmanTableBuilder {
+        if self.error.is_some() {
+            return self;
+        }
+
+        if self.assigned[byte as usize] {
+            self.error = Some(TableBuilderError::DuplicateByte(byte));
+        } else if bit_count > 32 {
+            self.error = Some(TableBuilderError::InvalidBitCount(byte));
+        } else if bit_count < 32 && value >> bit_count != 0 {
+            self.error = Some(TableBuilderError::ValueTooLong(byte));
+        } else {
+            self.values[byte as usize] = value;
+            self.bit_counts[byte as usize] = bit_count;
+            self.assigned[byte as usize] = true;
+        }
+
+        self
+    }
+
+    pub fn build(&self) -> Result<HuffmanTable, TableBuilderError> {
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+
+        Ok(HuffmanTable {
+            values: self.values,
+            bit_counts: self.bit_counts,
+        })
+    }
+}
+
+impl Default for HuffmanTableBuilder {
+    fn default() -> HuffmanTableBuilder {
+        HuffmanTableBuilder::new()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TableBuilderError {
+    DuplicateByte(u8),
+    // the bit count for the byte is more than 32
+    InvalidBitCount(u8),
+    // the value for the byte has more bits set than its bit count allows
+    ValueTooLong(u8),
+}
+
+impl fmt::Display for TableBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TableBuilderError::DuplicateByte(byte) => {
+                write!(f, "byte 0x{:02X} was given more than one code", byte)
+            }
+            TableBuilderError::InvalidBitCount(byte) => {
+                write!(f, "bit count for byte 0x{:02X} is more than 32", byte)
+            }
+            TableBuilderError::ValueTooLong(byte) => write!(
+                f,
+                "value for byte 0x{:02X} is longer than its bit count",
+                byte
+            ),
+        }
+    }
+}
+
+impl Error for TableBuilderError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_the_same_table_as_constructing_the_arrays() {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b101;
+        bit_counts[0xCB] = 3;
+
+        values[0xFF] = u32::MAX;
+        bit_counts[0xFF] = 32;
+
+        let table = HuffmanTable { values, bit_counts };
+
+        let built = HuffmanTableBuilder::new()
+            .entry(0xA0, 0b01, 2)
+            .entry(0xCB, 0b101, 3)
+            .entry(0xFF, u32::MAX, 32)
+            .build()
+            .unwrap();
+
+        assert_eq!(built.values, table.values);
+        assert_eq!(built.bit_counts, table.bit_counts);
+    }
+
+    #[test]
+    fn it_errs_on_a_value_longer_than_its_bit_count() {
+        let result = HuffmanTableBuilder::new()
+            .entry(0xA0, 0b01, 2)
+            .entry(0xCB, 0b111, 2)
+            .build();
+
+        assert_eq!(result.err(), Some(TableBuilderError::ValueTooLong(0xCB)));
+    }
+
+    #[test]
+    fn it_errs_on_a_byte_assigned_twice() {
+        let result = HuffmanTableBuilder::new()
+            .entry(0xA0, 0b01, 2)
+            .entry(0xA0, 0b10, 2)
+            .build();
+
+        assert_eq!(result.err(), Some(TableBuilderError::DuplicateByte(0xA0)));
+    }
+
+    #[test]
+    fn it_errs_on_a_bit_count_over_32() {
+        let result = HuffmanTableBuilder::new().entry(0x01, 0b1, 33).build();
+
+        assert_eq!(result.err(), Some(TableBuilderError::InvalidBitCount(0x01)));
+    }
+
+    #[test]
+    fn it_returns_the_first_error() {
+        let result = HuffmanTableBuilder::new()
+            .entry(0xA0, 0b111, 2)
+            .entry(0xA0, 0b10, 2)
+            .build();
+
+        assert_eq!(result.err(), Some(TableBuilderError::ValueTooLong(0xA0)));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::huffman_table::HuffmanTable;
+
+// the codes that differ from a base table, as (byte, value, bit_count) entries
+#[derive(Debug, PartialEq, Eq)]
+pub struct TableDiff {
+    pub entries: Vec<(u8, u32, u8)>,
+}
+
+impl HuffmanTable {
+    pub fn diff(&self, base: &HuffmanTable) -> TableDiff {
+        let entries = (0..=255)
+            .filter(|&byte| {
+                self.values[byte as usize] != base.values[byte as usize]
+                    || self.bit_counts[byte as usize] != base.bit_counts[byte as usize]
+            })
+            .map(|byte| {
+                (
+                    byte,
+                    self.values[byte as usize],
+                    self.bit_counts[byte as usize],
+                )
+            })
+            .collect();
+
+        TableDiff { entries }
+    }
+
+    pub fn apply_diff(base: &HuffmanTable, diff: &TableDiff) -> HuffmanTable {
+        let mut values = base.values;
+        let mut bit_counts = base.bit_counts;
+
+        for &(byte, value, bit_count) in &diff.entries {
+            values[byte as usize] = value;
+            bit_counts[byte as usize] = bit_count;
+        }
+
+        HuffmanTable { values, bit_counts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base_table() -> HuffmanTable {
+        let mut values: [u32; 256] = [0; 256];
+        let mut bit_counts: [u8; 256] = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x02] = 0b11;
+        bit_counts[0x02] = 2;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_records_only_the_codes_that_changed() {
+        let base = base_table();
+
+        let mut values = base.values;
+        let mut bit_counts = base.bit_counts;
+
+        values[0x02] = 0b110;
+        bit_counts[0x02] = 3;
+
+        values[0x03] = 0b111;
+        bit_counts[0x03] = 3;
+
+        let target = HuffmanTable { values, bit_counts };
+
+        assert_eq!(
+            target.diff(&base),
+            TableDiff {
+                entries: vec![(0x02, 0b110, 3), (0x03, 0b111, 3)]
+            }
+        );
+    }
+
+    #[test]
+    fn it_reconstructs_the_target_table_by_applying_the_diff_to_the_base() {
+        let base = base_table();
+
+        let mut values = base.values;
+        let mut bit_counts = base.bit_counts;
+
+        values[0x00] = 0b00;
+        bit_counts[0x00] = 2;
+
+        values[0x01] = 0b0;
+        bit_counts[0x01] = 0;
+
+        values[0xFF] = 0b01;
+        bit_counts[0xFF] = 2;
+
+        let target = HuffmanTable { values, bit_counts };
+
+        let reconstructed = HuffmanTable::apply_diff(&base, &target.diff(&base));
+
+        assert_eq!(reconstructed.values, target.values);
+        assert_eq!(reconstructed.bit_counts, target.bit_counts);
+    }
+
+    #[test]
+    fn it_keeps_the_decoder_in_sync_with_adapted_tables_through_their_diffs() {
+        use crate::frequencies::count_frequencies;
+        use crate::Huffman;
+
+        let messages: [&[u8]; 4] = [
+            b"GET /index.html HTTP/1.1",
+            b"GET /about.html HTTP/1.1",
+            b"POST /login HTTP/1.1",
+            b"GET /index.html?page=2 HTTP/1.1",
+        ];
+
+        let mut encoder_table = HuffmanTable::from_bytes(messages[0]);
+        let mut decoder_table = HuffmanTable::from_bytes(messages[0]);
+
+        for message in &messages[1..] {
+            let adapted = encoder_table.adapt(&count_frequencies(message), 0.5);
+            let diff = adapted.diff(&encoder_table);
+
+            let mut encoder = Huffman::new(adapted, None);
+            let mut compressed = Vec::new();
+            encoder.compress(message, &mut compressed).unwrap();
+
+            // the decoder only receives the diff and the compressed message
+            let decoder = Huffman::new(HuffmanTable::apply_diff(&decoder_table, &diff), None);
+            let mut decompressed = Vec::new();
+            decoder
+                .decompress(&compressed, Some(message.len()), &mut decompressed)
+                .unwrap();
+
+            assert_eq!(decompressed, *message);
+
+            (encoder_table, decoder_table) = (encoder.table, decoder.table);
+        }
+    }
+
+    #[test]
+    fn it_returns_an_empty_diff_for_identical_tables() {
+        assert!(base_table().diff(&base_table()).entries.is_empty());
+    }
+}
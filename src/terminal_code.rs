@@ -1,4 +1,166 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::huffman_table::HuffmanTable;
+
 pub struct TerminalCode {
     pub bit_count: u8,
     pub value: u32,
 }
+
+impl TerminalCode {
+    // the terminal code must not be equal to, a prefix of, or prefixed by any byte's code,
+    // otherwise a decoder can't tell the end of the data apart from that byte
+    pub fn validate(&self, table: &HuffmanTable) -> Result<(), TerminalCodeError> {
+        if self.bit_count == 0 || self.bit_count > 32 {
+            return Err(TerminalCodeError::InvalidBitCount(self.bit_count));
+        }
+
+        for byte in 0..=255 {
+            let value = table.get_compressed_value(byte);
+            let bit_count = table.get_compressed_value_bit_count(byte);
+
+            if bit_count == 0 {
+                continue;
+            }
+
+            let conflicts = if bit_count <= self.bit_count {
+                is_prefix(value, bit_count, self.value, self.bit_count)
+            } else {
+                is_prefix(self.value, self.bit_count, value, bit_count)
+            };
+
+            if conflicts {
+                return Err(TerminalCodeError::ConflictsWithByte(byte));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_prefix(short_value: u32, short_bit_count: u8, long_value: u32, long_bit_count: u8) -> bool {
+    (long_value as u64 >> (long_bit_count - short_bit_count)) == short_value as u64
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TerminalCodeError {
+    ConflictsWithByte(u8),
+    // a terminal code has to be between 1 and 32 bits long
+    InvalidBitCount(u8),
+}
+
+impl fmt::Display for TerminalCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerminalCodeError::ConflictsWithByte(byte) => write!(
+                f,
+                "terminal code conflicts with the code for byte 0x{:02X}",
+                byte
+            ),
+            TerminalCodeError::InvalidBitCount(bit_count) => write!(
+                f,
+                "terminal code bit count {} is not between 1 and 32",
+                bit_count
+            ),
+        }
+    }
+}
+
+impl Error for TerminalCodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0x00] = 0b0;
+        bit_counts[0x00] = 1;
+
+        values[0x01] = 0b10;
+        bit_counts[0x01] = 2;
+
+        values[0x02] = 0b110;
+        bit_counts[0x02] = 3;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_accepts_a_terminal_code_that_is_prefix_free_against_the_table() {
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        assert_eq!(terminal_code.validate(&example_table()), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_a_terminal_code_equal_to_a_byte_code() {
+        let terminal_code = TerminalCode {
+            bit_count: 2,
+            value: 0b10,
+        };
+
+        assert_eq!(
+            terminal_code.validate(&example_table()),
+            Err(TerminalCodeError::ConflictsWithByte(0x01))
+        );
+    }
+
+    #[test]
+    fn it_rejects_a_terminal_code_that_is_a_prefix_of_a_byte_code() {
+        let terminal_code = TerminalCode {
+            bit_count: 2,
+            value: 0b11,
+        };
+
+        assert_eq!(
+            terminal_code.validate(&example_table()),
+            Err(TerminalCodeError::ConflictsWithByte(0x02))
+        );
+    }
+
+    #[test]
+    fn it_rejects_a_terminal_code_prefixed_by_a_byte_code() {
+        let terminal_code = TerminalCode {
+            bit_count: 4,
+            value: 0b0111,
+        };
+
+        assert_eq!(
+            terminal_code.validate(&example_table()),
+            Err(TerminalCodeError::ConflictsWithByte(0x00))
+        );
+    }
+
+    #[test]
+    fn it_rejects_a_terminal_code_with_no_bits() {
+        let terminal_code = TerminalCode {
+            bit_count: 0,
+            value: 0,
+        };
+
+        assert_eq!(
+            terminal_code.validate(&example_table()),
+            Err(TerminalCodeError::InvalidBitCount(0))
+        );
+    }
+
+    #[test]
+    fn it_rejects_a_terminal_code_longer_than_32_bits() {
+        let terminal_code = TerminalCode {
+            bit_count: 33,
+            value: u32::MAX,
+        };
+
+        assert_eq!(
+            terminal_code.validate(&example_table()),
+            Err(TerminalCodeError::InvalidBitCount(33))
+        );
+    }
+}
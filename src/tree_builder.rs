@@ -0,0 +1,105 @@
+use crate::huffman_table::HuffmanTable;
+
+// a strategy for building a table from byte frequencies, see Huffman::compress_optimal
+pub trait TreeBuilder {
+    fn build(&self, freqs: &[u64; 256]) -> HuffmanTable;
+}
+
+// the optimal table, as built by HuffmanTable::from_frequencies
+pub struct OptimalBuilder;
+
+impl TreeBuilder for OptimalBuilder {
+    fn build(&self, freqs: &[u64; 256]) -> HuffmanTable {
+        HuffmanTable::from_frequencies(freqs)
+    }
+}
+
+// the optimal table with no code longer than max_bits. panics if max_bits is too small to give
+// every byte in freqs a code
+pub struct LengthLimitedBuilder {
+    pub max_bits: u8,
+}
+
+impl TreeBuilder for LengthLimitedBuilder {
+    fn build(&self, freqs: &[u64; 256]) -> HuffmanTable {
+        HuffmanTable::length_limited_from_frequencies(freqs, self.max_bits)
+    }
+}
+
+// the same table whatever the frequencies, e.g. one agreed on ahead of time so it never needs
+// sending. bytes without a code in it can't be compressed
+pub struct StaticBuilder {
+    pub table: HuffmanTable,
+}
+
+impl TreeBuilder for StaticBuilder {
+    fn build(&self, _freqs: &[u64; 256]) -> HuffmanTable {
+        HuffmanTable {
+            values: self.table.values,
+            bit_counts: self.table.bit_counts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode_tree::DecodeTree;
+    use crate::frequencies::count_frequencies;
+
+    fn geometric_frequencies() -> [u64; 256] {
+        let mut freqs = [0; 256];
+        for (byte, freq) in freqs.iter_mut().enumerate().take(30) {
+            *freq = 1 << byte;
+        }
+        freqs
+    }
+
+    #[test]
+    fn it_builds_valid_tables_with_each_builder() {
+        let freqs = geometric_frequencies();
+        let builders: [&dyn TreeBuilder; 3] = [
+            &OptimalBuilder,
+            &LengthLimitedBuilder { max_bits: 10 },
+            &StaticBuilder {
+                table: HuffmanTable::from_bytes(&(0..=255).collect::<Vec<u8>>()),
+            },
+        ];
+
+        let max_bit_counts: Vec<u8> = builders
+            .iter()
+            .map(|builder| {
+                let table = builder.build(&freqs);
+                assert!(DecodeTree::new(&table, None).is_ok());
+                assert!(table.bit_counts.iter().take(30).all(|&b| b > 0));
+
+                *table.bit_counts.iter().max().unwrap()
+            })
+            .collect();
+
+        assert_eq!(max_bit_counts, [29, 10, 8]);
+    }
+
+    #[test]
+    fn it_builds_the_same_table_with_the_optimal_builder_as_from_frequencies() {
+        let freqs = count_frequencies(b"it was the best of times, it was the worst of times");
+
+        assert_eq!(
+            OptimalBuilder.build(&freqs).bit_counts,
+            HuffmanTable::from_frequencies(&freqs).bit_counts
+        );
+    }
+
+    #[test]
+    fn it_ignores_the_frequencies_with_the_static_builder() {
+        let table = HuffmanTable::from_bytes(b"abracadabra");
+        let builder = StaticBuilder {
+            table: HuffmanTable::from_bytes(b"abracadabra"),
+        };
+
+        let built = builder.build(&geometric_frequencies());
+
+        assert_eq!(built.values, table.values);
+        assert_eq!(built.bit_counts, table.bit_counts);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::wide_table::WideHuffmanTable;
+
+// the most bits the root of a table can be indexed by
+const MAX_ROOT_BITS: u8 = 16;
+
+#[derive(Clone, Copy)]
+enum Entry {
+    Empty,
+    // the symbol and the length of its whole code
+    Symbol(u16, u8),
+    // the codes longer than the root that start with this entry's bits, in a table of
+    // 1 << bits entries from offset on
+    SubTable { offset: u32, bits: u8 },
+}
+
+// a two-level lookup table for a wide table, like zlib's inflate tables. the root is indexed by
+// the first root_bits of a code, and codes longer than that get a sub-table for each distinct
+// root prefix, only as big as the longest code under it needs. a sparse alphabet with a few long
+// codes then takes far less memory than one flat table as wide as the longest code
+pub struct WideDecodeTable {
+    root_bits: u8,
+    max_bits: u8,
+    sub_table_count: usize,
+    entries: Vec<Entry>,
+}
+
+impl WideDecodeTable {
+    // root_bits must be between 1 and 16. the table's codes must be prefix free, as checked by
+    // WideDecodeTree::new
+    pub fn from_wide(table: &WideHuffmanTable, root_bits: u8) -> WideDecodeTable {
+        assert!(
+            (1..=MAX_ROOT_BITS).contains(&root_bits),
+            "root_bits must be between 1 and {}",
+            MAX_ROOT_BITS
+        );
+
+        let codes = || {
+            (0..table.symbol_count()).filter_map(|symbol| {
+                let symbol = symbol as u16;
+                let bit_count = table.get_compressed_value_bit_count(symbol);
+                let value = table.get_compressed_value(symbol);
+                (bit_count > 0).then_some((symbol, value, bit_count))
+            })
+        };
+
+        let max_bits = codes()
+            .map(|(_, _, bit_count)| bit_count)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let root_bits = root_bits.min(max_bits);
+
+        // the longest code under each root prefix that needs a sub-table
+        let mut sub_table_bits = vec![0; 1 << root_bits];
+        for (_, value, bit_count) in codes().filter(|&(_, _, b)| b > root_bits) {
+            let prefix = (value >> (bit_count - root_bits)) as usize;
+            sub_table_bits[prefix] = sub_table_bits[prefix].max(bit_count - root_bits);
+        }
+
+        let mut entries = vec![Entry::Empty; 1 << root_bits];
+        let mut sub_table_count = 0;
+
+        for (prefix, &bits) in sub_table_bits.iter().enumerate().filter(|(_, &b)| b > 0) {
+            entries[prefix] = Entry::SubTable {
+                offset: entries.len() as u32,
+                bits,
+            };
+            entries.resize(entries.len() + (1 << bits), Entry::Empty);
+            sub_table_count += 1;
+        }
+
+        for (symbol, value, bit_count) in codes() {
+            let entry = Entry::Symbol(symbol, bit_count);
+
+            if bit_count <= root_bits {
+                let free_bits = root_bits - bit_count;
+                let first = (value as usize) << free_bits;
+                entries[first..first + (1 << free_bits)].fill(entry);
+            } else {
+                let prefix = (value >> (bit_count - root_bits)) as usize;
+                let Entry::SubTable { offset, bits } = entries[prefix] else {
+                    unreachable!("every long code's prefix has a sub-table");
+                };
+
+                let suffix_bits = bit_count - root_bits;
+                let suffix = value as usize & ((1 << suffix_bits) - 1);
+                let free_bits = bits - suffix_bits;
+                let first = offset as usize + (suffix << free_bits);
+                entries[first..first + (1 << free_bits)].fill(entry);
+            }
+        }
+
+        WideDecodeTable {
+            root_bits,
+            max_bits,
+            sub_table_count,
+            entries,
+        }
+    }
+
+    // how many bits lookup needs to see, the length of the longest code
+    pub fn window_bits(&self) -> u8 {
+        self.max_bits
+    }
+
+    pub fn sub_table_count(&self) -> usize {
+        self.sub_table_count
+    }
+
+    // the heap bytes taken up by the root and sub-table entries
+    pub fn memory_bytes(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<Entry>()
+    }
+
+    // the symbol and bit count of the code at the start of the window_bits wide window, or None
+    // if the window doesn't start with a code
+    pub fn lookup(&self, window: u32) -> Option<(u16, u8)> {
+        let root_index = window >> (self.max_bits - self.root_bits);
+
+        let entry = match self.entries[root_index as usize] {
+            Entry::SubTable { offset, bits } => {
+                let shift = self.max_bits - self.root_bits - bits;
+                let index = (window >> shift) & ((1 << bits) - 1);
+                self.entries[offset as usize + index as usize]
+            }
+            entry => entry,
+        };
+
+        match entry {
+            Entry::Symbol(symbol, bit_count) => Some((symbol, bit_count)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::WideCompressor;
+    use crate::wide_decompressor::{WideDecodeTree, WideDecompressor};
+
+    // 400 symbols spread across the whole u16 range, with a few very common ones
+    fn sparse_src() -> Vec<u16> {
+        let mut src = Vec::new();
+        for i in 0..400u32 {
+            let symbol = (i * 163) as u16;
+            let count = if i < 4 { 2000 } else { 1 + i % 3 };
+            src.extend(std::iter::repeat_n(symbol, count as usize));
+        }
+        src
+    }
+
+    fn table_for(src: &[u16]) -> WideHuffmanTable {
+        let mut counts = vec![0; 1 << 16];
+        for &symbol in src {
+            counts[symbol as usize] += 1;
+        }
+        WideHuffmanTable::from_frequencies(&counts)
+    }
+
+    #[test]
+    fn it_looks_up_every_code_in_a_sparse_wide_table() {
+        let table = table_for(&sparse_src());
+        let decode_table = WideDecodeTable::from_wide(&table, 8);
+        let window_bits = decode_table.window_bits();
+
+        assert!(window_bits > 8);
+
+        for symbol in 0..table.symbol_count() {
+            let symbol = symbol as u16;
+            let bit_count = table.get_compressed_value_bit_count(symbol);
+
+            if bit_count > 0 {
+                let value = table.get_compressed_value(symbol);
+                let free_bits = window_bits - bit_count;
+
+                // the code followed by ones, then by zeroes
+                for window in [
+                    value << free_bits | ((1 << free_bits) - 1),
+                    value << free_bits,
+                ] {
+                    assert_eq!(decode_table.lookup(window), Some((symbol, bit_count)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_keeps_memory_bounded_with_few_sub_tables() {
+        let table = table_for(&sparse_src());
+        let decode_table = WideDecodeTable::from_wide(&table, 8);
+        let flat_bytes = (1 << decode_table.window_bits()) * std::mem::size_of::<Entry>();
+
+        // only the root prefixes that the long codes share get a sub-table
+        assert!(decode_table.sub_table_count() > 0);
+        assert!(decode_table.sub_table_count() < 1 << 8);
+        assert!(decode_table.memory_bytes() * 4 < flat_bytes);
+    }
+
+    #[test]
+    fn it_decodes_the_same_with_and_without_the_decode_table() {
+        let src = sparse_src();
+        let table = table_for(&src);
+        let tree = WideDecodeTree::new(&table).unwrap();
+        let decode_table = WideDecodeTable::from_wide(&table, 8);
+
+        let mut compressor = WideCompressor::new(&table);
+        let mut compressed = Vec::new();
+        for &symbol in &src {
+            compressor.compress_symbol(symbol).unwrap();
+            compressor.drain_into(&mut compressed);
+        }
+        compressor.end();
+        compressor.drain_into(&mut compressed);
+
+        let mut decompressor = WideDecompressor::new(&tree, &compressed)
+            .with_expected_len(src.len())
+            .with_decode_table(&decode_table);
+        let decompressed: Vec<u16> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressor.error(), None);
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_builds_a_table_with_no_codes() {
+        let decode_table = WideDecodeTable::from_wide(&table_for(&[]), 8);
+
+        assert_eq!(decode_table.lookup(0), None);
+        assert_eq!(decode_table.sub_table_count(), 0);
+    }
+}
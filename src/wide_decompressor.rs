@@ -0,0 +1,315 @@
+use crate::decode_tree::DecodeTreeError;
+use crate::decompressor::DecodeError;
+use crate::wide_decode_table::WideDecodeTable;
+use crate::wide_table::WideHuffmanTable;
+
+enum Node {
+    Branch([Option<usize>; 2]),
+    Leaf(u16),
+}
+
+// a binary trie of every code in a wide table, like DecodeTree but with u16 symbols
+pub struct WideDecodeTree {
+    nodes: Vec<Node>,
+}
+
+impl WideDecodeTree {
+    pub fn new(table: &WideHuffmanTable) -> Result<WideDecodeTree, DecodeTreeError> {
+        let mut tree = WideDecodeTree {
+            nodes: vec![Node::Branch([None, None])],
+        };
+
+        for symbol in 0..table.symbol_count() {
+            let symbol = symbol as u16;
+            let bit_count = table.get_compressed_value_bit_count(symbol);
+
+            if bit_count > 0 {
+                tree.insert(table.get_compressed_value(symbol), bit_count, symbol)?;
+            }
+        }
+
+        tree.nodes.shrink_to_fit();
+
+        Ok(tree)
+    }
+
+    fn insert(&mut self, value: u32, bit_count: u8, symbol: u16) -> Result<(), DecodeTreeError> {
+        let conflict = DecodeTreeError::SymbolPrefixConflict(symbol);
+
+        if bit_count > 32 {
+            return Err(conflict);
+        }
+
+        let mut node = self.root();
+
+        for position in (0..bit_count).rev() {
+            let bit = ((value >> position) & 1) as usize;
+
+            let existing = match &self.nodes[node] {
+                Node::Branch(children) => children[bit],
+                Node::Leaf(_) => return Err(conflict),
+            };
+
+            node = match existing {
+                Some(_) if position == 0 => return Err(conflict),
+                Some(child) => child,
+                None => {
+                    let child = self.nodes.len();
+
+                    if let Node::Branch(children) = &mut self.nodes[node] {
+                        children[bit] = Some(child);
+                    }
+
+                    self.nodes.push(if position == 0 {
+                        Node::Leaf(symbol)
+                    } else {
+                        Node::Branch([None, None])
+                    });
+
+                    child
+                }
+            };
+        }
+
+        Ok(())
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn child(&self, node: usize, bit: u8) -> Option<usize> {
+        match &self.nodes[node] {
+            Node::Branch(children) => children[bit as usize],
+            Node::Leaf(_) => None,
+        }
+    }
+
+    pub fn symbol(&self, node: usize) -> Option<u16> {
+        match &self.nodes[node] {
+            Node::Branch(_) => None,
+            Node::Leaf(symbol) => Some(*symbol),
+        }
+    }
+}
+
+// decodes u16 symbols produced by a WideCompressor using the same table. like Decompressor
+// without a terminal code, decoding stops after expected_len symbols if one was given, or when
+// the input runs out, where fewer than eight zero bits left over are the padding from
+// WideCompressor::end
+pub struct WideDecompressor<'a> {
+    tree: &'a WideDecodeTree,
+    decode_table: Option<&'a WideDecodeTable>,
+    src: &'a [u8],
+    bit_offset: u64,
+    expected_len: Option<usize>,
+    decompressed_len: usize,
+    finished: bool,
+    error: Option<DecodeError>,
+}
+
+impl<'a> WideDecompressor<'a> {
+    pub fn new(tree: &'a WideDecodeTree, src: &'a [u8]) -> WideDecompressor<'a> {
+        WideDecompressor {
+            tree,
+            decode_table: None,
+            src,
+            bit_offset: 0,
+            expected_len: None,
+            decompressed_len: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    pub fn with_expected_len(mut self, expected_len: usize) -> WideDecompressor<'a> {
+        self.expected_len = Some(expected_len);
+        self
+    }
+
+    // decodes each code with one or two lookups in decode_table, which must be built from the
+    // same table as the tree, falling back to walking the tree near the end of the input
+    pub fn with_decode_table(mut self, decode_table: &'a WideDecodeTable) -> WideDecompressor<'a> {
+        self.decode_table = Some(decode_table);
+        self
+    }
+
+    pub fn error(&self) -> Option<&DecodeError> {
+        self.error.as_ref()
+    }
+
+    // the offset of the next code to decode, e.g. for reporting where a symbol that's out of
+    // place for the caller's format starts
+    pub fn bit_offset(&self) -> u64 {
+        self.bit_offset
+    }
+
+    fn read_bit(&mut self) -> Option<u8> {
+        let byte = *self.src.get((self.bit_offset / 8) as usize)?;
+        let bit = (byte >> (7 - self.bit_offset % 8)) & 1;
+
+        self.bit_offset += 1;
+
+        Some(bit)
+    }
+
+    fn look_up(&self, decode_table: &WideDecodeTable) -> Option<(u16, u8)> {
+        let window_bits = decode_table.window_bits() as u64;
+
+        // the eight bytes the window falls within, with zeroes past the end of the input
+        let first_byte = (self.bit_offset / 8) as usize;
+        let mut bytes = [0; 8];
+        for (byte, &src_byte) in bytes.iter_mut().zip(self.src[first_byte..].iter()) {
+            *byte = src_byte;
+        }
+
+        let window = u64::from_be_bytes(bytes) << (self.bit_offset % 8) >> (64 - window_bits);
+        let (symbol, bit_count) = decode_table.lookup(window as u32)?;
+
+        // a code running into the bits past the end is left for the tree walk to report
+        if self.bit_offset + bit_count as u64 > self.src.len() as u64 * 8 {
+            return None;
+        }
+
+        Some((symbol, bit_count))
+    }
+
+    fn is_padding(&self, bit_offset: u64) -> bool {
+        if self.expected_len.is_some() {
+            return false;
+        }
+
+        let total_bits = self.src.len() as u64 * 8;
+        let padding_bits = total_bits - bit_offset;
+
+        padding_bits == 0
+            || padding_bits < 8 && self.src[self.src.len() - 1] & (0xFF >> (8 - padding_bits)) == 0
+    }
+
+    fn end(&mut self, code_offset: u64, error: DecodeError) {
+        self.finished = true;
+
+        if !self.is_padding(code_offset) {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl Iterator for WideDecompressor<'_> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        if self.finished || self.expected_len == Some(self.decompressed_len) {
+            return None;
+        }
+
+        if let Some(decode_table) = self.decode_table {
+            if let Some((symbol, bit_count)) = self.look_up(decode_table) {
+                self.bit_offset += bit_count as u64;
+                self.decompressed_len += 1;
+                return Some(symbol);
+            }
+        }
+
+        let code_offset = self.bit_offset;
+        let mut node = self.tree.root();
+
+        loop {
+            let Some(bit) = self.read_bit() else {
+                let error = DecodeError::UnexpectedEnd {
+                    bit_offset: code_offset,
+                };
+                self.end(code_offset, error);
+                return None;
+            };
+
+            let Some(child) = self.tree.child(node, bit) else {
+                let error = DecodeError::InvalidCode {
+                    bit_offset: code_offset,
+                };
+                self.end(code_offset, error);
+                return None;
+            };
+            node = child;
+
+            if let Some(symbol) = self.tree.symbol(node) {
+                self.decompressed_len += 1;
+                return Some(symbol);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compressor::WideCompressor;
+
+    fn compress(table: &WideHuffmanTable, src: &[u16]) -> Vec<u8> {
+        let mut compressor = WideCompressor::new(table);
+        let mut output = Vec::new();
+
+        for &symbol in src {
+            compressor.compress_symbol(symbol).unwrap();
+            compressor.drain_into(&mut output);
+        }
+
+        compressor.end();
+        compressor.drain_into(&mut output);
+
+        output
+    }
+
+    fn table_for(src: &[u16]) -> WideHuffmanTable {
+        let mut counts = vec![0; *src.iter().max().unwrap() as usize + 1];
+        for &symbol in src {
+            counts[symbol as usize] += 1;
+        }
+        WideHuffmanTable::from_frequencies(&counts)
+    }
+
+    #[test]
+    fn it_round_trips_symbols_wider_than_a_byte() {
+        let src: Vec<u16> = (0..3000u32).map(|i| ((i * i) % 40_000) as u16).collect();
+        let table = table_for(&src);
+        let tree = WideDecodeTree::new(&table).unwrap();
+
+        let compressed = compress(&table, &src);
+        let mut decompressor =
+            WideDecompressor::new(&tree, &compressed).with_expected_len(src.len());
+        let decompressed: Vec<u16> = decompressor.by_ref().collect();
+
+        assert_eq!(decompressor.error(), None);
+        assert_eq!(decompressed, src);
+    }
+
+    #[test]
+    fn it_errs_on_truncated_input() {
+        let src: Vec<u16> = (0..100).map(|i| i * 300).collect();
+        let table = table_for(&src);
+        let tree = WideDecodeTree::new(&table).unwrap();
+
+        let compressed = compress(&table, &src);
+        let mut decompressor = WideDecompressor::new(&tree, &compressed[..compressed.len() - 2])
+            .with_expected_len(src.len());
+        decompressor.by_ref().for_each(drop);
+
+        assert!(matches!(
+            decompressor.error(),
+            Some(DecodeError::UnexpectedEnd { .. })
+        ));
+    }
+
+    #[test]
+    fn it_errors_when_two_symbols_share_a_code() {
+        let table = WideHuffmanTable {
+            values: vec![0b0, 0b10, 0b10],
+            bit_counts: vec![1, 2, 2],
+        };
+
+        assert_eq!(
+            WideDecodeTree::new(&table).err(),
+            Some(DecodeTreeError::SymbolPrefixConflict(2))
+        );
+    }
+}
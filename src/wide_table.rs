@@ -0,0 +1,95 @@
+use crate::canonical::canonical_values;
+use crate::huffman_table::code_lengths;
+
+// the most symbols a wide table can have codes for, one for every u16
+const MAX_SYMBOL_COUNT: usize = 1 << 16;
+
+// a table over u16 symbols rather than bytes, for alphabets too big for HuffmanTable. values and
+// bit_counts are indexed by symbol, and symbols past their end have no code
+pub struct WideHuffmanTable {
+    pub values: Vec<u32>,
+    pub bit_counts: Vec<u8>,
+}
+
+impl WideHuffmanTable {
+    // builds an optimal prefix free table with canonical codes for symbols 0 up to counts.len(),
+    // which can be at most 65536. symbols with a frequency of 0 get no code
+    pub fn from_frequencies(counts: &[u64]) -> WideHuffmanTable {
+        assert!(
+            counts.len() <= MAX_SYMBOL_COUNT,
+            "a wide table has at most {} symbols",
+            MAX_SYMBOL_COUNT
+        );
+
+        let bit_counts = code_lengths(counts);
+        let values = canonical_values(&bit_counts);
+
+        WideHuffmanTable { values, bit_counts }
+    }
+
+    pub fn symbol_count(&self) -> usize {
+        self.bit_counts.len()
+    }
+
+    pub fn get_compressed_value(&self, symbol: u16) -> u32 {
+        self.values.get(symbol as usize).copied().unwrap_or(0)
+    }
+
+    pub fn get_compressed_value_bit_count(&self, symbol: u16) -> u8 {
+        self.bit_counts.get(symbol as usize).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_counts() -> Vec<u64> {
+        (0..5000).map(|symbol| (symbol * 7919) % 97).collect()
+    }
+
+    #[test]
+    fn it_builds_codes_for_symbols_beyond_a_byte() {
+        let table = WideHuffmanTable::from_frequencies(&example_counts());
+
+        assert_eq!(table.symbol_count(), 5000);
+        assert!(table.get_compressed_value_bit_count(4999) > 0);
+        assert_eq!(table.get_compressed_value_bit_count(0), 0);
+    }
+
+    #[test]
+    fn it_gives_symbols_past_the_end_of_the_table_no_code() {
+        let table = WideHuffmanTable::from_frequencies(&example_counts());
+
+        assert_eq!(table.get_compressed_value(5000), 0);
+        assert_eq!(table.get_compressed_value_bit_count(u16::MAX), 0);
+    }
+
+    #[test]
+    fn it_builds_a_complete_prefix_free_code() {
+        let table = WideHuffmanTable::from_frequencies(&example_counts());
+
+        // the kraft sum of a complete prefix free code is exactly 1
+        let kraft_sum: f64 = table
+            .bit_counts
+            .iter()
+            .filter(|&&bit_count| bit_count > 0)
+            .map(|&bit_count| 0.5f64.powi(bit_count as i32))
+            .sum();
+
+        assert!((kraft_sum - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_builds_the_same_codes_as_a_byte_table_for_the_same_counts() {
+        let counts: Vec<u64> = (0..256).map(|byte| (byte * 31) % 17).collect();
+        let mut byte_counts = [0; 256];
+        byte_counts.copy_from_slice(&counts);
+
+        let table = WideHuffmanTable::from_frequencies(&counts);
+        let byte_table = crate::huffman_table::HuffmanTable256::from_frequencies(&byte_counts);
+
+        assert_eq!(table.values, byte_table.values);
+        assert_eq!(table.bit_counts, byte_table.bit_counts);
+    }
+}
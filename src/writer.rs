@@ -0,0 +1,252 @@
+use std::io::{self, Write};
+
+use crate::compressor::Compressor;
+use crate::huffman_table::HuffmanTable;
+use crate::terminal_code::TerminalCode;
+
+// compresses bytes straight into a writer, so the compressed output never has to be held in memory.
+// complete bytes are written as soon as the compressor has them, or once flush_threshold of them
+// have built up if with_flush_threshold asks for larger writes
+pub struct HuffmanWriter<'a, W: Write> {
+    inner: W,
+    compressor: Compressor<'a>,
+    terminal_code: Option<&'a TerminalCode>,
+    flush_threshold: u8,
+    // complete bytes drained from the compressor but not yet written
+    pending: Vec<u8>,
+}
+
+impl<'a, W: Write> HuffmanWriter<'a, W> {
+    pub fn new(
+        inner: W,
+        table: &'a HuffmanTable,
+        terminal_code: Option<&'a TerminalCode>,
+    ) -> HuffmanWriter<'a, W> {
+        HuffmanWriter {
+            inner,
+            compressor: Compressor::new(table),
+            terminal_code,
+            flush_threshold: 1,
+            pending: Vec::new(),
+        }
+    }
+
+    // holds complete bytes back until at least flush_threshold of them are ready, so the inner
+    // writer sees fewer, larger writes. 0 is treated the same as the default of 1, writing bytes
+    // as soon as they're complete
+    pub fn with_flush_threshold(mut self, flush_threshold: u8) -> Self {
+        self.flush_threshold = flush_threshold;
+        self.pending = Vec::with_capacity(flush_threshold as usize + 8);
+        self
+    }
+
+    // a byte the table can't compress is reported as an InvalidInput error
+    pub fn write_byte(&mut self, byte: u8) -> io::Result<()> {
+        self.compressor
+            .compress_byte(byte)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        self.flush_compressed_bytes(false)
+    }
+
+    // appends the terminal code, pads to a byte boundary and writes what's left, returning the
+    // inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(terminal_code) = self.terminal_code {
+            self.compressor
+                .append_terminal_code(terminal_code)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        }
+
+        self.compressor.end();
+        self.flush_compressed_bytes(true)?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+
+    // writes the pending bytes once there are flush_threshold of them, or whatever is pending
+    // when finishing
+    fn flush_compressed_bytes(&mut self, finishing: bool) -> io::Result<()> {
+        self.compressor.drain_into(&mut self.pending);
+
+        if self.pending.is_empty()
+            || (!finishing && self.pending.len() < self.flush_threshold as usize)
+        {
+            return Ok(());
+        }
+
+        self.inner.write_all(&self.pending)?;
+        self.pending.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Huffman;
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // records the length of every write it's given
+    struct RecordingWriter {
+        bytes: Vec<u8>,
+        write_lens: Vec<usize>,
+    }
+
+    impl Write for RecordingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.bytes.extend_from_slice(buf);
+            self.write_lens.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn table() -> HuffmanTable {
+        let mut values = [0; 256];
+        let mut bit_counts = [0; 256];
+
+        values[0xA0] = 0b01;
+        bit_counts[0xA0] = 2;
+
+        values[0xCB] = 0b101;
+        bit_counts[0xCB] = 3;
+
+        values[0xB3] = 0b1101_0110_0101;
+        bit_counts[0xB3] = 12;
+
+        HuffmanTable { values, bit_counts }
+    }
+
+    #[test]
+    fn it_writes_the_same_output_as_compress() {
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let src = [0xA0, 0xCB, 0xB3, 0xB3, 0xA0, 0xCB];
+
+        let table = table();
+
+        let mut writer = HuffmanWriter::new(Vec::new(), &table, Some(&terminal_code));
+        for &byte in &src {
+            writer.write_byte(byte).unwrap();
+        }
+        let output = writer.finish().unwrap();
+
+        let mut huffman = Huffman::new(table, Some(terminal_code));
+        let mut expected_output = Vec::new();
+        huffman.compress(&src, &mut expected_output).unwrap();
+
+        assert_eq!(output, expected_output);
+    }
+
+    #[test]
+    fn it_writes_complete_bytes_before_finishing() {
+        let table = table();
+        let mut output = Vec::new();
+
+        let mut writer = HuffmanWriter::new(&mut output, &table, None);
+        writer.write_byte(0xB3).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(output, vec![0b1101_0110, 0b0101_0000]);
+    }
+
+    #[test]
+    fn it_writes_the_same_output_with_a_flush_threshold() {
+        let terminal_code = TerminalCode {
+            bit_count: 3,
+            value: 0b111,
+        };
+
+        let src = [
+            0xA0, 0xCB, 0xB3, 0xB3, 0xA0, 0xCB, 0xB3, 0xB3, 0xB3, 0xCB, 0xB3,
+        ];
+
+        let table = table();
+
+        let mut writer = HuffmanWriter::new(Vec::new(), &table, Some(&terminal_code));
+        let mut threshold_writer =
+            HuffmanWriter::new(Vec::new(), &table, Some(&terminal_code)).with_flush_threshold(4);
+        for &byte in &src {
+            writer.write_byte(byte).unwrap();
+            threshold_writer.write_byte(byte).unwrap();
+        }
+
+        assert_eq!(threshold_writer.finish().unwrap(), writer.finish().unwrap());
+    }
+
+    #[test]
+    fn it_holds_bytes_back_until_the_flush_threshold() {
+        let table = table();
+        let recorder = RecordingWriter {
+            bytes: Vec::new(),
+            write_lens: Vec::new(),
+        };
+
+        // each 0xB3 completes a byte and a half
+        let mut writer = HuffmanWriter::new(recorder, &table, None).with_flush_threshold(4);
+        for _ in 0..3 {
+            writer.write_byte(0xB3).unwrap();
+        }
+        let recorder = writer.finish().unwrap();
+
+        assert_eq!(recorder.write_lens, vec![4, 1]);
+        assert_eq!(recorder.bytes.len(), 5);
+    }
+
+    #[test]
+    fn it_writes_bytes_as_soon_as_they_are_complete_with_a_flush_threshold_of_0() {
+        let table = table();
+        let recorder = RecordingWriter {
+            bytes: Vec::new(),
+            write_lens: Vec::new(),
+        };
+
+        let mut writer = HuffmanWriter::new(recorder, &table, None).with_flush_threshold(0);
+        for _ in 0..3 {
+            writer.write_byte(0xB3).unwrap();
+        }
+        let recorder = writer.finish().unwrap();
+
+        assert_eq!(recorder.write_lens, vec![1, 2, 1, 1]);
+    }
+
+    #[test]
+    fn it_surfaces_errors_from_the_inner_writer() {
+        let table = table();
+
+        let mut writer = HuffmanWriter::new(FailingWriter, &table, None);
+
+        let err = writer.write_byte(0xB3).unwrap_err();
+        assert_eq!(err.to_string(), "disk full");
+    }
+
+    #[test]
+    fn it_reports_a_byte_without_a_code_as_invalid_input() {
+        let table = table();
+
+        let mut writer = HuffmanWriter::new(Vec::new(), &table, None);
+
+        let err = writer.write_byte(0x00).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,117 @@
+use huffman_compression::{BitOrder, Compressor, Huffman, HuffmanTable, TerminalCode};
+
+// these outputs are frozen. if a change to the compressor makes any of these tests fail then it
+// has changed the compressed format, which is a breaking change for anyone decoding it
+
+fn golden_table() -> HuffmanTable {
+    let mut values = [0; 256];
+    let mut bit_counts = [0; 256];
+
+    values[0x00] = 0b00;
+    bit_counts[0x00] = 2;
+
+    values[0x01] = 0b010;
+    bit_counts[0x01] = 3;
+
+    values[0x02] = 0b011;
+    bit_counts[0x02] = 3;
+
+    values[0x03] = 0b10;
+    bit_counts[0x03] = 2;
+
+    values[0x04] = 0b110;
+    bit_counts[0x04] = 3;
+
+    values[0xFF] = 0b11100;
+    bit_counts[0xFF] = 5;
+
+    HuffmanTable { values, bit_counts }
+}
+
+fn golden_terminal_code() -> TerminalCode {
+    TerminalCode {
+        bit_count: 4,
+        value: 0b1111,
+    }
+}
+
+fn compress_lsb_first(src: &[u8], terminal_code: Option<TerminalCode>) -> Vec<u8> {
+    let table = golden_table();
+    let mut compressor = Compressor::with_bit_order(&table, BitOrder::LsbFirst);
+    let mut output = Vec::new();
+
+    for &byte in src {
+        compressor.compress_byte(byte).unwrap();
+        compressor.drain_into(&mut output);
+    }
+
+    if let Some(terminal_code) = terminal_code {
+        compressor.append_terminal_code(&terminal_code).unwrap();
+    }
+
+    compressor.end();
+    compressor.drain_into(&mut output);
+
+    output
+}
+
+#[test]
+fn test_msb_first_single_byte_output_is_unchanged() {
+    let mut huffman = Huffman::new(golden_table(), None);
+
+    let mut output = Vec::new();
+    huffman.compress(&[0x00, 0x03], &mut output).unwrap();
+
+    assert_eq!(output, vec![0x20]);
+}
+
+#[test]
+fn test_msb_first_boundary_crossing_output_is_unchanged() {
+    let mut huffman = Huffman::new(golden_table(), Some(golden_terminal_code()));
+
+    let mut output = Vec::new();
+    huffman
+        .compress(&[0xFF, 0x01, 0x02, 0x04, 0x00, 0xFF, 0x03], &mut output)
+        .unwrap();
+
+    assert_eq!(output, vec![0xE2, 0x78, 0xE5, 0xE0]);
+}
+
+#[test]
+fn test_msb_first_exact_byte_output_is_unchanged() {
+    let mut huffman = Huffman::new(golden_table(), None);
+
+    let mut output = Vec::new();
+    huffman
+        .compress(
+            &[0x04, 0x01, 0x03, 0xFF, 0x02, 0x00, 0x01, 0x02],
+            &mut output,
+        )
+        .unwrap();
+
+    assert_eq!(output, vec![0xCA, 0xE3, 0x13]);
+}
+
+#[test]
+fn test_lsb_first_single_byte_output_is_unchanged() {
+    let output = compress_lsb_first(&[0x00, 0x03], None);
+
+    assert_eq!(output, vec![0x04]);
+}
+
+#[test]
+fn test_lsb_first_boundary_crossing_output_is_unchanged() {
+    let output = compress_lsb_first(
+        &[0xFF, 0x01, 0x02, 0x04, 0x00, 0xFF, 0x03],
+        Some(golden_terminal_code()),
+    );
+
+    assert_eq!(output, vec![0x47, 0x1E, 0xA7, 0x07]);
+}
+
+#[test]
+fn test_lsb_first_exact_byte_output_is_unchanged() {
+    let output = compress_lsb_first(&[0x04, 0x01, 0x03, 0xFF, 0x02, 0x00, 0x01, 0x02], None);
+
+    assert_eq!(output, vec![0x53, 0xC7, 0xC8]);
+}
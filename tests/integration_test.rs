@@ -1,4 +1,4 @@
-use huffman_compression::{Huffman, HuffmanTable, TerminalCode};
+use huffman_compression::{Compressor, Huffman, HuffmanTable, TerminalCode};
 
 const SERVUO_HUFFMAN_TABLE_VALUES: [u32; 256] = [
     0x000, 0x01F, 0x022, 0x034, 0x075, 0x028, 0x03B, 0x032, 0x0E0, 0x062, 0x056, 0x079, 0x19D,
@@ -57,7 +57,7 @@ fn test_example_serv_uo_packet_is_compressed_correctly() {
     let src = vec![0xB9, 0x00, 0xFF, 0x92, 0xDB];
     let mut output = Vec::new();
 
-    huffman.compress(src, &mut output);
+    huffman.compress(&src, &mut output).unwrap();
 
     assert_eq!(output, vec![0xB3, 0x0C, 0x59, 0xE4, 0xCB, 0xA0]);
 }
@@ -130,7 +130,46 @@ fn test_example_character_list_serv_uo_packet_is_compressed_correctly() {
 
     let mut output = Vec::new();
 
-    huffman.compress(src, &mut output);
+    huffman.compress(&src, &mut output).unwrap();
 
     assert_eq!(output, expected,);
 }
+
+#[test]
+fn test_pending_bits_are_the_serv_uo_code_not_yet_read_out() {
+    let table = HuffmanTable {
+        values: SERVUO_HUFFMAN_TABLE_VALUES,
+        bit_counts: SERVUO_HUFFMAN_TABLE_BIT_COUNTS,
+    };
+
+    let terminal_code = TerminalCode {
+        bit_count: UO_TERMINAL_CODE_BIT_COUNT,
+        value: UO_TERMINAL_CODE_VALUE,
+    };
+
+    let mut compressor = Compressor::new(&table);
+    let mut output = Vec::new();
+    let mut pending_bit_count = 0;
+
+    for byte in [0xB9, 0x00, 0xFF, 0x92, 0xDB] {
+        let value = SERVUO_HUFFMAN_TABLE_VALUES[byte as usize];
+        let bit_count = SERVUO_HUFFMAN_TABLE_BIT_COUNTS[byte as usize];
+
+        compressor.compress_byte(byte).unwrap();
+        compressor.drain_into(&mut output);
+
+        pending_bit_count = (pending_bit_count + bit_count) % 8;
+        let mask = (1 << pending_bit_count.min(bit_count)) - 1;
+
+        assert_eq!(compressor.pending_bit_count(), pending_bit_count);
+        assert_eq!(compressor.peek_pending() & mask, value & mask);
+    }
+
+    compressor.append_terminal_code(&terminal_code).unwrap();
+    compressor.end();
+    compressor.drain_into(&mut output);
+
+    assert_eq!(compressor.pending_bit_count(), 0);
+    assert_eq!(compressor.peek_pending(), 0);
+    assert_eq!(output, vec![0xB3, 0x0C, 0x59, 0xE4, 0xCB, 0xA0]);
+}